@@ -0,0 +1,896 @@
+pub mod expression;
+mod macros;
+pub(crate) mod opcodes;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::image::Image;
+use crate::symbols::SymbolTable;
+use expression::{evaluate, evaluate_defined, is_name, parse_string};
+use macros::{Block, Macro, Recording};
+use opcodes::{Mnemonic, Operands};
+
+const DIRECTIVES: [&str; 17] =
+[
+    "ORG", "EQU", "SET", "DB", "DW", "DS", "END", "MACRO", "ENDM", "REPT", "IRP", "EXITM", "LOCAL", "INCLUDE",
+    "IF", "ELSE", "ENDIF",
+];
+
+/// How deep macros and included files can nest.
+const MAX_NESTING: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SymbolKind
+{
+    Label,
+    Equate,
+    /// Defined with SET, which may change it later on.
+    Variable,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Symbol
+{
+    value: u16,
+    kind:  SymbolKind,
+}
+
+/// A line of source and the file it came from.
+#[derive(Clone, Debug)]
+struct SourceLine
+{
+    file:   Option<Rc<PathBuf>>,
+    number: usize,
+    text:   String,
+}
+
+impl SourceLine
+{
+    /// `file: line N`, or just `line N` for source that did not come from a file.
+    fn location(&self) -> String
+    {
+        match &self.file
+        {
+            Some(file) => format!("{}: line {}", file.display(), self.number),
+            None => format!("line {}", self.number),
+        }
+    }
+}
+
+fn source_lines(source: &str, file: Option<Rc<PathBuf>>) -> Vec<SourceLine>
+{
+    source.lines().enumerate().map(|(i, text)| SourceLine { file: file.clone(), number: i + 1, text: text.to_string() }).collect()
+}
+
+/// Lines being read: a file, or the expansion of a macro, REPT or IRP.
+struct Input
+{
+    lines:        Vec<SourceLine>,
+    next:         usize,
+    /// What was expanded and where, for expansions.
+    expansion:    Option<(String, String)>,
+    /// Conditionals open when the input started, which EXITM goes back to.
+    conditionals: usize,
+}
+
+/// An IF and whether its lines are being assembled.
+struct Conditional
+{
+    active:    bool,
+    /// The IF or ELSE part has been taken, or the whole IF is inside one that was not.
+    taken:     bool,
+    seen_else: bool,
+    start:     String,
+}
+
+/// A line of the listing: the source, where it went and what it assembled to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ListingLine
+{
+    pub line:     usize,
+    pub address:  Option<u16>,
+    /// The value given by EQU or SET.
+    pub value:    Option<u16>,
+    pub bytes:    Vec<u8>,
+    pub source:   String,
+    /// The line came from a macro, REPT or IRP.
+    pub expanded: bool,
+}
+
+/// The output of the assembler.
+#[derive(Clone, Debug, Default)]
+pub struct Assembly
+{
+    pub image:   Image,
+    /// The address after the last byte put out or reserved with DS.
+    pub end:     u32,
+    /// Every symbol with its final value, by address.
+    pub symbols: Vec<(String, u16)>,
+    pub listing: Vec<ListingLine>,
+}
+
+impl Assembly
+{
+    /// The program as a raw binary from its lowest address to the end of the space it
+    /// reserves, gaps filled with zeros.
+    pub fn to_binary(&self) -> Vec<u8>
+    {
+        let mut binary = self.image.to_binary();
+        if let Some(start) = self.image.start()
+        {
+            binary.resize(binary.len().max(self.end.saturating_sub(start as u32) as usize), 0);
+        }
+        binary
+    }
+
+    /// The listing as text: address, up to four bytes a row, line number and source, marked
+    /// with + where it came from a macro.
+    pub fn write_listing(&self) -> String
+    {
+        let mut text = String::new();
+        for line in self.listing.iter()
+        {
+            let address = line.address.map_or("    ".to_string(), |address| format!("{:04X}", address));
+            let first = match line.value
+            {
+                Some(value) => format!("= {:04X}", value),
+                None => hex_bytes(line.bytes.iter().take(4)),
+            };
+            let marker = if line.expanded { '+' } else { ' ' };
+            text += &format!("{} {:<12}{:>5}{} {}\n", address, first, line.line, marker, line.source);
+            for (row, chunk) in line.bytes.chunks(4).enumerate().skip(1)
+            {
+                let address = line.address.unwrap_or(0).wrapping_add(row as u16 * 4);
+                text += &format!("{:04X} {}\n", address, hex_bytes(chunk.iter()));
+            }
+        }
+        text
+    }
+
+    /// The symbol table as `ADDR NAME` lines, the format CP/M debuggers read.
+    pub fn write_symbols(&self) -> String
+    {
+        self.symbols.iter().map(|(name, value)| format!("{:04X} {}\n", value, name)).collect()
+    }
+}
+
+fn hex_bytes<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> String
+{
+    bytes.map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+}
+
+/// Assembles 8080 source in Intel syntax, in upper or lower case. Files are included from
+/// the current directory.
+pub fn assemble(source: &str) -> Result<Assembly, String>
+{
+    assemble_lines(source_lines(source, None))
+}
+
+/// Assembles a source file, naming it in errors. Files are included from its directory.
+pub fn assemble_file(path: &Path) -> Result<Assembly, String>
+{
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    assemble_lines(source_lines(&source, Some(Rc::new(path.to_path_buf()))))
+}
+
+/// Assembles one instruction, DB or DW at `address`, as when patching memory. `$` is the
+/// address and `symbols` the only other symbols.
+pub fn assemble_instruction(text: &str, address: u16, symbols: &SymbolTable) -> Result<Vec<u8>, String>
+{
+    let mut assembler = Assembler::new();
+    for (name, value) in symbols.iter()
+    {
+        assembler.symbols.insert(name.to_string(), Symbol { value, kind: SymbolKind::Equate });
+    }
+    assembler.here = address as u32;
+    assembler.last_pass = true;
+    let (operation, operands) = split_word(strip_comment(text).trim());
+    let operation = operation.to_ascii_uppercase();
+    if operation != "DB" && operation != "DW" && !assembler.mnemonics.contains_key(&operation)
+    {
+        return Err(format!("unknown instruction {}", operation));
+    }
+    let mut listed = ListingLine::default();
+    assembler.statement(None, &operation, split_operands(operands), &mut listed)?;
+    Ok(listed.bytes)
+}
+
+fn assemble_lines(lines: Vec<SourceLine>) -> Result<Assembly, String>
+{
+    let mut assembler = Assembler::new();
+    assembler.pass(&lines, false)?;
+    assembler.pass(&lines, true)?;
+
+    // The labels made for LOCAL names are left out
+    let mut symbols: Vec<(String, u16)> = assembler.symbols.iter()
+        .filter(|(name, _)| !name.starts_with("??"))
+        .map(|(name, symbol)| (name.clone(), symbol.value))
+        .collect();
+    symbols.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+    Ok(Assembly { image: assembler.image, end: assembler.end, symbols, listing: assembler.listing })
+}
+
+struct Assembler
+{
+    mnemonics:    HashMap<String, Mnemonic>,
+    symbols:      HashMap<String, Symbol>,
+    macros:       HashMap<String, Macro>,
+    inputs:       Vec<Input>,
+    recording:    Option<Recording>,
+    conditionals: Vec<Conditional>,
+    locals:       usize,
+    here:         u32,
+    end:          u32,
+    last_pass:    bool,
+    ended:        bool,
+    image:        Image,
+    listing:      Vec<ListingLine>,
+}
+
+impl Assembler
+{
+    fn new() -> Assembler
+    {
+        Assembler
+        {
+            mnemonics:    opcodes::mnemonics(),
+            symbols:      HashMap::new(),
+            macros:       HashMap::new(),
+            inputs:       Vec::new(),
+            recording:    None,
+            conditionals: Vec::new(),
+            locals:       0,
+            here:         0,
+            end:          0,
+            last_pass:    false,
+            ended:        false,
+            image:        Image::default(),
+            listing:      Vec::new(),
+        }
+    }
+
+    /// Goes through the source once. The first pass finds the labels, the last one puts out
+    /// the code and the listing.
+    fn pass(&mut self, lines: &[SourceLine], last_pass: bool) -> Result<(), String>
+    {
+        self.here = 0;
+        self.last_pass = last_pass;
+        self.ended = false;
+        self.macros.clear();
+        self.recording = None;
+        self.conditionals.clear();
+        self.locals = 0;
+        self.inputs = vec![Input { lines: lines.to_vec(), next: 0, expansion: None, conditionals: 0 }];
+
+        while let Some(line) = self.next_line()
+        {
+            let expanded = self.inputs.iter().any(|input| input.expansion.is_some());
+            let mut listed = ListingLine { line: line.number, source: line.text.clone(), expanded, ..ListingLine::default() };
+            if let Err(error) = self.line(&line, &mut listed)
+            {
+                return Err(self.error_at(&line, error));
+            }
+            if last_pass
+            {
+                self.listing.push(listed);
+            }
+            if self.ended
+            {
+                break;
+            }
+        }
+
+        if let Some(recording) = &self.recording
+        {
+            return Err(format!("{}: no ENDM for this block", recording.start));
+        }
+        if let Some(conditional) = self.conditionals.last()
+        {
+            return Err(format!("{}: no ENDIF for this IF", conditional.start));
+        }
+        Ok(())
+    }
+
+    fn next_line(&mut self) -> Option<SourceLine>
+    {
+        loop
+        {
+            let input = self.inputs.last_mut()?;
+            if let Some(line) = input.lines.get(input.next)
+            {
+                input.next += 1;
+                return Some(line.clone());
+            }
+            self.inputs.pop();
+        }
+    }
+
+    /// An error with where it happened and the macros it happened in.
+    fn error_at(&self, line: &SourceLine, error: String) -> String
+    {
+        let mut message = format!("{}: {}", line.location(), error);
+        for (name, call) in self.inputs.iter().rev().filter_map(|input| input.expansion.as_ref())
+        {
+            message += &format!(", in {} at {}", name, call);
+        }
+        message
+    }
+
+    fn push_input(&mut self, lines: Vec<SourceLine>, expansion: Option<(String, String)>) -> Result<(), String>
+    {
+        if self.inputs.len() > MAX_NESTING
+        {
+            return Err(format!("macros and included files nest more than {} deep", MAX_NESTING));
+        }
+        self.inputs.push(Input { lines, next: 0, expansion, conditionals: self.conditionals.len() });
+        Ok(())
+    }
+
+    fn is_operation(&self, word: &str) -> bool
+    {
+        let word = word.to_ascii_uppercase();
+        DIRECTIVES.contains(&word.as_str()) || self.mnemonics.contains_key(&word) || self.macros.contains_key(&word)
+    }
+
+    /// Whether lines are being assembled, rather than skipped by an IF.
+    fn is_active(&self) -> bool
+    {
+        self.conditionals.iter().all(|conditional| conditional.active)
+    }
+
+    /// Handles the blocks, conditionals, macros and included files, and passes everything
+    /// else on to `statement`.
+    fn line(&mut self, line: &SourceLine, listed: &mut ListingLine) -> Result<(), String>
+    {
+        let code = strip_comment(&line.text);
+        if code.trim().is_empty() || code.starts_with('*')
+        {
+            return Ok(());
+        }
+        let (label, operation, operands) = self.split_statement(code);
+        let operation = operation.to_ascii_uppercase();
+
+        if self.recording.is_some()
+        {
+            return self.record(line, &operation);
+        }
+        if let "IF" | "ELSE" | "ENDIF" = operation.as_str()
+        {
+            return self.conditional(line, &operation, operands);
+        }
+        if !self.is_active()
+        {
+            return Ok(());
+        }
+
+        match operation.as_str()
+        {
+            "MACRO" =>
+            {
+                let name = label.ok_or("MACRO needs a name")?.to_ascii_uppercase();
+                let parameters: Vec<String> = split_operands(operands).iter().map(|parameter| parameter.to_ascii_uppercase()).collect();
+                if let Some(parameter) = parameters.iter().find(|parameter| !is_name(parameter))
+                {
+                    return Err(format!("invalid parameter {}", parameter));
+                }
+                self.start_recording(line, Block::Macro { name, parameters });
+                Ok(())
+            },
+            "REPT" =>
+            {
+                self.define_label(label)?;
+                let count = self.defined_value(self.single(&operation, &split_operands(operands))?)?;
+                self.start_recording(line, Block::Repeat(count.max(0) as usize));
+                Ok(())
+            },
+            "IRP" =>
+            {
+                self.define_label(label)?;
+                let (parameter, arguments) = operands.split_once(',').unwrap_or((operands, ""));
+                let parameter = parameter.trim().to_ascii_uppercase();
+                if !is_name(&parameter)
+                {
+                    return Err(format!("invalid parameter {}", parameter));
+                }
+                let arguments = arguments.trim();
+                let arguments = arguments.strip_prefix('<').and_then(|inner| inner.strip_suffix('>')).unwrap_or(arguments);
+                self.start_recording(line, Block::Irp { parameter, arguments: macros::split_arguments(arguments) });
+                Ok(())
+            },
+            "ENDM"    => Err("ENDM without MACRO, REPT or IRP".to_string()),
+            "LOCAL"   => Err("LOCAL outside a macro".to_string()),
+            "EXITM"   => self.exit_macro(),
+            "INCLUDE" => self.include(line, operands),
+            _ => match self.macros.get(&operation).cloned()
+            {
+                Some(called) =>
+                {
+                    self.define_label(label)?;
+                    listed.address = Some(self.here as u16);
+                    self.call(line, &operation, &called, operands)
+                },
+                None => self.statement(label, &operation, split_operands(operands), listed),
+            },
+        }
+    }
+
+    fn start_recording(&mut self, line: &SourceLine, block: Block)
+    {
+        self.recording = Some(Recording { block, depth: 1, body: Vec::new(), start: line.location() });
+    }
+
+    /// Adds a line to the block being read, and expands or defines the block at its ENDM.
+    fn record(&mut self, line: &SourceLine, operation: &str) -> Result<(), String>
+    {
+        let recording = self.recording.as_mut().expect("Only called while recording");
+        match operation
+        {
+            "MACRO" | "REPT" | "IRP" => recording.depth += 1,
+            "ENDM" => recording.depth -= 1,
+            _ => (),
+        }
+        if recording.depth > 0
+        {
+            recording.body.push(line.clone());
+            return Ok(());
+        }
+
+        let recording = self.recording.take().expect("Only called while recording");
+        let location = recording.start;
+        let (name, lines) = match recording.block
+        {
+            Block::Macro { name, parameters } =>
+            {
+                self.macros.insert(name, Macro { parameters, body: recording.body });
+                return Ok(());
+            },
+            Block::Repeat(count) =>
+            {
+                let mut lines = Vec::new();
+                for _ in 0..count
+                {
+                    lines.extend(macros::expand(&recording.body, &[], &mut self.locals));
+                }
+                ("REPT", lines)
+            },
+            Block::Irp { parameter, arguments } =>
+            {
+                let mut lines = Vec::new();
+                for argument in arguments
+                {
+                    lines.extend(macros::expand(&recording.body, &[(parameter.clone(), argument)], &mut self.locals));
+                }
+                ("IRP", lines)
+            },
+        };
+        self.push_input(lines, Some((name.to_string(), location)))
+    }
+
+    fn call(&mut self, line: &SourceLine, name: &str, called: &Macro, operands: &str) -> Result<(), String>
+    {
+        let arguments = macros::split_arguments(operands);
+        if arguments.len() > called.parameters.len()
+        {
+            return Err(format!("{} takes {} arguments, not {}", name, called.parameters.len(), arguments.len()));
+        }
+        let names: Vec<(String, String)> = called.parameters.iter().cloned()
+            .zip(arguments.into_iter().chain(std::iter::repeat(String::new())))
+            .collect();
+        let lines = macros::expand(&called.body, &names, &mut self.locals);
+        self.push_input(lines, Some((name.to_string(), line.location())))
+    }
+
+    /// Leaves the innermost macro, REPT or IRP.
+    fn exit_macro(&mut self) -> Result<(), String>
+    {
+        match self.inputs.iter().rposition(|input| input.expansion.is_some())
+        {
+            Some(index) =>
+            {
+                self.conditionals.truncate(self.inputs[index].conditionals);
+                self.inputs.truncate(index);
+                Ok(())
+            },
+            None => Err("EXITM outside a macro".to_string()),
+        }
+    }
+
+    /// Reads a file named relative to the one including it.
+    fn include(&mut self, line: &SourceLine, operands: &str) -> Result<(), String>
+    {
+        let name = operands.trim().trim_matches(|c| c == '\'' || c == '"' || c == '<' || c == '>');
+        if name.is_empty()
+        {
+            return Err("INCLUDE needs a file name".to_string());
+        }
+        let directory = line.file.as_ref().and_then(|file| file.parent()).unwrap_or_else(|| Path::new(""));
+        let path = directory.join(name);
+        let source = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.push_input(source_lines(&source, Some(Rc::new(path))), None)
+    }
+
+    fn conditional(&mut self, line: &SourceLine, operation: &str, operands: &str) -> Result<(), String>
+    {
+        match operation
+        {
+            "IF" =>
+            {
+                let enclosing = self.is_active();
+                let active = enclosing && self.defined_value(operands)? != 0;
+                self.conditionals.push(Conditional { active, taken: active || !enclosing, seen_else: false, start: line.location() });
+                Ok(())
+            },
+            "ELSE" => match self.conditionals.last_mut()
+            {
+                Some(conditional) if conditional.seen_else => Err("second ELSE for one IF".to_string()),
+                Some(conditional) =>
+                {
+                    conditional.active = !conditional.taken;
+                    conditional.taken = true;
+                    conditional.seen_else = true;
+                    Ok(())
+                },
+                None => Err("ELSE without IF".to_string()),
+            },
+            _ => match self.conditionals.pop()
+            {
+                Some(_) => Ok(()),
+                None => Err("ENDIF without IF".to_string()),
+            },
+        }
+    }
+
+    fn statement(&mut self, label: Option<&str>, operation: &str, operands: Vec<&str>, listed: &mut ListingLine) -> Result<(), String>
+    {
+        if operation == "ORG"
+        {
+            self.here = self.single(operation, &operands).and_then(|text| self.defined_value(text))? as u32 & 0xFFFF;
+        }
+        if let Some(label) = label
+        {
+            match operation
+            {
+                "EQU" | "SET" =>
+                {
+                    let kind = if operation == "EQU" { SymbolKind::Equate } else { SymbolKind::Variable };
+                    let expression = self.single(operation, &operands)?;
+                    match evaluate(expression, self.here as u16, &|name| self.lookup(name))?
+                    {
+                        Some(value) =>
+                        {
+                            self.define(label, value as u16, kind)?;
+                            listed.value = Some(value as u16);
+                        },
+                        // Defined later on, so it can wait for the last pass
+                        None if !self.last_pass => (),
+                        None => return self.defined_value(expression).map(|_| ()),
+                    }
+                    return Ok(());
+                },
+                _ => self.define_label(Some(label))?,
+            }
+        }
+        listed.address = Some(self.here as u16);
+
+        match operation
+        {
+            "" | "ORG" => Ok(()),
+            "EQU" | "SET" => Err(format!("{} needs a label", operation)),
+            "DB" =>
+            {
+                let mut bytes = Vec::new();
+                for operand in operands.iter()
+                {
+                    match parse_string(operand)
+                    {
+                        Ok((string, rest)) if rest.trim().is_empty() => bytes.extend(string.bytes()),
+                        _ => bytes.push(self.byte(operand)?),
+                    }
+                }
+                self.emit(&bytes, listed)
+            },
+            "DW" =>
+            {
+                let mut bytes = Vec::new();
+                for operand in operands.iter()
+                {
+                    let word = self.word(operand)?;
+                    bytes.extend_from_slice(&[word as u8, (word >> 8) as u8]);
+                }
+                self.emit(&bytes, listed)
+            },
+            "DS" =>
+            {
+                let size = self.defined_value(self.single(operation, &operands)?)?;
+                self.advance(size)
+            },
+            "END" =>
+            {
+                if let Some(entry) = operands.first()
+                {
+                    self.image.entry = Some(self.word(entry)?);
+                }
+                self.ended = true;
+                Ok(())
+            },
+            _ => match self.mnemonics.get(operation).cloned()
+            {
+                Some(mnemonic) =>
+                {
+                    let bytes = self.instruction(operation, &mnemonic, &operands)?;
+                    self.emit(&bytes, listed)
+                },
+                None => Err(format!("unknown instruction {}", operation)),
+            },
+        }
+    }
+
+    /// Splits a statement into its label, operation and operands. A label ends with a colon,
+    /// or starts in the first column, or is the name before EQU, SET or MACRO.
+    fn split_statement<'a>(&self, code: &'a str) -> (Option<&'a str>, &'a str, &'a str)
+    {
+        let first_column = !code.starts_with(char::is_whitespace);
+        let rest = code.trim();
+        let (word, after) = split_word(rest);
+        let mut label = None;
+        let mut rest = rest;
+        if let Some(after) = rest[word.len()..].strip_prefix(':')
+        {
+            label = Some(word);
+            rest = after.trim_start_matches(':').trim_start();
+        }
+        else if first_column && !self.is_operation(word)
+        {
+            label = Some(word);
+            rest = after;
+        }
+        else
+        {
+            let next = split_word(after).0.to_ascii_uppercase();
+            if next == "EQU" || next == "SET" || next == "MACRO"
+            {
+                label = Some(word);
+                rest = after;
+            }
+        }
+        let (operation, operands) = split_word(rest);
+        (label, operation, operands)
+    }
+
+    fn lookup(&self, name: &str) -> Option<u16>
+    {
+        self.symbols.get(name).map(|symbol| symbol.value)
+    }
+
+    /// Gives the label of a line the current address.
+    fn define_label(&mut self, label: Option<&str>) -> Result<(), String>
+    {
+        match label
+        {
+            Some(label) => self.define(label, self.here as u16, SymbolKind::Label),
+            None => Ok(()),
+        }
+    }
+
+    fn define(&mut self, name: &str, value: u16, kind: SymbolKind) -> Result<(), String>
+    {
+        if !is_name(name)
+        {
+            return Err(format!("invalid label {}", name));
+        }
+        let name = name.to_ascii_uppercase();
+        match self.symbols.get(&name)
+        {
+            Some(symbol) if symbol.kind == SymbolKind::Variable && kind == SymbolKind::Variable => (),
+            Some(_) if !self.last_pass => return Err(format!("{} is defined twice", name)),
+            Some(symbol) if symbol.kind != SymbolKind::Variable && symbol.value != value =>
+            {
+                return Err(format!("{} was at {:04X}H in the first pass and {:04X}H in the second", name, symbol.value, value));
+            },
+            _ => (),
+        }
+        self.symbols.insert(name, Symbol { value, kind });
+        Ok(())
+    }
+
+    /// The only operand of a directive.
+    fn single<'a>(&self, operation: &str, operands: &[&'a str]) -> Result<&'a str, String>
+    {
+        match operands
+        {
+            [operand] => Ok(operand),
+            _ => Err(format!("{} takes one operand", operation)),
+        }
+    }
+
+    /// The value of an expression. Symbols not defined yet count as 0 in the first pass.
+    fn value(&self, text: &str) -> Result<i64, String>
+    {
+        let lookup = |name: &str| self.lookup(name);
+        if self.last_pass
+        {
+            evaluate_defined(text, self.here as u16, &lookup)
+        }
+        else
+        {
+            Ok(evaluate(text, self.here as u16, &lookup)?.unwrap_or(0))
+        }
+    }
+
+    /// The value of an expression that decides where code goes, which must be known in the
+    /// first pass.
+    fn defined_value(&self, text: &str) -> Result<i64, String>
+    {
+        evaluate_defined(text, self.here as u16, &|name| self.lookup(name))
+    }
+
+    fn byte(&self, text: &str) -> Result<u8, String>
+    {
+        match self.value(text)?
+        {
+            value if (-0x100..0x100).contains(&value) => Ok(value as u8),
+            value => Err(format!("{} = {:X}H does not fit in a byte", text, value)),
+        }
+    }
+
+    fn word(&self, text: &str) -> Result<u16, String>
+    {
+        match self.value(text)?
+        {
+            value if (-0x10000..0x10000).contains(&value) => Ok(value as u16),
+            value => Err(format!("{} = {:X}H does not fit in a word", text, value)),
+        }
+    }
+
+    /// A register by name, or by number as an expression.
+    fn register(&self, text: &str) -> Result<u8, String>
+    {
+        if let Some(register) = opcodes::register(text)
+        {
+            return Ok(register);
+        }
+        match self.value(text)
+        {
+            Ok(value) if (0..8).contains(&value) => Ok(value as u8),
+            _ => Err(format!("{} is not a register", text)),
+        }
+    }
+
+    fn instruction(&self, name: &str, mnemonic: &Mnemonic, operands: &[&str]) -> Result<Vec<u8>, String>
+    {
+        let count = match mnemonic.operands
+        {
+            Operands::None => 0,
+            Operands::Move | Operands::DestinationByte | Operands::PairWord => 2,
+            _ => 1,
+        };
+        if operands.len() != count
+        {
+            return Err(format!("{} takes {} operand{}, not {}", name, count, if count == 1 { "" } else { "s" }, operands.len()));
+        }
+        let pair = |text: &str| opcodes::register_pair(text, mnemonic.operands)
+            .ok_or_else(|| format!("{} is not a register pair {} can use", text, name));
+
+        let (opcode, immediate) = match mnemonic.operands
+        {
+            Operands::None => (Some(mnemonic.opcodes[0]), Vec::new()),
+            Operands::Destination => (mnemonic.encode(self.register(operands[0])?, 3), Vec::new()),
+            Operands::Source => (mnemonic.encode(self.register(operands[0])?, 0), Vec::new()),
+            Operands::Move =>
+            {
+                let registers = (self.register(operands[0])? << 3) | self.register(operands[1])?;
+                (mnemonic.encode(registers, 0), Vec::new())
+            },
+            Operands::DestinationByte => (mnemonic.encode(self.register(operands[0])?, 3), vec![self.byte(operands[1])?]),
+            Operands::Pair | Operands::StackPair | Operands::IndexPair => (mnemonic.encode(pair(operands[0])?, 4), Vec::new()),
+            Operands::PairWord =>
+            {
+                let word = self.word(operands[1])?;
+                (mnemonic.encode(pair(operands[0])?, 4), vec![word as u8, (word >> 8) as u8])
+            },
+            Operands::Byte => (Some(mnemonic.opcodes[0]), vec![self.byte(operands[0])?]),
+            Operands::Word =>
+            {
+                let word = self.word(operands[0])?;
+                (Some(mnemonic.opcodes[0]), vec![word as u8, (word >> 8) as u8])
+            },
+            Operands::Restart => match self.value(operands[0])?
+            {
+                number if (0..8).contains(&number) => (mnemonic.encode(number as u8, 3), Vec::new()),
+                number => return Err(format!("RST {} is not 0 to 7", number)),
+            },
+        };
+        match opcode
+        {
+            Some(opcode) =>
+            {
+                let mut bytes = vec![opcode];
+                bytes.extend(immediate);
+                Ok(bytes)
+            },
+            None => Err(format!("{} {} is not an instruction", name, operands.join(","))),
+        }
+    }
+
+    /// Puts out bytes at the current address in the last pass.
+    fn emit(&mut self, bytes: &[u8], listed: &mut ListingLine) -> Result<(), String>
+    {
+        if self.last_pass
+        {
+            self.image.add(self.here as u16, bytes);
+            listed.bytes.extend_from_slice(bytes);
+        }
+        self.advance(bytes.len() as i64)
+    }
+
+    fn advance(&mut self, size: i64) -> Result<(), String>
+    {
+        let end = self.here as i64 + size;
+        if !(0..=0x10000).contains(&end)
+        {
+            return Err("the program runs past the end of memory".to_string());
+        }
+        self.here = end as u32;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+}
+
+/// The line up to a `;` that is not inside a string.
+fn strip_comment(line: &str) -> &str
+{
+    let mut quote = None;
+    for (i, c) in line.char_indices()
+    {
+        match (quote, c)
+        {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, ';') => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+/// The first word of `text` and the rest after the spaces that follow it. A word ends at
+/// white space or a colon.
+fn split_word(text: &str) -> (&str, &str)
+{
+    let end = text.find(|c: char| c.is_whitespace() || c == ':').unwrap_or(text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+/// Splits operands at the commas outside strings and parentheses.
+fn split_operands(text: &str) -> Vec<&str>
+{
+    let text = text.trim();
+    if text.is_empty()
+    {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    let mut quote = None;
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices()
+    {
+        match (quote, c)
+        {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 =>
+            {
+                operands.push(text[start..i].trim());
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    operands.push(text[start..].trim());
+    operands
+}
@@ -0,0 +1,352 @@
+#[derive(Clone, Debug, PartialEq)]
+enum Token
+{
+    Number(i64),
+    Name(String),
+    Here,
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+/// Operators by how loosely they bind, loosest first, in the Intel spelling and the C one.
+const BINARY_OPERATORS: [&[&str]; 5] =
+[
+    &["OR", "XOR"],
+    &["AND"],
+    &["EQ", "NE", "LT", "LE", "GT", "GE"],
+    &["+", "-"],
+    &["*", "/", "MOD", "SHL", "SHR"],
+];
+
+/// Evaluates an expression at address `here`: numbers in decimal or with an H, O, Q or B
+/// suffix, one or two character strings, `$` for the current address, symbols looked up with
+/// `lookup` and the operators of the Intel assembler, loosest first:
+///
+/// `OR XOR`, `AND`, `NOT`, `EQ NE LT LE GT GE`, `+ -`, `* / MOD SHL SHR`, unary `+ - HIGH LOW`
+///
+/// `| ^ & ~ == != < <= > >= % << >>` are accepted too. Comparisons give 0FFFFH when true.
+/// Gives `None` if a symbol is not defined yet.
+pub fn evaluate<F>(text: &str, here: u16, lookup: &F) -> Result<Option<i64>, String>
+    where F: Fn(&str) -> Option<u16>
+{
+    let (value, undefined) = parse(text, here, lookup)?;
+    Ok(if undefined.is_some() { None } else { Some(value) })
+}
+
+/// Evaluates an expression like `evaluate`, failing if it uses a symbol that is not defined.
+pub fn evaluate_defined<F>(text: &str, here: u16, lookup: &F) -> Result<i64, String>
+    where F: Fn(&str) -> Option<u16>
+{
+    match parse(text, here, lookup)?
+    {
+        (value, None) => Ok(value),
+        (_, Some(name)) => Err(format!("undefined symbol {}", name)),
+    }
+}
+
+/// The value of an expression and the first symbol in it that is not defined.
+fn parse<F>(text: &str, here: u16, lookup: &F) -> Result<(i64, Option<String>), String>
+    where F: Fn(&str) -> Option<u16>
+{
+    let tokens = tokenize(text)?;
+    if tokens.is_empty()
+    {
+        return Err("missing value".to_string());
+    }
+    let mut parser = Parser { tokens: &tokens, position: 0, here, lookup, undefined: None };
+    let value = parser.binary(0)?;
+    if let Some(token) = parser.tokens.get(parser.position)
+    {
+        return Err(format!("unexpected {} in {}", describe(token), text.trim()));
+    }
+    Ok((value, parser.undefined))
+}
+
+/// The value of a character string used as a number: one character, or two with the first in
+/// the high byte.
+pub fn string_value(text: &str) -> Result<i64, String>
+{
+    match text.as_bytes()
+    {
+        [low] => Ok(*low as i64),
+        [high, low] => Ok(((*high as i64) << 8) | *low as i64),
+        _ => Err(format!("'{}' is too long to be a number", text)),
+    }
+}
+
+/// Reads a string quoted with ' or " from the start of `text`, a doubled quote standing for
+/// itself. Returns the string and the text after it.
+pub fn parse_string(text: &str) -> Result<(String, &str), String>
+{
+    let mut chars = text.char_indices();
+    let quote = match chars.next()
+    {
+        Some((_, c)) if c == '\'' || c == '"' => c,
+        _ => return Err(format!("expected a string, got {}", text)),
+    };
+    let mut string = String::new();
+    while let Some((i, c)) = chars.next()
+    {
+        if c == quote
+        {
+            if text[i + 1..].starts_with(quote)
+            {
+                chars.next();
+            }
+            else
+            {
+                return Ok((string, &text[i + 1..]));
+            }
+        }
+        string.push(c);
+    }
+    Err("unterminated string".to_string())
+}
+
+pub fn is_name_start(c: char) -> bool
+{
+    c.is_ascii_alphabetic() || c == '_' || c == '?' || c == '@' || c == '.'
+}
+
+pub fn is_name_char(c: char) -> bool
+{
+    is_name_start(c) || c.is_ascii_digit()
+}
+
+/// A symbol, macro or parameter name.
+pub fn is_name(name: &str) -> bool
+{
+    name.starts_with(is_name_start) && name.chars().all(is_name_char)
+}
+
+/// Parses a number in decimal, hex with an H suffix or 0x prefix, octal with O or Q or binary
+/// with B.
+pub fn parse_number(word: &str) -> Result<i64, String>
+{
+    let upper = word.to_ascii_uppercase();
+    let (digits, radix) = if let Some(digits) = upper.strip_prefix("0X")
+    {
+        (digits, 16)
+    }
+    else
+    {
+        match upper.chars().last()
+        {
+            Some('H') => (&upper[..upper.len() - 1], 16),
+            Some('O') | Some('Q') => (&upper[..upper.len() - 1], 8),
+            Some('B') => (&upper[..upper.len() - 1], 2),
+            Some('D') => (&upper[..upper.len() - 1], 10),
+            _ => (upper.as_str(), 10),
+        }
+    };
+    i64::from_str_radix(digits, radix).map_err(|_| format!("invalid number {}", word))
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String>
+{
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next()
+    {
+        if c == '\'' || c == '"'
+        {
+            let (string, after) = parse_string(rest)?;
+            tokens.push(Token::Number(string_value(&string)?));
+            rest = after;
+        }
+        else if c.is_ascii_digit()
+        {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..end])?));
+            rest = &rest[end..];
+        }
+        else if c == '$' && !rest[1..].starts_with(is_name_char)
+        {
+            tokens.push(Token::Here);
+            rest = &rest[1..];
+        }
+        else if is_name_start(c) || c == '$'
+        {
+            let end = rest[1..].find(|c: char| !is_name_char(c) && c != '$').map_or(rest.len(), |end| end + 1);
+            let name = rest[..end].replace('$', "").to_ascii_uppercase();
+            let operator = ["OR", "XOR", "AND", "NOT", "EQ", "NE", "LT", "LE", "GT", "GE", "MOD", "SHL", "SHR", "HIGH", "LOW"]
+                .iter().find(|operator| **operator == name);
+            tokens.push(match operator
+            {
+                Some(operator) => Token::Operator(operator),
+                None => Token::Name(name),
+            });
+            rest = &rest[end..];
+        }
+        else
+        {
+            let symbols: [(&str, &'static str); 19] = [
+                ("<<", "SHL"), (">>", "SHR"), ("<=", "LE"), (">=", "GE"), ("==", "EQ"), ("!=", "NE"), ("<>", "NE"),
+                ("<", "LT"), (">", "GT"), ("=", "EQ"), ("|", "OR"), ("^", "XOR"), ("&", "AND"), ("~", "NOT"),
+                ("%", "MOD"), ("+", "+"), ("-", "-"), ("*", "*"), ("/", "/"),
+            ];
+            match symbols.iter().find(|(symbol, _)| rest.starts_with(symbol))
+            {
+                Some((symbol, operator)) =>
+                {
+                    tokens.push(Token::Operator(operator));
+                    rest = &rest[symbol.len()..];
+                },
+                None if c == '(' => { tokens.push(Token::Open); rest = &rest[1..]; },
+                None if c == ')' => { tokens.push(Token::Close); rest = &rest[1..]; },
+                None => return Err(format!("unexpected {} in {}", c, text.trim())),
+            }
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String
+{
+    match token
+    {
+        Token::Number(number) => number.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Here => "$".to_string(),
+        Token::Operator(operator) => operator.to_string(),
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+    }
+}
+
+struct Parser<'a, F>
+{
+    tokens:    &'a [Token],
+    position:  usize,
+    here:      u16,
+    lookup:    &'a F,
+    undefined: Option<String>,
+}
+
+impl<'a, F> Parser<'a, F>
+    where F: Fn(&str) -> Option<u16>
+{
+    fn peek_operator(&self) -> Option<&'static str>
+    {
+        match self.tokens.get(self.position)
+        {
+            Some(Token::Operator(operator)) => Some(operator),
+            _ => None,
+        }
+    }
+
+    /// Operators of `level` and tighter. NOT binds tighter than AND and looser than the
+    /// comparisons.
+    fn binary(&mut self, level: usize) -> Result<i64, String>
+    {
+        if level == BINARY_OPERATORS.len()
+        {
+            return self.unary();
+        }
+        let mut left = self.operand(level)?;
+        while let Some(operator) = self.peek_operator().filter(|operator| BINARY_OPERATORS[level].contains(operator))
+        {
+            self.position += 1;
+            let right = self.operand(level)?;
+            left = apply(operator, left, right)?;
+        }
+        Ok(left)
+    }
+
+    /// The operands of the operators of `level`.
+    fn operand(&mut self, level: usize) -> Result<i64, String>
+    {
+        if level == 1
+        {
+            self.not()
+        }
+        else
+        {
+            self.binary(level + 1)
+        }
+    }
+
+    fn not(&mut self) -> Result<i64, String>
+    {
+        if self.peek_operator() == Some("NOT")
+        {
+            self.position += 1;
+            return Ok(!self.not()? & 0xFFFF);
+        }
+        self.binary(2)
+    }
+
+    fn unary(&mut self) -> Result<i64, String>
+    {
+        match self.peek_operator()
+        {
+            Some("-")    => { self.position += 1; Ok(self.unary()?.wrapping_neg()) },
+            Some("+")    => { self.position += 1; self.unary() },
+            Some("HIGH") => { self.position += 1; Ok((self.unary()? >> 8) & 0xFF) },
+            Some("LOW")  => { self.position += 1; Ok(self.unary()? & 0xFF) },
+            Some("NOT")  => { self.position += 1; Ok(!self.unary()? & 0xFFFF) },
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<i64, String>
+    {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token
+        {
+            Some(Token::Number(number)) => Ok(number),
+            Some(Token::Here) => Ok(self.here as i64),
+            Some(Token::Name(name)) => match (self.lookup)(&name)
+            {
+                Some(value) => Ok(value as i64),
+                None =>
+                {
+                    self.undefined.get_or_insert(name);
+                    Ok(0)
+                },
+            },
+            Some(Token::Open) =>
+            {
+                let value = self.binary(0)?;
+                match self.tokens.get(self.position)
+                {
+                    Some(Token::Close) => { self.position += 1; Ok(value) },
+                    _ => Err("missing )".to_string()),
+                }
+            },
+            Some(other) => Err(format!("unexpected {}", describe(&other))),
+            None => Err("missing value at the end of the expression".to_string()),
+        }
+    }
+}
+
+fn apply(operator: &str, left: i64, right: i64) -> Result<i64, String>
+{
+    let truth = |condition: bool| if condition { 0xFFFF } else { 0 };
+    let (left_word, right_word) = (left & 0xFFFF, right & 0xFFFF);
+    Ok(match operator
+    {
+        "+"   => left.wrapping_add(right),
+        "-"   => left.wrapping_sub(right),
+        "*"   => left.wrapping_mul(right),
+        "/" | "MOD" if right == 0 => return Err("division by zero".to_string()),
+        "/"   => left.wrapping_div(right),
+        "MOD" => left.wrapping_rem(right),
+        "SHL" | "SHR" if right < 0 => return Err(format!("negative shift count {}", right)),
+        "SHL" => if right >= 16 { 0 } else { (left << right) & 0xFFFF },
+        "SHR" => if right >= 16 { 0 } else { left_word >> right },
+        "AND" => left_word & right_word,
+        "OR"  => left_word | right_word,
+        "XOR" => left_word ^ right_word,
+        "EQ"  => truth(left_word == right_word),
+        "NE"  => truth(left_word != right_word),
+        "LT"  => truth(left_word < right_word),
+        "LE"  => truth(left_word <= right_word),
+        "GT"  => truth(left_word > right_word),
+        "GE"  => truth(left_word >= right_word),
+        _ => unreachable!("The tokenizer only makes known operators"),
+    })
+}
@@ -0,0 +1,147 @@
+use super::{split_word, strip_comment, SourceLine};
+use super::expression::{is_name_char, is_name_start};
+
+/// A macro's parameters and the lines of its body.
+#[derive(Clone, Debug)]
+pub struct Macro
+{
+    pub parameters: Vec<String>,
+    pub body:       Vec<SourceLine>,
+}
+
+/// The kinds of block that end with ENDM.
+#[derive(Clone, Debug)]
+pub enum Block
+{
+    Macro { name: String, parameters: Vec<String> },
+    Repeat(usize),
+    Irp { parameter: String, arguments: Vec<String> },
+}
+
+/// A block being read up to its ENDM.
+#[derive(Clone, Debug)]
+pub struct Recording
+{
+    pub block: Block,
+    /// Blocks open inside it, counting itself.
+    pub depth: usize,
+    pub body:  Vec<SourceLine>,
+    /// Where the block starts, for the error if it never ends.
+    pub start: String,
+}
+
+/// Splits macro arguments at the commas outside strings, parentheses and angle brackets. An
+/// argument in angle brackets is passed without them, commas and all.
+pub fn split_arguments(text: &str) -> Vec<String>
+{
+    let text = text.trim();
+    if text.is_empty()
+    {
+        return Vec::new();
+    }
+    let mut arguments = Vec::new();
+    let mut quote = None;
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices()
+    {
+        match (quote, c)
+        {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '(') | (None, '<') => depth += 1,
+            (None, ')') | (None, '>') => depth -= 1,
+            (None, ',') if depth == 0 =>
+            {
+                arguments.push(unbracket(&text[start..i]));
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    arguments.push(unbracket(&text[start..]));
+    arguments
+}
+
+fn unbracket(argument: &str) -> String
+{
+    let argument = argument.trim();
+    argument.strip_prefix('<').and_then(|inner| inner.strip_suffix('>')).unwrap_or(argument).to_string()
+}
+
+/// The body of a macro with `names` replaced by their values and LOCAL labels renamed to
+/// `??0001` and so on, counting from `locals`.
+pub fn expand(body: &[SourceLine], names: &[(String, String)], locals: &mut usize) -> Vec<SourceLine>
+{
+    let mut names = names.to_vec();
+    let mut lines = Vec::new();
+    for line in body.iter()
+    {
+        let (operation, operands) = split_word(strip_comment(&line.text).trim());
+        if operation.eq_ignore_ascii_case("LOCAL")
+        {
+            for local in operands.split(',').map(str::trim).filter(|local| !local.is_empty())
+            {
+                *locals += 1;
+                names.push((local.to_ascii_uppercase(), format!("??{:04}", locals)));
+            }
+            continue;
+        }
+        lines.push(SourceLine { text: substitute(&line.text, &names), ..line.clone() });
+    }
+    lines
+}
+
+/// Replaces the names in a line. Inside strings only names joined on with `&` are replaced.
+/// The `&` that joins a name to the text next to it is dropped.
+fn substitute(text: &str, names: &[(String, String)]) -> String
+{
+    let (code, comment) = text.split_at(strip_comment(text).len());
+    let chars: Vec<char> = code.chars().collect();
+    let mut output = String::new();
+    let mut quote = None;
+    let mut i = 0;
+    while i < chars.len()
+    {
+        let c = chars[i];
+        if is_name_start(c) || c.is_ascii_digit()
+        {
+            let start = i;
+            while i < chars.len() && is_name_char(chars[i])
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let joined_before = start > 0 && chars[start - 1] == '&';
+            let joined_after = chars.get(i) == Some(&'&');
+            let value = names.iter().find(|(name, _)| name.eq_ignore_ascii_case(&word)).map(|(_, value)| value);
+            match value
+            {
+                Some(value) if is_name_start(c) && (quote.is_none() || joined_before || joined_after) =>
+                {
+                    if joined_before && output.ends_with('&')
+                    {
+                        output.pop();
+                    }
+                    output += value;
+                    if joined_after
+                    {
+                        i += 1;
+                    }
+                },
+                _ => output += &word,
+            }
+            continue;
+        }
+        match quote
+        {
+            Some(q) if c == q => quote = None,
+            None if c == '\'' || c == '"' => quote = Some(c),
+            _ => (),
+        }
+        output.push(c);
+        i += 1;
+    }
+    output + comment
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use crate::i8080::instructions::{Instruction, InstructionTypes};
+use crate::i8080::registers::*;
+
+/// The operands an instruction takes and where they go in the opcode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operands
+{
+    None,
+    /// A register in bits 3 to 5, like INR.
+    Destination,
+    /// A register in bits 0 to 2, like ADD.
+    Source,
+    /// MOV's two registers.
+    Move,
+    /// A register in bits 3 to 5 and a byte, MVI.
+    DestinationByte,
+    /// B, D, H or SP in bits 4 and 5.
+    Pair,
+    /// A pair and a word, LXI.
+    PairWord,
+    /// B, D, H or PSW in bits 4 and 5.
+    StackPair,
+    /// B or D, LDAX and STAX.
+    IndexPair,
+    Byte,
+    Word,
+    /// RST's 0 to 7 in bits 3 to 5.
+    Restart,
+}
+
+impl Operands
+{
+    pub fn of(instruction: &InstructionTypes) -> Operands
+    {
+        use InstructionTypes::*;
+        match instruction
+        {
+            INR | DCR => Operands::Destination,
+            ADD | ADC | SUB | SBB | ANA | XRA | ORA | CMP => Operands::Source,
+            MOV => Operands::Move,
+            MVI => Operands::DestinationByte,
+            INX | DCX | DAD => Operands::Pair,
+            LXI => Operands::PairWord,
+            PUSH | POP => Operands::StackPair,
+            LDAX | STAX => Operands::IndexPair,
+            ADI | ACI | SUI | SBI | ANI | XRI | ORI | CPI | IN | OUT => Operands::Byte,
+            JMP | JNZ | JZ | JNC | JC | JPO | JPE | JP | JM | CALL | CNZ | CZ | CNC | CC | CPO | CPE | CP | CM |
+            LDA | STA | LHLD | SHLD => Operands::Word,
+            RST => Operands::Restart,
+            _ => Operands::None,
+        }
+    }
+}
+
+/// An instruction's operands and every opcode that encodes it.
+#[derive(Clone, Debug)]
+pub struct Mnemonic
+{
+    pub operands: Operands,
+    pub opcodes:  Vec<u8>,
+}
+
+impl Mnemonic
+{
+    /// The opcode with `field` put in at `shift`, if there is one.
+    pub fn encode(&self, field: u8, shift: u8) -> Option<u8>
+    {
+        let opcode = self.opcodes[0] | (field << shift);
+        if self.opcodes.contains(&opcode) { Some(opcode) } else { None }
+    }
+}
+
+/// The mnemonics of the 8080, found by decoding every opcode.
+pub fn mnemonics() -> HashMap<String, Mnemonic>
+{
+    let mut mnemonics: HashMap<String, Mnemonic> = HashMap::new();
+    for byte in 0..=0xFF
+    {
+        let instruction = Instruction::from_byte(byte).instruction_type;
+        if instruction == InstructionTypes::Unknown
+        {
+            continue;
+        }
+        let operands = Operands::of(&instruction);
+        mnemonics.entry(format!("{:?}", instruction))
+            .or_insert(Mnemonic { operands, opcodes: Vec::new() })
+            .opcodes.push(byte);
+    }
+    mnemonics
+}
+
+/// The number of register B, C, D, E, H, L, M or A.
+pub fn register(name: &str) -> Option<u8>
+{
+    match name.to_ascii_uppercase().as_str()
+    {
+        "B" => Some(B_REG),
+        "C" => Some(C_REG),
+        "D" => Some(D_REG),
+        "E" => Some(E_REG),
+        "H" => Some(H_REG),
+        "L" => Some(L_REG),
+        "M" => Some(MEM_REF),
+        "A" => Some(A_REG),
+        _ => None,
+    }
+}
+
+/// The number of a register pair written B, D, H or SP, or BC, DE and HL. PSW takes the place
+/// of SP for PUSH and POP.
+pub fn register_pair(name: &str, operands: Operands) -> Option<u8>
+{
+    match (name.to_ascii_uppercase().as_str(), operands)
+    {
+        ("B", _) | ("BC", _) => Some(BC_PAIR_REG),
+        ("D", _) | ("DE", _) => Some(DE_PAIR_REG),
+        ("H", Operands::Pair) | ("H", Operands::PairWord) | ("H", Operands::StackPair) |
+        ("HL", Operands::Pair) | ("HL", Operands::PairWord) | ("HL", Operands::StackPair) => Some(HL_PAIR_REG),
+        ("SP", Operands::Pair) | ("SP", Operands::PairWord) => Some(SP_REG),
+        ("PSW", Operands::StackPair) => Some(SP_REG),
+        _ => None,
+    }
+}
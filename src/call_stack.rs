@@ -0,0 +1,121 @@
+use crate::coverage::Step;
+use crate::disassembler::Flow;
+use crate::i8080::Processor;
+use crate::i8080::instructions::InstructionTypes;
+
+/// Mismatches kept, the latest.
+const MAX_MISMATCHES: usize = 8;
+
+/// A subroutine called, or an interrupt taken, and not returned from yet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame
+{
+    /// Where the subroutine starts.
+    pub entry:          u16,
+    pub return_address: u16,
+    /// SP while the return address is on top of the stack.
+    pub stack_pointer:  u16,
+    pub interrupt:      bool,
+}
+
+/// Where the stack was changed other than by a call and its return.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch
+{
+    /// The instruction that changed it.
+    pub address: u16,
+    pub message: String,
+}
+
+/// The calls being run, followed from the CALL, conditional calls, RST and interrupts taken.
+/// A frame is dropped once SP moves above its return address, by a return or otherwise.
+#[derive(Clone, Debug, Default)]
+pub struct CallStack
+{
+    frames:     Vec<Frame>,
+    mismatches: Vec<Mismatch>,
+}
+
+impl CallStack
+{
+    /// The frames, outermost first.
+    pub fn get_frames(&self) -> &[Frame]
+    {
+        &self.frames
+    }
+
+    /// The latest returns to somewhere else than the call, return addresses swapped with
+    /// XTHL and frames dropped by moving SP, oldest first.
+    pub fn get_mismatches(&self) -> &[Mismatch]
+    {
+        &self.mismatches
+    }
+
+    /// Follows the instruction of `step`, `processor` being the state after it ran.
+    pub fn update(&mut self, step: &Step, processor: &Processor)
+    {
+        let line = step.get_line();
+        let before = step.get_stack_pointer();
+        let stack_pointer = processor.get_stack_pointer();
+        let pc = processor.get_pc();
+        let top = processor.get_memory_at(stack_pointer) as u16 | ((processor.get_memory_at(stack_pointer.wrapping_add(1)) as u16) << 8);
+        let (instruction, flow) = match &line.instruction
+        {
+            Some(instruction) if !step.get_halted() => (Some(instruction.clone()), line.get_flow()),
+            _ => (None, Flow::Next),
+        };
+        let name = instruction.as_ref().map_or_else(|| "An interrupt".to_string(), |instruction| format!("{:?}", instruction));
+
+        if instruction == Some(InstructionTypes::XTHL)
+        {
+            if let Some(frame) = self.frames.last_mut().filter(|frame| frame.stack_pointer == stack_pointer && frame.return_address != top)
+            {
+                let message = format!("XTHL swapped return address {:04X} for {:04X}", frame.return_address, top);
+                frame.return_address = top;
+                self.mismatch(line.address, message);
+            }
+        }
+
+        let returned = matches!(flow, Flow::Return | Flow::ConditionalReturn) && stack_pointer == before.wrapping_add(2);
+        if returned && self.frames.last().is_some_and(|frame| frame.stack_pointer == before)
+        {
+            let frame = self.frames.pop().expect("a frame to return from");
+            if pc != frame.return_address
+            {
+                self.mismatch(line.address, format!("{} went to {:04X} instead of {:04X}", name, pc, frame.return_address));
+            }
+        }
+        while let Some(frame) = self.frames.last().filter(|frame| frame.stack_pointer < stack_pointer).copied()
+        {
+            self.frames.pop();
+            self.mismatch(line.address, format!("{} dropped return address {:04X}", name, frame.return_address));
+        }
+
+        // An interrupt taken in the same clock as the instruction pushes PC below what it pushed
+        let pushed = before.wrapping_sub(stack_pointer);
+        let vector = pc & !0x38 == 0;
+        let interrupt = Frame { entry: pc, return_address: top, stack_pointer, interrupt: true };
+        match (flow, pushed)
+        {
+            (Flow::Call(_), 2) => self.frames.push(Frame { entry: pc, return_address: line.get_next(), stack_pointer, interrupt: false }),
+            (Flow::Call(_), 4) if vector =>
+            {
+                let called = stack_pointer.wrapping_add(2);
+                self.frames.push(Frame { entry: top, return_address: line.get_next(), stack_pointer: called, interrupt: false });
+                self.frames.push(interrupt);
+            },
+            (_, 2) if vector && instruction != Some(InstructionTypes::PUSH) => self.frames.push(interrupt),
+            (_, 4) if vector && instruction == Some(InstructionTypes::PUSH) => self.frames.push(interrupt),
+            _ => (),
+        }
+    }
+
+    fn mismatch(&mut self, address: u16, message: String)
+    {
+        if self.mismatches.len() == MAX_MISMATCHES
+        {
+            self.mismatches.remove(0);
+        }
+        self.mismatches.push(Mismatch { address, message });
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use std::io::{stdin, stdout, Read, Write};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+/// Typing this on the host terminal (Ctrl-]) disconnects the emulated machine from it.
+pub const QUIT_KEY: u8 = 0x1D;
+
+/// A character device the emulated machine talks to, such as the host terminal.
+pub trait Console
+{
+    /// True if a read would not block, i.e. a character is waiting or the console is closed.
+    fn status(&mut self) -> bool;
+
+    /// Blocks until a character is available. Returns None once the console is closed.
+    fn read(&mut self) -> Option<u8>;
+
+    fn write(&mut self, c: u8);
+}
+
+/// The host terminal. Input is collected by a thread so the status can be polled without
+/// blocking; the terminal should be in raw mode so characters arrive as they are typed.
+pub struct HostConsole
+{
+    input:   Receiver<u8>,
+    pending: Option<u8>,
+    closed:  bool,
+}
+
+impl HostConsole
+{
+    pub fn new() -> HostConsole
+    {
+        let (sender, input) = channel();
+        thread::spawn(move ||
+        {
+            let mut buffer = [0u8; 64];
+            loop
+            {
+                let count = match stdin().read(&mut buffer)
+                {
+                    Ok(0) | Err(_) => break,
+                    Ok(count) => count,
+                };
+                if buffer[..count].iter().any(|byte| sender.send(*byte).is_err())
+                {
+                    break;
+                }
+            }
+        });
+
+        HostConsole
+        {
+            input,
+            pending: None,
+            closed:  false,
+        }
+    }
+
+    fn accept(&mut self, c: u8) -> Option<u8>
+    {
+        if c == QUIT_KEY
+        {
+            self.closed = true;
+            return None;
+        }
+        Some(c)
+    }
+}
+
+impl Default for HostConsole
+{
+    fn default() -> HostConsole
+    {
+        HostConsole::new()
+    }
+}
+
+impl Console for HostConsole
+{
+    fn status(&mut self) -> bool
+    {
+        if self.pending.is_none() && !self.closed
+        {
+            match self.input.try_recv()
+            {
+                Ok(c) => self.pending = self.accept(c),
+                Err(TryRecvError::Disconnected) => self.closed = true,
+                Err(TryRecvError::Empty) => (),
+            }
+        }
+        self.pending.is_some() || self.closed
+    }
+
+    fn read(&mut self) -> Option<u8>
+    {
+        if let Some(c) = self.pending.take()
+        {
+            return Some(c);
+        }
+        if self.closed
+        {
+            return None;
+        }
+        match self.input.recv()
+        {
+            Ok(c) => self.accept(c),
+            Err(_) =>
+            {
+                self.closed = true;
+                None
+            },
+        }
+    }
+
+    fn write(&mut self, c: u8)
+    {
+        let mut out = stdout();
+        out.write_all(&[c]).expect("Failed to write to console");
+        out.flush().expect("Failed to flush console");
+    }
+}
+
+/// A console with scripted input that records everything written to it, for tests and
+/// headless runs. It closes when the input runs out.
+#[derive(Default)]
+pub struct BufferConsole
+{
+    pub input:  VecDeque<u8>,
+    pub output: Vec<u8>,
+}
+
+impl BufferConsole
+{
+    pub fn new(input: &[u8]) -> BufferConsole
+    {
+        BufferConsole
+        {
+            input:  input.iter().cloned().collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Console for BufferConsole
+{
+    fn status(&mut self) -> bool
+    {
+        // Reads never block: they either return the next character or close the console
+        true
+    }
+
+    fn read(&mut self) -> Option<u8>
+    {
+        self.input.pop_front()
+    }
+
+    fn write(&mut self, c: u8)
+    {
+        self.output.push(c);
+    }
+}
+
+/// A serial port with nothing plugged in: no input ever arrives and output is dropped.
+#[derive(Default)]
+pub struct NullConsole;
+
+impl Console for NullConsole
+{
+    fn status(&mut self) -> bool
+    {
+        false
+    }
+
+    fn read(&mut self) -> Option<u8>
+    {
+        None
+    }
+
+    fn write(&mut self, _c: u8)
+    {
+    }
+}
@@ -0,0 +1,234 @@
+use std::path::Path;
+use crate::assembler::opcodes::Operands;
+use crate::disassembler::{decode, Flow, Line};
+use crate::i8080::Processor;
+use crate::i8080::instructions::InstructionTypes;
+use crate::i8080::registers::MEM_REF;
+
+/// Executed as the first byte of an instruction.
+pub const OPCODE: u8 = 1;
+/// Executed as the operand of an instruction.
+pub const OPERAND: u8 = 2;
+/// Read as data, stack included.
+pub const READ: u8 = 4;
+pub const WRITTEN: u8 = 8;
+
+/// The letters a coverage file gives each kind of access.
+const KINDS: [(u8, char); 4] = [(OPCODE, 'X'), (OPERAND, 'O'), (READ, 'R'), (WRITTEN, 'W')];
+
+/// How each byte of memory was used while running.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coverage
+{
+    /// OPCODE, OPERAND, READ and WRITTEN, by address.
+    flags: Vec<u8>,
+}
+
+impl Default for Coverage
+{
+    fn default() -> Coverage
+    {
+        Coverage { flags: vec![0; 0x10000] }
+    }
+}
+
+/// What is known before an instruction runs to tell what it accessed after.
+pub struct Step
+{
+    line:          Line,
+    stack_pointer: u16,
+    bc:            u16,
+    de:            u16,
+    hl:            u16,
+    cycles:        u64,
+    /// Whether the processor idles in HLT instead of running the instruction.
+    halted:        bool,
+}
+
+impl Step
+{
+    /// Decodes the instruction at PC, about to run.
+    pub fn new(processor: &Processor) -> Step
+    {
+        let registers = processor.get_registers();
+        let pair = |high: u8, low: u8| ((high as u16) << 8) | low as u16;
+        Step
+        {
+            line:          decode(&|address| processor.get_memory_at(address), processor.get_pc()),
+            stack_pointer: processor.get_stack_pointer(),
+            bc:            pair(registers.b, registers.c),
+            de:            pair(registers.d, registers.e),
+            hl:            pair(registers.h, registers.l),
+            cycles:        processor.get_cycles(),
+            halted:        processor.get_halted(),
+        }
+    }
+
+    /// The instruction about to run.
+    pub fn get_line(&self) -> &Line
+    {
+        &self.line
+    }
+
+    pub fn get_stack_pointer(&self) -> u16
+    {
+        self.stack_pointer
+    }
+
+    /// The processor's cycle count before the instruction.
+    pub fn get_cycles(&self) -> u64
+    {
+        self.cycles
+    }
+
+    pub fn get_halted(&self) -> bool
+    {
+        self.halted
+    }
+}
+
+impl Coverage
+{
+    /// The OPCODE, OPERAND, READ and WRITTEN flags of `address`.
+    pub fn get(&self, address: u16) -> u8
+    {
+        self.flags[address as usize]
+    }
+
+    pub fn mark(&mut self, address: u16, kind: u8)
+    {
+        self.flags[address as usize] |= kind;
+    }
+
+    /// Records the instruction of `step` as run, `processor` being the state after. Calls and
+    /// returns are told taken from how far the stack pointer moved.
+    pub fn record(&mut self, step: &Step, processor: &Processor)
+    {
+        use InstructionTypes::*;
+        let line = &step.line;
+        let instruction = match &line.instruction
+        {
+            Some(instruction) if !step.halted => instruction,
+            _ => return,
+        };
+        self.mark(line.address, OPCODE);
+        for offset in 1..line.bytes.len() as u16
+        {
+            self.mark(line.address.wrapping_add(offset), OPERAND);
+        }
+
+        let opcode = line.bytes[0];
+        let operand = line.get_operand();
+        let sp = step.stack_pointer;
+        let moved = processor.get_stack_pointer().wrapping_sub(sp);
+        let mut access = |address: u16, kind: u8, length: u16|
+        {
+            for offset in 0..length
+            {
+                self.mark(address.wrapping_add(offset), kind);
+            }
+        };
+        match instruction
+        {
+            MOV if opcode & 7 == MEM_REF => access(step.hl, READ, 1),
+            MOV if (opcode >> 3) & 7 == MEM_REF => access(step.hl, WRITTEN, 1),
+            MVI if (opcode >> 3) & 7 == MEM_REF => access(step.hl, WRITTEN, 1),
+            INR | DCR if (opcode >> 3) & 7 == MEM_REF => access(step.hl, READ | WRITTEN, 1),
+            _ if Operands::of(instruction) == Operands::Source && opcode & 7 == MEM_REF => access(step.hl, READ, 1),
+            LDA => access(operand, READ, 1),
+            STA => access(operand, WRITTEN, 1),
+            LHLD => access(operand, READ, 2),
+            SHLD => access(operand, WRITTEN, 2),
+            LDAX => access(if opcode & 0x10 == 0 { step.bc } else { step.de }, READ, 1),
+            STAX => access(if opcode & 0x10 == 0 { step.bc } else { step.de }, WRITTEN, 1),
+            POP => access(sp, READ, 2),
+            XTHL => access(sp, READ | WRITTEN, 2),
+            PUSH => access(sp.wrapping_sub(2), WRITTEN, 2),
+            _ => match line.get_flow()
+            {
+                Flow::Call(_) if moved == 0xFFFE => access(sp.wrapping_sub(2), WRITTEN, 2),
+                Flow::Return | Flow::ConditionalReturn if moved == 2 => access(sp, READ, 2),
+                _ => (),
+            },
+        }
+    }
+
+    /// Adds the accesses of another run.
+    pub fn merge(&mut self, other: &Coverage)
+    {
+        for (flags, other) in self.flags.iter_mut().zip(other.flags.iter())
+        {
+            *flags |= *other;
+        }
+    }
+
+    /// How many of the bytes from `start` to `end` inclusive have each of OPCODE or OPERAND,
+    /// READ and WRITTEN, and how many none of them, as percentages.
+    pub fn percentages(&self, start: u16, end: u16) -> [f64; 4]
+    {
+        let bytes = &self.flags[start as usize..=end.max(start) as usize];
+        let share = |test: &dyn Fn(u8) -> bool| 100.0 * bytes.iter().filter(|flags| test(**flags)).count() as f64 / bytes.len() as f64;
+        [share(&|flags| flags & (OPCODE | OPERAND) != 0), share(&|flags| flags & READ != 0),
+         share(&|flags| flags & WRITTEN != 0), share(&|flags| flags == 0)]
+    }
+
+    /// Runs of bytes used the same way, as `START-END KINDS` lines in hex with X for opcodes,
+    /// O for operands, R for data read and W for data written.
+    pub fn to_text(&self) -> String
+    {
+        let mut text = String::new();
+        let mut start = 0;
+        while start < self.flags.len()
+        {
+            let flags = self.flags[start];
+            let end = start + self.flags[start..].iter().take_while(|other| **other == flags).count() - 1;
+            if flags != 0
+            {
+                let kinds: String = KINDS.iter().filter(|(kind, _)| flags & kind != 0).map(|(_, letter)| *letter).collect();
+                text += &format!("{:04X}-{:04X} {}\n", start, end, kinds);
+            }
+            start = end + 1;
+        }
+        text
+    }
+
+    /// Reads the lines `to_text` writes. Blank lines and lines starting with `;` are skipped.
+    pub fn parse(text: &str) -> Result<Coverage, String>
+    {
+        let mut coverage = Coverage::default();
+        for (number, line) in text.lines().enumerate()
+        {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';')
+            {
+                continue;
+            }
+            let error = || format!("line {}: {} is not a coverage range", number + 1, line);
+            let (range, kinds) = line.split_once(char::is_whitespace).ok_or_else(error)?;
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let start = u16::from_str_radix(start, 16).map_err(|_| error())?;
+            let end = u16::from_str_radix(end, 16).map_err(|_| error())?;
+            let mut flags = 0;
+            for letter in kinds.trim().chars()
+            {
+                flags |= KINDS.iter().find(|(_, kind)| *kind == letter.to_ascii_uppercase()).ok_or_else(error)?.0;
+            }
+            for address in start..=end
+            {
+                coverage.mark(address, flags);
+            }
+        }
+        Ok(coverage)
+    }
+
+    pub fn load(path: &Path) -> Result<Coverage, String>
+    {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Coverage::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String>
+    {
+        std::fs::write(path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,631 @@
+pub mod bios;
+pub mod disk;
+pub mod fcb;
+pub mod filesystem;
+pub mod host;
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::i8080::Processor;
+use crate::i8080::registers::Registers;
+use crate::cpm::fcb::*;
+use crate::cpm::host::*;
+
+/// Start of the transient program area, where .COM files are loaded and started.
+pub const TPA_START: u16 = 0x0100;
+
+/// Programs enter the BDOS with `CALL 5`.
+pub const BDOS_ENTRY: u16 = 0x0005;
+
+/// A jump to address 0 is a warm boot, i.e. the program has exited.
+pub const WARM_BOOT: u16 = 0x0000;
+
+/// Where the jump at `BDOS_ENTRY` points. Programs read the word at 0x0006 to find the top of
+/// the TPA, so this has to look like a real BDOS address. A RET is placed here so the
+/// processor returns to the caller once the call has been served.
+const BDOS_ADDRESS: u16 = 0xFE06;
+
+/// Bytes a .COM file can have: from `TPA_START` up to the stack `load` sets below the BDOS.
+pub const TPA_SIZE: usize = (BDOS_ADDRESS - 8 - TPA_START) as usize;
+
+/// Warm boot entry of the BIOS jump table, referenced by the jump at address 0.
+const BIOS_WARM_BOOT: u16 = 0xFF03;
+
+/// Default DMA address, which also holds the command tail when a program starts.
+const DEFAULT_DMA: u16 = 0x0080;
+const FIRST_FCB: u16 = 0x005C;
+const SECOND_FCB: u16 = 0x006C;
+
+const DRIVES: usize = 16;
+const DIRECTORY_ENTRY_SIZE: usize = 32;
+
+const JMP: u8 = 0xC3;
+const RET: u8 = 0xC9;
+
+const CTRL_Z: u8 = 0x1A;
+const CPM_VERSION: u8 = 0x22;
+
+const SYSTEM_RESET: u8 = 0;
+const CONSOLE_INPUT: u8 = 1;
+const CONSOLE_OUTPUT: u8 = 2;
+const DIRECT_CONSOLE_IO: u8 = 6;
+const PRINT_STRING: u8 = 9;
+const READ_CONSOLE_BUFFER: u8 = 10;
+const CONSOLE_STATUS: u8 = 11;
+const VERSION_NUMBER: u8 = 12;
+const RESET_DISK_SYSTEM: u8 = 13;
+const SELECT_DISK: u8 = 14;
+const OPEN_FILE: u8 = 15;
+const CLOSE_FILE: u8 = 16;
+const SEARCH_FIRST: u8 = 17;
+const SEARCH_NEXT: u8 = 18;
+const DELETE_FILE: u8 = 19;
+const READ_SEQUENTIAL: u8 = 20;
+const WRITE_SEQUENTIAL: u8 = 21;
+const MAKE_FILE: u8 = 22;
+const RENAME_FILE: u8 = 23;
+const LOGIN_VECTOR: u8 = 24;
+const CURRENT_DISK: u8 = 25;
+const SET_DMA: u8 = 26;
+const USER_CODE: u8 = 32;
+const READ_RANDOM: u8 = 33;
+const WRITE_RANDOM: u8 = 34;
+const COMPUTE_FILE_SIZE: u8 = 35;
+const SET_RANDOM_RECORD: u8 = 36;
+
+const SUCCESS: u8 = 0;
+const END_OF_FILE: u8 = 1;
+const ERROR: u8 = 0xFF;
+
+/// Parses a drive mapping such as `A=./dir` given on the command line.
+pub fn parse_drive_mapping(mapping: &str) -> Result<(u8, PathBuf), String>
+{
+    let (drive, path) = match mapping.split_once('=')
+    {
+        Some(parts) => parts,
+        None => return Err(format!("Expected DRIVE=DIRECTORY, got {}", mapping)),
+    };
+
+    let drive = drive.trim_end_matches(':').to_ascii_uppercase();
+    match drive.as_bytes()
+    {
+        [letter @ b'A'..=b'P'] => Ok((letter - b'A', PathBuf::from(path))),
+        _ => Err(format!("No such drive {}, expected A to P", drive)),
+    }
+}
+
+/// A minimal CP/M environment: traps `CALL 5` and serves the BDOS on the host. Console
+/// functions use the host's input and output and the file functions work on host directories
+/// mounted as drives.
+pub struct Cpm<R: Read, W: Write>
+{
+    input:          R,
+    output:         W,
+    drives:         Vec<Option<HostDrive>>,
+    current_disk:   u8,
+    user:           u8,
+    dma:            u16,
+    search_results: Vec<[u8; DIRECTORY_ENTRY_SIZE]>,
+}
+
+impl<R: Read, W: Write> Cpm<R, W>
+{
+    pub fn new(input: R, output: W) -> Cpm<R, W>
+    {
+        Cpm
+        {
+            input,
+            output,
+            drives:         vec![None; DRIVES],
+            current_disk:   0,
+            user:           0,
+            dma:            DEFAULT_DMA,
+            search_results: Vec::new(),
+        }
+    }
+
+    /// Maps drive `drive` (0 is A:) to a directory on the host.
+    pub fn mount(&mut self, drive: u8, directory: PathBuf)
+    {
+        self.drives[drive as usize] = Some(HostDrive::new(directory));
+    }
+
+    /// Fills in the command tail at 0x80 and the two default FCBs from the program's
+    /// arguments, like the CCP does before starting a program.
+    pub fn set_arguments(&mut self, processor: &mut Processor, arguments: &[String])
+    {
+        let mut tail = String::new();
+        for argument in arguments
+        {
+            tail.push(' ');
+            tail.push_str(&argument.to_ascii_uppercase());
+        }
+        let tail = &tail.as_bytes()[..tail.len().min(127)];
+        processor.set_memory_at(DEFAULT_DMA, tail.len() as u8);
+        processor.load_bytes_at(DEFAULT_DMA + 1, tail);
+
+        for (i, fcb) in [FIRST_FCB, SECOND_FCB].iter().enumerate()
+        {
+            let (drive, name) = match arguments.get(i)
+            {
+                Some(argument) => parse_file_name(argument),
+                None => (0, [b' '; 11]),
+            };
+            processor.set_memory_at(*fcb, drive);
+            processor.load_bytes_at(*fcb + 1, &name);
+            processor.load_bytes_at(*fcb + 12, &[0; 4]);
+        }
+        processor.set_memory_at(FIRST_FCB + 32, 0);
+    }
+
+    /// Sets up page zero and loads `program` into the TPA, leaving the processor ready to start
+    /// it. A return address of 0 is pushed so a plain RET from the program exits as well.
+    pub fn load(&mut self, processor: &mut Processor, program: &[u8])
+    {
+        processor.set_memory_at(WARM_BOOT, JMP);
+        processor.set_memory_at(WARM_BOOT + 1, BIOS_WARM_BOOT as u8);
+        processor.set_memory_at(WARM_BOOT + 2, (BIOS_WARM_BOOT >> 8) as u8);
+        processor.set_memory_at(BDOS_ENTRY, JMP);
+        processor.set_memory_at(BDOS_ENTRY + 1, BDOS_ADDRESS as u8);
+        processor.set_memory_at(BDOS_ENTRY + 2, (BDOS_ADDRESS >> 8) as u8);
+        processor.set_memory_at(BDOS_ADDRESS, RET);
+
+        processor.load_bytes_at(TPA_START, program);
+        processor.set_memory_at(DEFAULT_DMA, 0);
+
+        let stack_pointer = BDOS_ADDRESS - 8;
+        processor.set_memory_at(stack_pointer, 0);
+        processor.set_memory_at(stack_pointer + 1, 0);
+        processor.set_stack_pointer(stack_pointer);
+        processor.set_pc(TPA_START);
+    }
+
+    /// Executes one instruction, serving a BDOS call first if the processor is about to enter
+    /// one. Returns false once the program has exited through a warm boot.
+    pub fn clock(&mut self, processor: &mut Processor) -> bool
+    {
+        match processor.get_pc()
+        {
+            WARM_BOOT => return false,
+            BDOS_ENTRY if !self.bdos_call(processor) => return false,
+            _ => (),
+        }
+        processor.clock();
+        true
+    }
+
+    pub fn output(&self) -> &W
+    {
+        &self.output
+    }
+
+    /// Serves the BDOS function in register C. Returns false if the function ends the program.
+    fn bdos_call(&mut self, processor: &mut Processor) -> bool
+    {
+        let mut registers = processor.get_registers();
+        let argument = ((registers.d as u16) << 8) | registers.e as u16;
+        let fcb = Fcb::at(argument);
+
+        let result: u16 = match registers.c
+        {
+            SYSTEM_RESET => return false,
+            CONSOLE_INPUT =>
+            {
+                let c = self.read_char();
+                self.write_char(c);
+                c as u16
+            },
+            CONSOLE_OUTPUT =>
+            {
+                self.write_char(registers.e);
+                0
+            },
+            DIRECT_CONSOLE_IO =>
+            {
+                if registers.e == 0xFF
+                {
+                    self.read_char() as u16
+                }
+                else
+                {
+                    self.write_char(registers.e);
+                    0
+                }
+            },
+            PRINT_STRING =>
+            {
+                self.print_string(processor, argument);
+                0
+            },
+            READ_CONSOLE_BUFFER =>
+            {
+                self.read_console_buffer(processor, argument);
+                0
+            },
+            CONSOLE_STATUS => 0,
+            VERSION_NUMBER => CPM_VERSION as u16,
+            RESET_DISK_SYSTEM =>
+            {
+                self.current_disk = 0;
+                self.dma = DEFAULT_DMA;
+                0
+            },
+            SELECT_DISK => self.select_disk(registers.e) as u16,
+            OPEN_FILE => self.open_file(processor, fcb) as u16,
+            CLOSE_FILE => self.close_file(processor, fcb) as u16,
+            SEARCH_FIRST => self.search_first(processor, fcb) as u16,
+            SEARCH_NEXT => self.search_next(processor) as u16,
+            DELETE_FILE => self.delete_file(processor, fcb) as u16,
+            READ_SEQUENTIAL => self.read_sequential(processor, fcb) as u16,
+            WRITE_SEQUENTIAL => self.write_sequential(processor, fcb) as u16,
+            MAKE_FILE => self.make_file(processor, fcb) as u16,
+            RENAME_FILE => self.rename_file(processor, fcb) as u16,
+            LOGIN_VECTOR => self.login_vector(),
+            CURRENT_DISK => self.current_disk as u16,
+            SET_DMA =>
+            {
+                self.dma = argument;
+                0
+            },
+            USER_CODE =>
+            {
+                if registers.e != 0xFF
+                {
+                    self.user = registers.e & 0x0F;
+                }
+                self.user as u16
+            },
+            READ_RANDOM => self.read_random(processor, fcb) as u16,
+            WRITE_RANDOM => self.write_random(processor, fcb) as u16,
+            COMPUTE_FILE_SIZE => self.compute_file_size(processor, fcb) as u16,
+            SET_RANDOM_RECORD =>
+            {
+                let record = fcb.sequential_record(processor);
+                fcb.set_random_record(processor, record);
+                0
+            },
+            _ => 0,
+        };
+
+        self.output.flush().expect("Failed to flush console output");
+        set_result(&mut registers, result);
+        processor.set_all_registers(registers);
+        true
+    }
+
+    /// Reads one character from the console, translating newlines to the carriage return CP/M
+    /// programs expect. End of input reads as ^Z.
+    fn read_char(&mut self) -> u8
+    {
+        let mut byte = [0u8; 1];
+        match self.input.read(&mut byte)
+        {
+            Ok(1) if byte[0] == b'\n' => b'\r',
+            Ok(1) => byte[0],
+            _ => CTRL_Z,
+        }
+    }
+
+    fn write_char(&mut self, c: u8)
+    {
+        self.output.write_all(&[c]).expect("Failed to write to console");
+    }
+
+    /// Prints the '$' terminated string at `address`.
+    fn print_string(&mut self, processor: &Processor, address: u16)
+    {
+        let mut address = address;
+        loop
+        {
+            let c = processor.get_memory_at(address);
+            if c == b'$'
+            {
+                break;
+            }
+            self.write_char(c);
+            address = address.wrapping_add(1);
+        }
+    }
+
+    /// Reads a line into the buffer at `address`. The first byte holds the buffer size, the
+    /// second receives the number of characters read and the characters follow. Line editing
+    /// and echo are left to the host terminal.
+    fn read_console_buffer(&mut self, processor: &mut Processor, address: u16)
+    {
+        let size = processor.get_memory_at(address);
+        let mut count: u8 = 0;
+
+        loop
+        {
+            let c = self.read_char();
+            if c == b'\r' || c == CTRL_Z
+            {
+                break;
+            }
+            if count < size
+            {
+                processor.set_memory_at(address.wrapping_add(2 + count as u16), c);
+                count += 1;
+            }
+        }
+        processor.set_memory_at(address.wrapping_add(1), count);
+    }
+
+    fn drive(&self, processor: &Processor, fcb: Fcb) -> Option<&HostDrive>
+    {
+        let drive = match fcb.drive(processor)
+        {
+            0 | b'?' => self.current_disk,
+            drive => drive - 1,
+        };
+        self.drives.get(drive as usize)?.as_ref()
+    }
+
+    /// The host file an FCB refers to, if it exists.
+    fn host_file(&self, processor: &Processor, fcb: Fcb) -> Option<PathBuf>
+    {
+        self.drive(processor, fcb)?.find(&fcb.name(processor))
+    }
+
+    fn select_disk(&mut self, disk: u8) -> u8
+    {
+        match self.drives.get(disk as usize)
+        {
+            Some(Some(_)) =>
+            {
+                self.current_disk = disk;
+                SUCCESS
+            },
+            _ => ERROR,
+        }
+    }
+
+    fn login_vector(&self) -> u16
+    {
+        let mut vector = 0;
+        for (i, drive) in self.drives.iter().enumerate()
+        {
+            if drive.is_some()
+            {
+                vector |= 1 << i;
+            }
+        }
+        vector
+    }
+
+    fn open_file(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        match self.host_file(processor, fcb)
+        {
+            Some(path) =>
+            {
+                fcb.rewind(processor);
+                fcb.set_record_count(processor, size_in_records(&path));
+                SUCCESS
+            },
+            None => ERROR,
+        }
+    }
+
+    /// Files are written through as records arrive, so closing only checks the file exists.
+    fn close_file(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        match self.host_file(processor, fcb)
+        {
+            Some(_) => SUCCESS,
+            None => ERROR,
+        }
+    }
+
+    fn make_file(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        let name = fcb.name(processor);
+        let created = match self.drive(processor, fcb)
+        {
+            Some(drive) if !is_ambiguous(&name) => drive.create(&name).is_some(),
+            _ => false,
+        };
+
+        if !created
+        {
+            return ERROR;
+        }
+        fcb.rewind(processor);
+        fcb.set_record_count(processor, 0);
+        SUCCESS
+    }
+
+    fn delete_file(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        let files = match self.drive(processor, fcb)
+        {
+            Some(drive) => drive.find_all(&fcb.name(processor)),
+            None => return ERROR,
+        };
+
+        if files.is_empty()
+        {
+            return ERROR;
+        }
+        for (_, path) in files
+        {
+            if std::fs::remove_file(path).is_err()
+            {
+                return ERROR;
+            }
+        }
+        SUCCESS
+    }
+
+    fn rename_file(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        let from = fcb.name(processor);
+        let to = fcb.rename_target(processor);
+        match self.drive(processor, fcb)
+        {
+            Some(drive) if drive.rename(&from, &to) => SUCCESS,
+            _ => ERROR,
+        }
+    }
+
+    /// Collects a directory entry for every matching file and returns the first of them.
+    fn search_first(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        self.search_results.clear();
+        let files = match self.drive(processor, fcb)
+        {
+            Some(drive) => drive.find_all(&fcb.name(processor)),
+            None => return ERROR,
+        };
+
+        for (name, path) in files.iter().rev()
+        {
+            let records = size_in_records(path);
+            let last_extent = records.saturating_sub(1) / RECORDS_PER_EXTENT;
+            let mut entry = [0u8; DIRECTORY_ENTRY_SIZE];
+            entry[0] = self.user;
+            entry[1..12].copy_from_slice(name);
+            entry[12] = (last_extent % 32) as u8;
+            entry[14] = (last_extent / 32) as u8;
+            entry[15] = (records - last_extent * RECORDS_PER_EXTENT) as u8;
+            self.search_results.push(entry);
+        }
+        self.search_next(processor)
+    }
+
+    /// Places the next directory entry found by a search at the start of the DMA buffer.
+    fn search_next(&mut self, processor: &mut Processor) -> u8
+    {
+        match self.search_results.pop()
+        {
+            Some(entry) =>
+            {
+                self.copy_to_dma(processor, &entry);
+                SUCCESS
+            },
+            None => ERROR,
+        }
+    }
+
+    fn read_record(&mut self, processor: &mut Processor, fcb: Fcb, record: u32) -> u8
+    {
+        let data = match self.host_file(processor, fcb)
+        {
+            Some(path) => read_record(&path, record),
+            None => return ERROR,
+        };
+
+        match data
+        {
+            Some(data) =>
+            {
+                self.copy_to_dma(processor, &data);
+                SUCCESS
+            },
+            None => END_OF_FILE,
+        }
+    }
+
+    /// Copies `data` to the DMA buffer, wrapping around the top of memory.
+    fn copy_to_dma(&self, processor: &mut Processor, data: &[u8])
+    {
+        for (i, byte) in data.iter().enumerate()
+        {
+            processor.set_memory_at(self.dma.wrapping_add(i as u16), *byte);
+        }
+    }
+
+    fn write_record(&mut self, processor: &mut Processor, fcb: Fcb, record: u32) -> u8
+    {
+        let path = match self.host_file(processor, fcb)
+        {
+            Some(path) => path,
+            None => return ERROR,
+        };
+
+        let mut data = [0u8; RECORD_SIZE];
+        for (i, byte) in data.iter_mut().enumerate()
+        {
+            *byte = processor.get_memory_at(self.dma.wrapping_add(i as u16));
+        }
+
+        if !write_record(&path, record, &data)
+        {
+            return ERROR;
+        }
+        fcb.set_record_count(processor, size_in_records(&path));
+        SUCCESS
+    }
+
+    fn read_sequential(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        let record = fcb.sequential_record(processor);
+        let result = self.read_record(processor, fcb, record);
+        if result == SUCCESS
+        {
+            self.advance(processor, fcb, record);
+        }
+        result
+    }
+
+    fn write_sequential(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        let record = fcb.sequential_record(processor);
+        let result = self.write_record(processor, fcb, record);
+        if result == SUCCESS
+        {
+            self.advance(processor, fcb, record);
+        }
+        result
+    }
+
+    /// Moves the sequential position past `record`, updating the record count when the move
+    /// crosses into the next extent.
+    fn advance(&mut self, processor: &mut Processor, fcb: Fcb, record: u32)
+    {
+        fcb.set_sequential_record(processor, record + 1);
+        if let Some(path) = self.host_file(processor, fcb)
+        {
+            fcb.set_record_count(processor, size_in_records(&path));
+        }
+    }
+
+    /// Random access leaves the sequential position at the accessed record, so that sequential
+    /// access continues from there.
+    fn read_random(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        let record = fcb.random_record(processor);
+        fcb.set_sequential_record(processor, record);
+        self.read_record(processor, fcb, record)
+    }
+
+    fn write_random(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        let record = fcb.random_record(processor);
+        fcb.set_sequential_record(processor, record);
+        self.write_record(processor, fcb, record)
+    }
+
+    fn compute_file_size(&mut self, processor: &mut Processor, fcb: Fcb) -> u8
+    {
+        match self.host_file(processor, fcb)
+        {
+            Some(path) =>
+            {
+                fcb.set_random_record(processor, size_in_records(&path));
+                SUCCESS
+            },
+            None => ERROR,
+        }
+    }
+}
+
+/// BDOS results are returned in HL, with L copied to A and H copied to B.
+fn set_result(registers: &mut Registers, result: u16)
+{
+    registers.l = result as u8;
+    registers.h = (result >> 8) as u8;
+    registers.accumulator = registers.l;
+    registers.b = registers.h;
+}
@@ -0,0 +1,331 @@
+use crate::console::Console;
+use crate::cpm::disk::*;
+use crate::cpm::fcb::RECORD_SIZE;
+use crate::i8080::Processor;
+use crate::i8080::registers::Registers;
+
+/// The CCP and BDOS together take up 0x1600 bytes, followed directly by the BIOS.
+const SYSTEM_SIZE: u16 = 0x1600;
+
+/// The CCP starts with a jump to its entry point, 0x35C bytes into the CCP.
+const CCP_ENTRY_OFFSET: u16 = 0x035C;
+
+const BIOS_ENTRIES: u16 = 17;
+const DISK_PARAMETER_HEADER_SIZE: u16 = 16;
+const MAX_DRIVES: usize = 16;
+/// The highest CCP base leaving room below 10000H for the BIOS jump table and directory buffer.
+const MAX_CCP_BASE: u16 = (0x10000 - RECORD_SIZE) as u16 - BIOS_ENTRIES * 3 - SYSTEM_SIZE;
+
+const DEFAULT_DMA: u16 = 0x0080;
+const IOBYTE: u16 = 0x0003;
+const CURRENT_DISK: u16 = 0x0004;
+const BDOS_ENTRY: u16 = 0x0005;
+
+const JMP: u8 = 0xC3;
+const RET: u8 = 0xC9;
+
+const BOOT: u16 = 0;
+const WBOOT: u16 = 1;
+const CONST: u16 = 2;
+const CONIN: u16 = 3;
+const CONOUT: u16 = 4;
+const LIST: u16 = 5;
+const PUNCH: u16 = 6;
+const READER: u16 = 7;
+const HOME: u16 = 8;
+const SELDSK: u16 = 9;
+const SETTRK: u16 = 10;
+const SETSEC: u16 = 11;
+const SETDMA: u16 = 12;
+const READ: u16 = 13;
+const WRITE: u16 = 14;
+const LISTST: u16 = 15;
+const SECTRAN: u16 = 16;
+
+/// Finds where a CP/M 2.2 system was built to run from the jump at the start of its CCP.
+pub fn detect_ccp_base(system: &[u8]) -> Option<u16>
+{
+    if system.len() < 3 || system[0] != JMP
+    {
+        return None;
+    }
+    let target = ((system[2] as u16) << 8) | system[1] as u16;
+    let base = target.checked_sub(CCP_ENTRY_OFFSET)?;
+    if base & 0xFF == 0 && base <= MAX_CCP_BASE
+    {
+        Some(base)
+    }
+    else
+    {
+        None
+    }
+}
+
+/// A CP/M 2.2 machine: the CCP and BDOS are loaded from the boot tracks of the disk in
+/// drive A: and run on top of an emulated BIOS. The BIOS jump table is filled with RET
+/// instructions; calls into it are trapped and served by the emulator before the RET runs.
+/// Disk images have no sector translation tables; the skew is applied when a record is
+/// located in the image.
+pub struct Bios<C: Console>
+{
+    console:      C,
+    disks:        Vec<Option<DiskImage>>,
+    headers:      Vec<u16>,
+    ccp_base:     u16,
+    system:       Vec<u8>,
+    current_disk: usize,
+    track:        u16,
+    sector:       u16,
+    dma:          u16,
+}
+
+impl<C: Console> Bios<C>
+{
+    pub fn new(console: C) -> Bios<C>
+    {
+        let mut disks = Vec::new();
+        disks.resize_with(MAX_DRIVES, || None);
+
+        Bios
+        {
+            console,
+            disks,
+            headers:      vec![0; MAX_DRIVES],
+            ccp_base:     0,
+            system:       Vec::new(),
+            current_disk: 0,
+            track:        0,
+            sector:       0,
+            dma:          DEFAULT_DMA,
+        }
+    }
+
+    /// Puts a disk image in drive `drive`, where 0 is A:.
+    pub fn insert(&mut self, drive: u8, image: DiskImage)
+    {
+        self.disks[drive as usize] = Some(image);
+    }
+
+    pub fn console(&self) -> &C
+    {
+        &self.console
+    }
+
+    pub fn bios_base(&self) -> u16
+    {
+        self.ccp_base + SYSTEM_SIZE
+    }
+
+    /// Cold boot: loads the CCP and BDOS from the boot tracks of drive A:, skipping the cold
+    /// start loader in the first sector, and builds the BIOS and disk tables above them. The
+    /// load address is detected from the CCP unless `ccp_base` is given.
+    pub fn boot(&mut self, processor: &mut Processor, ccp_base: Option<u16>) -> Result<(), String>
+    {
+        let image = match &self.disks[0]
+        {
+            Some(image) => image,
+            None => return Err("No disk in drive A:".to_string()),
+        };
+
+        let loader_size = image.definition.sector_size;
+        let system_tracks = image.system_tracks();
+        if system_tracks.len() < loader_size + SYSTEM_SIZE as usize
+        {
+            return Err(format!("The boot tracks of {} are too small to hold CP/M", image.definition.name));
+        }
+        self.system = system_tracks[loader_size..loader_size + SYSTEM_SIZE as usize].to_vec();
+
+        self.ccp_base = match ccp_base.or_else(|| detect_ccp_base(&self.system))
+        {
+            Some(base) => base,
+            None => return Err("Could not find the CCP in the boot tracks, give its address".to_string()),
+        };
+        if self.ccp_base > MAX_CCP_BASE
+        {
+            return Err(format!("The CCP at {:04X}H leaves no room for the BIOS, it must be at or below {:04X}H", self.ccp_base, MAX_CCP_BASE));
+        }
+
+        self.build_tables(processor)?;
+        processor.set_memory_at(IOBYTE, 0);
+        processor.set_memory_at(CURRENT_DISK, 0);
+        self.warm_boot(processor);
+        Ok(())
+    }
+
+    /// Executes one instruction, serving a BIOS call first if the processor is about to enter
+    /// the jump table. Returns false once the console has closed.
+    pub fn clock(&mut self, processor: &mut Processor) -> bool
+    {
+        let pc = processor.get_pc();
+        let bios_base = self.bios_base();
+
+        if pc >= bios_base && pc < bios_base + BIOS_ENTRIES * 3 && (pc - bios_base).is_multiple_of(3)
+            && !self.bios_call(processor, (pc - bios_base) / 3)
+        {
+            return false;
+        }
+        processor.clock();
+        true
+    }
+
+    /// Lays out the jump table, directory buffer and a disk parameter header, parameter block,
+    /// checksum vector and allocation vector for every drive, all above the BDOS.
+    fn build_tables(&mut self, processor: &mut Processor) -> Result<(), String>
+    {
+        let bios_base = self.bios_base();
+        for entry in 0..BIOS_ENTRIES
+        {
+            processor.load_bytes_at(bios_base + entry * 3, &[RET, 0, 0]);
+        }
+
+        let directory_buffer = bios_base + BIOS_ENTRIES * 3;
+        let mut free = directory_buffer as usize + RECORD_SIZE;
+
+        for (drive, disk) in self.disks.iter().enumerate()
+        {
+            let definition = match disk
+            {
+                Some(disk) => &disk.definition,
+                None =>
+                {
+                    self.headers[drive] = 0;
+                    continue;
+                },
+            };
+
+            let header = free;
+            let parameter_block = header + DISK_PARAMETER_HEADER_SIZE as usize;
+            let checksum_vector = parameter_block + 15;
+            let allocation_vector = checksum_vector + definition.directory_entries as usize / 4;
+            free = allocation_vector + definition.blocks() / 8 + 1;
+            if free > 0x10000
+            {
+                return Err("Not enough memory above the BDOS for the disk tables".to_string());
+            }
+
+            let words = [0, 0, 0, 0, directory_buffer, parameter_block as u16,
+                         checksum_vector as u16, allocation_vector as u16];
+            for (i, word) in words.iter().enumerate()
+            {
+                processor.set_memory_at(header as u16 + i as u16 * 2, *word as u8);
+                processor.set_memory_at(header as u16 + i as u16 * 2 + 1, (*word >> 8) as u8);
+            }
+            processor.load_bytes_at(parameter_block as u16, &definition.parameter_block());
+            self.headers[drive] = header as u16;
+        }
+        Ok(())
+    }
+
+    /// Reloads the CCP and BDOS, restores the jumps in page zero and enters the CCP with the
+    /// current drive in register C.
+    fn warm_boot(&mut self, processor: &mut Processor)
+    {
+        let bios_base = self.bios_base();
+        let bdos_entry = self.ccp_base + 0x0806;
+
+        processor.load_bytes_at(self.ccp_base, &self.system);
+        processor.load_bytes_at(0x0000, &[JMP, (bios_base + 3) as u8, ((bios_base + 3) >> 8) as u8]);
+        processor.load_bytes_at(BDOS_ENTRY, &[JMP, bdos_entry as u8, (bdos_entry >> 8) as u8]);
+
+        self.dma = DEFAULT_DMA;
+        let mut registers = processor.get_registers();
+        registers.c = processor.get_memory_at(CURRENT_DISK);
+        processor.set_all_registers(registers);
+        processor.set_stack_pointer(DEFAULT_DMA);
+        processor.set_pc(self.ccp_base);
+    }
+
+    /// Serves BIOS entry `entry`. Returns false if the console closed while waiting for input.
+    fn bios_call(&mut self, processor: &mut Processor, entry: u16) -> bool
+    {
+        let mut registers = processor.get_registers();
+        let bc = ((registers.b as u16) << 8) | registers.c as u16;
+
+        match entry
+        {
+            BOOT | WBOOT =>
+            {
+                self.warm_boot(processor);
+                return true;
+            },
+            CONST => registers.accumulator = if self.console.status() { 0xFF } else { 0x00 },
+            CONIN =>
+            {
+                match self.console.read()
+                {
+                    Some(c) => registers.accumulator = c & 0x7F,
+                    None => return false,
+                }
+            },
+            CONOUT => self.console.write(registers.c & 0x7F),
+            LIST | PUNCH => (),
+            READER => registers.accumulator = 0x1A,
+            HOME => self.track = 0,
+            SELDSK =>
+            {
+                let header = self.headers.get(registers.c as usize).cloned().unwrap_or(0);
+                if header != 0
+                {
+                    self.current_disk = registers.c as usize;
+                }
+                set_hl(&mut registers, header);
+            },
+            SETTRK => self.track = bc,
+            SETSEC => self.sector = bc,
+            SETDMA => self.dma = bc,
+            READ => registers.accumulator = self.read(processor),
+            WRITE => registers.accumulator = self.write(processor),
+            LISTST => registers.accumulator = 0xFF,
+            SECTRAN => set_hl(&mut registers, bc),
+            _ => (),
+        }
+
+        processor.set_all_registers(registers);
+        true
+    }
+
+    /// Reads the selected record into the DMA buffer. Returns 0 on success and 1 on error.
+    fn read(&mut self, processor: &mut Processor) -> u8
+    {
+        let data = match &self.disks[self.current_disk]
+        {
+            Some(disk) => disk.read_record(self.track, self.sector),
+            None => None,
+        };
+
+        match data
+        {
+            Some(data) =>
+            {
+                for (i, byte) in data.iter().enumerate()
+                {
+                    processor.set_memory_at(self.dma.wrapping_add(i as u16), *byte);
+                }
+                0
+            },
+            None => 1,
+        }
+    }
+
+    fn write(&mut self, processor: &mut Processor) -> u8
+    {
+        let mut data = [0u8; RECORD_SIZE];
+        for (i, byte) in data.iter_mut().enumerate()
+        {
+            *byte = processor.get_memory_at(self.dma.wrapping_add(i as u16));
+        }
+
+        let written = match &mut self.disks[self.current_disk]
+        {
+            Some(disk) => disk.write_record(self.track, self.sector, &data).is_ok(),
+            None => false,
+        };
+        if written { 0 } else { 1 }
+    }
+}
+
+fn set_hl(registers: &mut Registers, value: u16)
+{
+    registers.h = (value >> 8) as u8;
+    registers.l = value as u8;
+}
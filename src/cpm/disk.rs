@@ -0,0 +1,335 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::cpm::fcb::RECORD_SIZE;
+
+/// Directory entries are 32 bytes, four to a record.
+pub const DIRECTORY_ENTRY_SIZE: usize = 32;
+
+/// Geometry and file system layout of a CP/M disk format, in the spirit of a cpmtools
+/// diskdef. The image stores the sectors in physical order, track after track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiskDefinition
+{
+    pub name:              String,
+    pub sector_size:       usize,
+    pub sectors_per_track: u16,
+    pub tracks:            u16,
+    pub block_size:        usize,
+    pub directory_entries: u16,
+    pub skew:              u16,
+    pub boot_tracks:       u16,
+}
+
+impl DiskDefinition
+{
+    /// The 8" single sided, single density IBM 3740 format, the CP/M 2.2 distribution format.
+    pub fn ibm_3740() -> DiskDefinition
+    {
+        DiskDefinition
+        {
+            name:              "ibm-3740".to_string(),
+            sector_size:       128,
+            sectors_per_track: 26,
+            tracks:            77,
+            block_size:        1024,
+            directory_entries: 64,
+            skew:              6,
+            boot_tracks:       2,
+        }
+    }
+
+    /// The 4MB hard disk used by z80pack and many other emulators.
+    pub fn z80pack_hd() -> DiskDefinition
+    {
+        DiskDefinition
+        {
+            name:              "z80pack-hd".to_string(),
+            sector_size:       128,
+            sectors_per_track: 128,
+            tracks:            255,
+            block_size:        2048,
+            directory_entries: 1024,
+            skew:              0,
+            boot_tracks:       0,
+        }
+    }
+
+    /// Reads the definitions in a cpmtools diskdefs file. Only the geometry and file system
+    /// parameters are used; other settings are ignored.
+    pub fn parse_diskdefs(text: &str) -> Result<Vec<DiskDefinition>, String>
+    {
+        let mut definitions = Vec::new();
+        let mut current: Option<DiskDefinition> = None;
+
+        for (number, line) in text.lines().enumerate()
+        {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let mut words = line.split_whitespace();
+            let (key, value) = match (words.next(), words.next())
+            {
+                (Some(key), value) => (key, value.unwrap_or("")),
+                (None, _) => continue,
+            };
+            let error = |message: &str| format!("diskdefs line {}: {}", number + 1, message);
+
+            if key == "diskdef"
+            {
+                current = Some(DiskDefinition
+                {
+                    name:              value.to_string(),
+                    sector_size:       128,
+                    sectors_per_track: 0,
+                    tracks:            0,
+                    block_size:        1024,
+                    directory_entries: 64,
+                    skew:              0,
+                    boot_tracks:       0,
+                });
+                continue;
+            }
+
+            let definition = match current.as_mut()
+            {
+                Some(definition) => definition,
+                None => return Err(error("expected diskdef")),
+            };
+            let number_value = || value.parse::<usize>().map_err(|_| error(&format!("invalid {} {}", key, value)));
+            let word_value = || value.parse::<u16>().map_err(|_| error(&format!("invalid {} {}, 0 to 65535", key, value)));
+
+            match key
+            {
+                "seclen"    => definition.sector_size = number_value()?,
+                "sectrk"    => definition.sectors_per_track = word_value()?,
+                "tracks"    => definition.tracks = word_value()?,
+                "blocksize" => definition.block_size = number_value()?,
+                "maxdir"    => definition.directory_entries = word_value()?,
+                "skew"      => definition.skew = word_value()?,
+                "boottrk"   => definition.boot_tracks = word_value()?,
+                "end"       =>
+                {
+                    let definition = current.take().expect("Checked above");
+                    if definition.sectors_per_track == 0 || definition.tracks == 0
+                    {
+                        return Err(error(&format!("{} has no geometry", definition.name)));
+                    }
+                    if definition.boot_tracks >= definition.tracks
+                    {
+                        return Err(error(&format!("{} has no tracks left after its boot tracks", definition.name)));
+                    }
+                    if definition.block_size < 1024 || !definition.block_size.is_power_of_two()
+                    {
+                        return Err(error(&format!("{} has a block size of {}, not 1024, 2048, 4096 or more", definition.name, definition.block_size)));
+                    }
+                    if definition.sector_size == 0 || !definition.sector_size.is_multiple_of(RECORD_SIZE)
+                    {
+                        return Err(error(&format!("{} has a sector size of {}, not a multiple of 128", definition.name, definition.sector_size)));
+                    }
+                    definitions.push(definition);
+                },
+                _ => (),
+            }
+        }
+        Ok(definitions)
+    }
+
+    /// Looks a definition up in a diskdefs file if one is given, else among the built in ones.
+    pub fn find(name: &str, diskdefs: Option<&Path>) -> Result<DiskDefinition, String>
+    {
+        let definitions = match diskdefs
+        {
+            Some(path) =>
+            {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                DiskDefinition::parse_diskdefs(&text)?
+            },
+            None => vec![DiskDefinition::ibm_3740(), DiskDefinition::z80pack_hd()],
+        };
+
+        definitions.into_iter()
+            .find(|definition| definition.name == name)
+            .ok_or_else(|| format!("Unknown disk format {}", name))
+    }
+
+    /// 128 byte records per track, which is what CP/M counts in.
+    pub fn records_per_track(&self) -> u16
+    {
+        (self.sectors_per_track as usize * self.sector_size / RECORD_SIZE) as u16
+    }
+
+    pub fn records_per_block(&self) -> usize
+    {
+        self.block_size / RECORD_SIZE
+    }
+
+    pub fn image_size(&self) -> usize
+    {
+        self.tracks as usize * self.sectors_per_track as usize * self.sector_size
+    }
+
+    /// Number of allocation blocks outside the boot tracks.
+    pub fn blocks(&self) -> usize
+    {
+        (self.tracks - self.boot_tracks) as usize * self.sectors_per_track as usize * self.sector_size
+            / self.block_size
+    }
+
+    /// Allocation blocks reserved for the directory, at the start of the data area.
+    pub fn directory_blocks(&self) -> usize
+    {
+        (self.directory_entries as usize * DIRECTORY_ENTRY_SIZE).div_ceil(self.block_size)
+    }
+
+    /// Block numbers are stored in one byte when there are at most 256 blocks, else in two.
+    pub fn has_word_block_numbers(&self) -> bool
+    {
+        self.blocks() > 256
+    }
+
+    /// Logical 16K extents covered by one directory entry.
+    pub fn extents_per_entry(&self) -> usize
+    {
+        let pointers = if self.has_word_block_numbers() { 8 } else { 16 };
+        (pointers * self.block_size / (16 * 1024)).max(1)
+    }
+
+    /// The physical sector order on a track: entry n holds the index of the sector that
+    /// logical sector n is stored in. Same algorithm as cpmtools.
+    pub fn skew_table(&self) -> Vec<u16>
+    {
+        let sectors = self.sectors_per_track;
+        let skew = self.skew.max(1);
+        let mut table: Vec<u16> = Vec::new();
+        let mut j = 0;
+
+        for _ in 0..sectors
+        {
+            while table.contains(&j)
+            {
+                j = (j + 1) % sectors;
+            }
+            table.push(j);
+            j = (j + skew) % sectors;
+        }
+        table
+    }
+
+    /// Byte offset in the image of 128 byte record `record` on `track`, where records are
+    /// counted in logical order from the start of the track.
+    pub fn record_offset(&self, track: u16, record: u16) -> usize
+    {
+        let records_per_sector = self.sector_size / RECORD_SIZE;
+        let logical_sector = record as usize / records_per_sector;
+        let sector = self.skew_table()[logical_sector % self.sectors_per_track as usize] as usize;
+
+        (track as usize * self.sectors_per_track as usize + sector) * self.sector_size
+            + (record as usize % records_per_sector) * RECORD_SIZE
+    }
+
+    /// The disk parameter block the BDOS uses to find its way around the disk.
+    pub fn parameter_block(&self) -> [u8; 15]
+    {
+        let records_per_block = self.records_per_block();
+        let block_shift = records_per_block.trailing_zeros() as u8;
+        let block_mask = (records_per_block - 1) as u8;
+        let extent_mask = (self.extents_per_entry() - 1) as u8;
+        let max_block = (self.blocks() - 1) as u16;
+        let max_directory = self.directory_entries - 1;
+        let allocation = (0xFFFFu32 << (16 - self.directory_blocks().min(16))) as u16;
+        let checksum_size = self.directory_entries / 4;
+        let records_per_track = self.records_per_track();
+
+        [
+            records_per_track as u8, (records_per_track >> 8) as u8,
+            block_shift,
+            block_mask,
+            extent_mask,
+            max_block as u8, (max_block >> 8) as u8,
+            max_directory as u8, (max_directory >> 8) as u8,
+            (allocation >> 8) as u8, allocation as u8,
+            checksum_size as u8, (checksum_size >> 8) as u8,
+            self.boot_tracks as u8, (self.boot_tracks >> 8) as u8,
+        ]
+    }
+}
+
+/// A disk image held in memory. Changes are written through to the image file, if there is one.
+#[derive(Debug)]
+pub struct DiskImage
+{
+    pub definition: DiskDefinition,
+    data:           Vec<u8>,
+    file:           Option<File>,
+}
+
+impl DiskImage
+{
+    /// Opens an image file for reading and writing. Short images are padded with the 0xE5
+    /// filler of a freshly formatted disk.
+    pub fn open<P: AsRef<Path>>(path: P, definition: DiskDefinition) -> Result<DiskImage, String>
+    {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new().read(true).write(true).open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        data.resize(data.len().max(definition.image_size()), 0xE5);
+
+        Ok(DiskImage { definition, data, file: Some(file) })
+    }
+
+    /// Creates a freshly formatted image, which is not backed by a file.
+    pub fn formatted(definition: DiskDefinition) -> DiskImage
+    {
+        let data = vec![0xE5; definition.image_size()];
+        DiskImage { definition, data, file: None }
+    }
+
+    pub fn from_bytes(data: Vec<u8>, definition: DiskDefinition) -> DiskImage
+    {
+        let mut data = data;
+        data.resize(data.len().max(definition.image_size()), 0xE5);
+        DiskImage { definition, data, file: None }
+    }
+
+    pub fn bytes(&self) -> &[u8]
+    {
+        &self.data
+    }
+
+    pub fn read_record(&self, track: u16, record: u16) -> Option<&[u8]>
+    {
+        let offset = self.definition.record_offset(track, record);
+        self.data.get(offset..offset + RECORD_SIZE)
+    }
+
+    pub fn write_record(&mut self, track: u16, record: u16, data: &[u8]) -> Result<(), String>
+    {
+        let offset = self.definition.record_offset(track, record);
+        if offset + RECORD_SIZE > self.data.len()
+        {
+            return Err(format!("Track {} record {} is outside the image", track, record));
+        }
+        self.data[offset..offset + RECORD_SIZE].copy_from_slice(&data[..RECORD_SIZE]);
+
+        if let Some(file) = self.file.as_mut()
+        {
+            file.seek(SeekFrom::Start(offset as u64))
+                .and_then(|_| file.write_all(&data[..RECORD_SIZE]))
+                .map_err(|e| format!("Failed to write image: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// The contents of the boot tracks, which hold the loader, CCP and BDOS. Boot tracks are
+    /// read in physical sector order, without skew.
+    pub fn system_tracks(&self) -> &[u8]
+    {
+        let size = self.definition.boot_tracks as usize * self.definition.sectors_per_track as usize
+            * self.definition.sector_size;
+        &self.data[..size]
+    }
+}
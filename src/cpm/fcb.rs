@@ -0,0 +1,234 @@
+use crate::i8080::Processor;
+
+/// Size of a CP/M record, the unit of every file transfer.
+pub const RECORD_SIZE: usize = 128;
+
+/// Records in one logical extent (16K).
+pub const RECORDS_PER_EXTENT: u32 = 128;
+
+/// Extents in one module, counted by the S2 byte.
+const EXTENTS_PER_MODULE: u32 = 32;
+
+const DRIVE: u16 = 0;
+const NAME: u16 = 1;
+const EXTENT: u16 = 12;
+const S1: u16 = 13;
+const MODULE: u16 = 14;
+const RECORD_COUNT: u16 = 15;
+const CURRENT_RECORD: u16 = 32;
+const RANDOM_RECORD: u16 = 33;
+
+/// An 8.3 file name as stored in a file control block: eight name and three type characters,
+/// upper case and padded with spaces.
+pub type FileName = [u8; 11];
+
+/// A file control block in the processor's memory.
+#[derive(Clone, Copy, Debug)]
+pub struct Fcb
+{
+    pub address: u16,
+}
+
+impl Fcb
+{
+    pub fn at(address: u16) -> Fcb
+    {
+        Fcb { address }
+    }
+
+    /// The address of the byte `offset` into the FCB, wrapping around the top of memory.
+    fn field(&self, offset: u16) -> u16
+    {
+        self.address.wrapping_add(offset)
+    }
+
+    /// The drive byte: 0 is the current drive, 1 is A: and so on.
+    pub fn drive(&self, processor: &Processor) -> u8
+    {
+        processor.get_memory_at(self.field(DRIVE))
+    }
+
+    /// The file name with the attribute bits stripped.
+    pub fn name(&self, processor: &Processor) -> FileName
+    {
+        self.name_at(processor, NAME)
+    }
+
+    /// The new name of a rename request, stored in the second half of the FCB.
+    pub fn rename_target(&self, processor: &Processor) -> FileName
+    {
+        self.name_at(processor, NAME + 16)
+    }
+
+    fn name_at(&self, processor: &Processor, offset: u16) -> FileName
+    {
+        let mut name = [b' '; 11];
+        for (i, c) in name.iter_mut().enumerate()
+        {
+            *c = (processor.get_memory_at(self.field(offset + i as u16)) & 0x7F).to_ascii_uppercase();
+        }
+        name
+    }
+
+    /// Resets the position to the start of the file, as done by open and make.
+    pub fn rewind(&self, processor: &mut Processor)
+    {
+        processor.set_memory_at(self.field(EXTENT), 0);
+        processor.set_memory_at(self.field(S1), 0);
+        processor.set_memory_at(self.field(MODULE), 0);
+        processor.set_memory_at(self.field(CURRENT_RECORD), 0);
+    }
+
+    /// The sequential position, made up of the module, extent and current record bytes.
+    pub fn sequential_record(&self, processor: &Processor) -> u32
+    {
+        let module = (processor.get_memory_at(self.field(MODULE)) & 0x3F) as u32;
+        let extent = (processor.get_memory_at(self.field(EXTENT)) & 0x1F) as u32;
+        let record = (processor.get_memory_at(self.field(CURRENT_RECORD)) & 0x7F) as u32;
+        (module * EXTENTS_PER_MODULE + extent) * RECORDS_PER_EXTENT + record
+    }
+
+    pub fn set_sequential_record(&self, processor: &mut Processor, record: u32)
+    {
+        let extent = record / RECORDS_PER_EXTENT;
+        processor.set_memory_at(self.field(CURRENT_RECORD), (record % RECORDS_PER_EXTENT) as u8);
+        processor.set_memory_at(self.field(EXTENT), (extent % EXTENTS_PER_MODULE) as u8);
+        processor.set_memory_at(self.field(MODULE), (extent / EXTENTS_PER_MODULE) as u8);
+    }
+
+    /// Sets the record count of the current extent from the total size of the file.
+    pub fn set_record_count(&self, processor: &mut Processor, file_records: u32)
+    {
+        let extent_start = self.sequential_record(processor) / RECORDS_PER_EXTENT * RECORDS_PER_EXTENT;
+        let count = file_records.saturating_sub(extent_start).min(RECORDS_PER_EXTENT);
+        processor.set_memory_at(self.field(RECORD_COUNT), count as u8);
+    }
+
+    /// The record number used by random reads and writes, held in R0 and R1.
+    pub fn random_record(&self, processor: &Processor) -> u32
+    {
+        let lsb = processor.get_memory_at(self.field(RANDOM_RECORD)) as u32;
+        let msb = processor.get_memory_at(self.field(RANDOM_RECORD + 1)) as u32;
+        (msb << 8) | lsb
+    }
+
+    pub fn set_random_record(&self, processor: &mut Processor, record: u32)
+    {
+        processor.set_memory_at(self.field(RANDOM_RECORD), record as u8);
+        processor.set_memory_at(self.field(RANDOM_RECORD + 1), (record >> 8) as u8);
+        processor.set_memory_at(self.field(RANDOM_RECORD + 2), (record >> 16) as u8);
+    }
+}
+
+/// Translates a host file name into an 8.3 CP/M name. Names that do not fit, or contain
+/// characters CP/M cannot represent, have no CP/M name.
+pub fn host_to_cpm_name(host_name: &str) -> Option<FileName>
+{
+    let (name, extension) = match host_name.rfind('.')
+    {
+        Some(0) => return None,
+        Some(i) => (&host_name[..i], &host_name[i + 1..]),
+        None    => (host_name, ""),
+    };
+
+    let valid = |s: &str| s.bytes().all(|c| c.is_ascii_graphic() && !b".:;<>=?*[],/\\".contains(&c));
+    if name.is_empty() || name.len() > 8 || extension.len() > 3 || !valid(name) || !valid(extension)
+    {
+        return None;
+    }
+
+    let mut cpm_name = [b' '; 11];
+    for (i, c) in name.bytes().enumerate()
+    {
+        cpm_name[i] = c.to_ascii_uppercase();
+    }
+    for (i, c) in extension.bytes().enumerate()
+    {
+        cpm_name[8 + i] = c.to_ascii_uppercase();
+    }
+    Some(cpm_name)
+}
+
+/// Translates an 8.3 CP/M name into the lower case name used when creating host files.
+pub fn cpm_to_host_name(name: &FileName) -> String
+{
+    let base = String::from_utf8_lossy(&name[..8]).trim_end().to_ascii_lowercase();
+    let extension = String::from_utf8_lossy(&name[8..]).trim_end().to_ascii_lowercase();
+
+    if extension.is_empty()
+    {
+        base
+    }
+    else
+    {
+        format!("{}.{}", base, extension)
+    }
+}
+
+/// Whether `name` becomes a host file name that translates back to it unchanged. Names with
+/// path separators, dots, control characters or high bits set do not, and must not be used
+/// to name host files.
+pub fn has_host_name(name: &FileName) -> bool
+{
+    host_to_cpm_name(&cpm_to_host_name(name)) == Some(*name)
+}
+
+/// An 8.3 name the way CP/M prints it, e.g. `FOO.ASM`.
+pub fn display_name(name: &FileName) -> String
+{
+    cpm_to_host_name(name).to_ascii_uppercase()
+}
+
+/// Matches a name against a pattern where '?' matches any character.
+pub fn name_matches(pattern: &FileName, name: &FileName) -> bool
+{
+    pattern.iter().zip(name.iter()).all(|(p, n)| *p == b'?' || p == n)
+}
+
+pub fn is_ambiguous(name: &FileName) -> bool
+{
+    name.contains(&b'?')
+}
+
+/// Parses a command line file name such as `B:FOO.ASM` into a drive code, where 0 means the
+/// current drive, and a name in which '*' has been expanded to '?'.
+pub fn parse_file_name(text: &str) -> (u8, FileName)
+{
+    let text = text.to_ascii_uppercase();
+    let bytes = text.as_bytes();
+    let (drive, text) = if bytes.len() >= 2 && bytes[1] == b':' && bytes[0].is_ascii_uppercase()
+    {
+        (bytes[0] - b'A' + 1, &text[2..])
+    }
+    else
+    {
+        (0, &text[..])
+    };
+
+    let (base, extension) = match text.find('.')
+    {
+        Some(i) => (&text[..i], &text[i + 1..]),
+        None    => (text, ""),
+    };
+
+    let mut name = [b' '; 11];
+    fill_name_field(&mut name[..8], base);
+    fill_name_field(&mut name[8..], extension);
+    (drive, name)
+}
+
+fn fill_name_field(field: &mut [u8], text: &str)
+{
+    for (i, c) in text.bytes().take(field.len()).enumerate()
+    {
+        if c == b'*'
+        {
+            for f in field[i..].iter_mut()
+            {
+                *f = b'?';
+            }
+            return;
+        }
+        field[i] = c;
+    }
+}
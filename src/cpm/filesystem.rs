@@ -0,0 +1,368 @@
+use crate::cpm::disk::*;
+use crate::cpm::fcb::*;
+
+/// User number marking a free or deleted directory entry.
+const DELETED: u8 = 0xE5;
+
+/// Highest user number a file can belong to.
+const MAX_USER: u8 = 15;
+
+/// A file found in the directory, gathered from all of its extents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CpmFile
+{
+    pub user:    u8,
+    pub name:    FileName,
+    pub records: u32,
+    pub blocks:  Vec<u16>,
+}
+
+impl CpmFile
+{
+    pub fn size(&self) -> usize
+    {
+        self.records as usize * RECORD_SIZE
+    }
+}
+
+/// A raw directory entry: user, name, extent bytes, record count and block pointers.
+#[derive(Clone, Copy, Debug)]
+struct DirectoryEntry
+{
+    index: usize,
+    bytes: [u8; DIRECTORY_ENTRY_SIZE],
+}
+
+impl DirectoryEntry
+{
+    fn user(&self) -> u8
+    {
+        self.bytes[0]
+    }
+
+    fn name(&self) -> FileName
+    {
+        let mut name = [0u8; 11];
+        for (i, c) in name.iter_mut().enumerate()
+        {
+            *c = self.bytes[1 + i] & 0x7F;
+        }
+        name
+    }
+
+    /// The logical extent number of the last extent in this entry, from the EX and S2 bytes.
+    fn extent(&self) -> u32
+    {
+        (self.bytes[14] as u32 & 0x3F) * 32 + (self.bytes[12] as u32 & 0x1F)
+    }
+
+    fn record_count(&self) -> u32
+    {
+        self.bytes[15] as u32
+    }
+
+    fn blocks(&self, word_blocks: bool) -> Vec<u16>
+    {
+        let pointers = &self.bytes[16..32];
+        let blocks: Vec<u16> = if word_blocks
+        {
+            pointers.chunks(2).map(|p| p[0] as u16 | (p[1] as u16) << 8).collect()
+        }
+        else
+        {
+            pointers.iter().map(|p| *p as u16).collect()
+        };
+        blocks.into_iter().filter(|block| *block != 0).collect()
+    }
+}
+
+/// Parses a file name with an optional user number prefix, such as `3:FOO.TXT`.
+pub fn parse_user_file_name(text: &str) -> Result<(u8, FileName), String>
+{
+    let (user, text) = match text.split_once(':')
+    {
+        Some((user, name)) if !user.is_empty() && user.bytes().all(|c| c.is_ascii_digit()) =>
+        {
+            let user = user.parse::<u8>().map_err(|e| e.to_string())?;
+            (user, name)
+        },
+        _ => (0, text),
+    };
+
+    if user > MAX_USER
+    {
+        return Err(format!("User number {} is out of range", user));
+    }
+    let (_, name) = parse_file_name(text);
+    Ok((user, name))
+}
+
+/// The CP/M file system on a disk image: the directory in the first blocks after the boot
+/// tracks and the allocation blocks it points to.
+pub struct FileSystem<'a>
+{
+    image: &'a mut DiskImage,
+}
+
+impl<'a> FileSystem<'a>
+{
+    pub fn new(image: &'a mut DiskImage) -> FileSystem<'a>
+    {
+        FileSystem { image }
+    }
+
+    /// All files on the disk, sorted by user and name.
+    pub fn files(&self) -> Vec<CpmFile>
+    {
+        let word_blocks = self.image.definition.has_word_block_numbers();
+        let mut entries: Vec<DirectoryEntry> = self.directory().into_iter()
+            .filter(|entry| entry.user() <= MAX_USER)
+            .collect();
+        entries.sort_by_key(|entry| (entry.user(), entry.name(), entry.extent()));
+
+        let mut files: Vec<CpmFile> = Vec::new();
+        for entry in entries
+        {
+            let records = entry.extent() * RECORDS_PER_EXTENT + entry.record_count();
+            match files.last_mut()
+            {
+                Some(file) if file.user == entry.user() && file.name == entry.name() =>
+                {
+                    file.records = records;
+                    file.blocks.extend(entry.blocks(word_blocks));
+                },
+                _ => files.push(CpmFile
+                {
+                    user:   entry.user(),
+                    name:   entry.name(),
+                    records,
+                    blocks: entry.blocks(word_blocks),
+                }),
+            }
+        }
+        files
+    }
+
+    /// The files matching a possibly ambiguous name, within one user area.
+    pub fn find(&self, user: u8, pattern: &FileName) -> Vec<CpmFile>
+    {
+        self.files().into_iter()
+            .filter(|file| file.user == user && name_matches(pattern, &file.name))
+            .collect()
+    }
+
+    /// Reads the contents of a file. The size is a whole number of records.
+    pub fn read_file(&self, file: &CpmFile) -> Vec<u8>
+    {
+        let mut data = Vec::new();
+        for block in file.blocks.iter()
+        {
+            data.extend(self.read_block(*block));
+        }
+        data.resize(file.size(), 0x1A);
+        data
+    }
+
+    /// Number of unused allocation blocks.
+    pub fn free_blocks(&self) -> usize
+    {
+        self.allocation().iter().filter(|used| !**used).count()
+    }
+
+    /// Writes a file, replacing any file with the same name in the user area.
+    pub fn write_file(&mut self, user: u8, name: &FileName, data: &[u8]) -> Result<(), String>
+    {
+        if is_ambiguous(name)
+        {
+            return Err("Can not write a file with a wildcard name".to_string());
+        }
+        if user > MAX_USER
+        {
+            return Err(format!("User number {} is out of range", user));
+        }
+        self.delete(user, name);
+
+        let definition = &self.image.definition;
+        let block_size = definition.block_size;
+        let pointers_per_entry = if definition.has_word_block_numbers() { 8 } else { 16 };
+        let extents_per_entry = definition.extents_per_entry() as u32;
+        let records = data.len().div_ceil(RECORD_SIZE) as u32;
+        let blocks_needed = data.len().div_ceil(block_size);
+        let entries_needed = blocks_needed.div_ceil(pointers_per_entry).max(1);
+
+        let free_blocks: Vec<u16> = self.allocation().iter().enumerate()
+            .filter(|(_, used)| !**used)
+            .map(|(block, _)| block as u16)
+            .collect();
+        let free_entries: Vec<usize> = self.directory().iter()
+            .filter(|entry| entry.user() == DELETED)
+            .map(|entry| entry.index)
+            .collect();
+
+        if free_blocks.len() < blocks_needed
+        {
+            return Err("Disk full".to_string());
+        }
+        if free_entries.len() < entries_needed
+        {
+            return Err("Directory full".to_string());
+        }
+
+        let mut free_blocks = free_blocks.into_iter();
+        let word_blocks = self.image.definition.has_word_block_numbers();
+        let records_per_entry = extents_per_entry * RECORDS_PER_EXTENT;
+        for (i, entry_index) in free_entries.iter().take(entries_needed).enumerate()
+        {
+            let first_record = i as u32 * records_per_entry;
+            let entry_records = records.saturating_sub(first_record).min(records_per_entry);
+            let last_extent = i as u32 * extents_per_entry + entry_records.saturating_sub(1) / RECORDS_PER_EXTENT;
+
+            let mut bytes = [0u8; DIRECTORY_ENTRY_SIZE];
+            bytes[0] = user;
+            bytes[1..12].copy_from_slice(name);
+            bytes[12] = (last_extent % 32) as u8;
+            bytes[14] = (last_extent / 32) as u8;
+            bytes[15] = (entry_records - (last_extent - i as u32 * extents_per_entry) * RECORDS_PER_EXTENT) as u8;
+
+            let entry_blocks = (entry_records as usize * RECORD_SIZE).div_ceil(block_size);
+            for pointer in 0..entry_blocks
+            {
+                let block = free_blocks.next().expect("Counted free blocks ran out");
+                let start = ((i * pointers_per_entry + pointer) * block_size).min(data.len());
+                let end = (start + block_size).min(data.len());
+                self.write_block(block, &data[start..end])?;
+
+                if word_blocks
+                {
+                    bytes[16 + pointer * 2] = block as u8;
+                    bytes[17 + pointer * 2] = (block >> 8) as u8;
+                }
+                else
+                {
+                    bytes[16 + pointer] = block as u8;
+                }
+            }
+            self.write_entry(*entry_index, &bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes all files matching the name in the user area, returning how many there were.
+    pub fn delete(&mut self, user: u8, pattern: &FileName) -> usize
+    {
+        let entries: Vec<DirectoryEntry> = self.directory().into_iter()
+            .filter(|entry| entry.user() == user && name_matches(pattern, &entry.name()))
+            .collect();
+
+        let mut names: Vec<FileName> = entries.iter().map(|entry| entry.name()).collect();
+        names.sort();
+        names.dedup();
+
+        for entry in entries
+        {
+            let mut bytes = entry.bytes;
+            bytes[0] = DELETED;
+            // The entry lies within the directory, so the write can not fail
+            let _ = self.write_entry(entry.index, &bytes);
+        }
+        names.len()
+    }
+
+    fn directory(&self) -> Vec<DirectoryEntry>
+    {
+        let definition = &self.image.definition;
+        let entries = definition.directory_entries as usize;
+        let mut data = Vec::new();
+        for block in 0..definition.directory_blocks()
+        {
+            data.extend(self.read_block(block as u16));
+        }
+
+        data.chunks(DIRECTORY_ENTRY_SIZE)
+            .take(entries)
+            .enumerate()
+            .map(|(index, chunk)|
+            {
+                let mut bytes = [0u8; DIRECTORY_ENTRY_SIZE];
+                bytes.copy_from_slice(chunk);
+                DirectoryEntry { index, bytes }
+            })
+            .collect()
+    }
+
+    /// Which blocks are in use, by the directory itself or by a file.
+    fn allocation(&self) -> Vec<bool>
+    {
+        let definition = &self.image.definition;
+        let mut used = vec![false; definition.blocks()];
+        for block in used.iter_mut().take(definition.directory_blocks())
+        {
+            *block = true;
+        }
+
+        let word_blocks = definition.has_word_block_numbers();
+        for entry in self.directory().iter().filter(|entry| entry.user() <= MAX_USER)
+        {
+            for block in entry.blocks(word_blocks)
+            {
+                if let Some(used) = used.get_mut(block as usize)
+                {
+                    *used = true;
+                }
+            }
+        }
+        used
+    }
+
+    /// The track and record of the `index`th record of the data area.
+    fn locate(&self, index: usize) -> (u16, u16)
+    {
+        let definition = &self.image.definition;
+        let records_per_track = definition.records_per_track() as usize;
+        ((definition.boot_tracks as usize + index / records_per_track) as u16, (index % records_per_track) as u16)
+    }
+
+    fn read_block(&self, block: u16) -> Vec<u8>
+    {
+        let records_per_block = self.image.definition.records_per_block();
+        let mut data = Vec::with_capacity(self.image.definition.block_size);
+        for i in 0..records_per_block
+        {
+            let (track, record) = self.locate(block as usize * records_per_block + i);
+            match self.image.read_record(track, record)
+            {
+                Some(bytes) => data.extend_from_slice(bytes),
+                None => data.extend_from_slice(&[0xE5; RECORD_SIZE]),
+            }
+        }
+        data
+    }
+
+    /// Writes up to a block of data, padding the last record with ^Z.
+    fn write_block(&mut self, block: u16, data: &[u8]) -> Result<(), String>
+    {
+        let records_per_block = self.image.definition.records_per_block();
+        for (i, chunk) in data.chunks(RECORD_SIZE).enumerate().take(records_per_block)
+        {
+            let mut record_data = [0x1A; RECORD_SIZE];
+            record_data[..chunk.len()].copy_from_slice(chunk);
+            let (track, record) = self.locate(block as usize * records_per_block + i);
+            self.image.write_record(track, record, &record_data)?;
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, index: usize, bytes: &[u8; DIRECTORY_ENTRY_SIZE]) -> Result<(), String>
+    {
+        let entries_per_record = RECORD_SIZE / DIRECTORY_ENTRY_SIZE;
+        let (track, record) = self.locate(index / entries_per_record);
+        let mut data = match self.image.read_record(track, record)
+        {
+            Some(data) => data.to_vec(),
+            None => return Err(format!("Directory entry {} is outside the image", index)),
+        };
+        let offset = (index % entries_per_record) * DIRECTORY_ENTRY_SIZE;
+        data[offset..offset + DIRECTORY_ENTRY_SIZE].copy_from_slice(bytes);
+        self.image.write_record(track, record, &data)
+    }
+}
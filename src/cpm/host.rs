@@ -0,0 +1,139 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cpm::fcb::*;
+
+const CTRL_Z: u8 = 0x1A;
+
+/// A CP/M drive backed by a directory on the host. Files are looked up case insensitively
+/// through their 8.3 names; host files that have no 8.3 name are invisible to CP/M.
+#[derive(Clone, Debug)]
+pub struct HostDrive
+{
+    root: PathBuf,
+}
+
+impl HostDrive
+{
+    pub fn new<P: AsRef<Path>>(root: P) -> HostDrive
+    {
+        HostDrive { root: root.as_ref().to_path_buf() }
+    }
+
+    pub fn root(&self) -> &Path
+    {
+        &self.root
+    }
+
+    /// All files of the directory that have a CP/M name, sorted by that name.
+    pub fn list(&self) -> Vec<(FileName, PathBuf)>
+    {
+        let mut files = Vec::new();
+        let entries = match fs::read_dir(&self.root)
+        {
+            Ok(entries) => entries,
+            Err(_) => return files,
+        };
+
+        for entry in entries.flatten()
+        {
+            let path = entry.path();
+            if !path.is_file()
+            {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str().and_then(host_to_cpm_name)
+            {
+                files.push((name, path));
+            }
+        }
+        files.sort();
+        files
+    }
+
+    /// The host files matching a possibly ambiguous CP/M name.
+    pub fn find_all(&self, pattern: &FileName) -> Vec<(FileName, PathBuf)>
+    {
+        self.list().into_iter().filter(|(name, _)| name_matches(pattern, name)).collect()
+    }
+
+    pub fn find(&self, name: &FileName) -> Option<PathBuf>
+    {
+        self.list().into_iter().find(|(n, _)| n == name).map(|(_, path)| path)
+    }
+
+    /// Creates an empty file, replacing any existing file with the same CP/M name. Names that
+    /// have no host name, see `has_host_name`, are not created.
+    pub fn create(&self, name: &FileName) -> Option<PathBuf>
+    {
+        if !has_host_name(name)
+        {
+            return None;
+        }
+        let path = self.find(name).unwrap_or_else(|| self.root.join(cpm_to_host_name(name)));
+        File::create(&path).ok()?;
+        Some(path)
+    }
+
+    pub fn rename(&self, from: &FileName, to: &FileName) -> bool
+    {
+        if !has_host_name(to) || self.find(to).is_some()
+        {
+            return false;
+        }
+        match self.find(from)
+        {
+            Some(path) => fs::rename(path, self.root.join(cpm_to_host_name(to))).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Size of a host file in records, rounding a partial last record up.
+pub fn size_in_records(path: &Path) -> u32
+{
+    match fs::metadata(path)
+    {
+        Ok(metadata) => metadata.len().div_ceil(RECORD_SIZE as u64) as u32,
+        Err(_) => 0,
+    }
+}
+
+/// Reads one record. A partial last record is padded with ^Z, the CP/M end of file marker.
+/// Returns None past the end of the file.
+pub fn read_record(path: &Path, record: u32) -> Option<[u8; RECORD_SIZE]>
+{
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(record as u64 * RECORD_SIZE as u64)).ok()?;
+
+    let mut buffer = [CTRL_Z; RECORD_SIZE];
+    let mut read = 0;
+    while read < RECORD_SIZE
+    {
+        match file.read(&mut buffer[read..])
+        {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(_) => return None,
+        }
+    }
+
+    if read == 0
+    {
+        return None;
+    }
+    Some(buffer)
+}
+
+pub fn write_record(path: &Path, record: u32, data: &[u8]) -> bool
+{
+    let mut file = match OpenOptions::new().write(true).open(path)
+    {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+
+    file.seek(SeekFrom::Start(record as u64 * RECORD_SIZE as u64)).is_ok()
+        && file.write_all(data).is_ok()
+}
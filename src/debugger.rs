@@ -1,12 +1,82 @@
 use crate::i8080::Processor;
+use crate::machines::Machine;
+use crate::machines::screenshot::save;
+use crate::image::{Format, Image};
+use crate::assembler::assemble_instruction;
+use crate::assembler::expression::evaluate_defined;
+use crate::symbols::SymbolTable;
+use crate::disassembler::{decode, decode_before, decode_from, Flow};
+use crate::coverage::{Coverage, Step};
+use crate::profiler::Profiler;
+use crate::call_stack::CallStack;
 
 mod disassembler;
+pub mod front_panel;
+pub mod screen;
 use std::io::{stdin, Write, stdout};
+use screen::{FrameLimiter, RenderMode};
+
+/// Lines in the instructions pane.
+const INSTRUCTION_LINES: usize = 47;
+/// Instructions shown before PC.
+const INSTRUCTIONS_BEFORE_PC: i32 = 10;
+/// Lines the instructions pane scrolls at most from PC either way.
+const MAX_SCROLL: i32 = 0x400;
+/// Bytes in each region the coverage command reports on.
+const COVERAGE_REGION: u16 = 0x400;
+/// Addresses and subroutines a profile report lists by default.
+const PROFILE_LINES: usize = 10;
+/// Mismatches of the shadow call stack shown under the backtrace.
+const BACKTRACE_MISMATCHES: usize = 3;
+
+/// Where running stops, besides at breakpoints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target
+{
+    None,
+    /// PC at `address` with SP at or above `stack_pointer`, so deeper calls that pass by
+    /// the same address do not stop.
+    Return { address: u16, stack_pointer: u16 },
+    Address(u16),
+}
+
+impl Target
+{
+    /// Whether `processor` stopped at the target. SP is compared by the distance it moved,
+    /// so a stack that wraps below 0000H is still deeper.
+    pub fn reached(&self, processor: &Processor) -> bool
+    {
+        let pc = processor.get_pc();
+        match *self
+        {
+            Target::None => false,
+            Target::Return { address, stack_pointer } => pc == address && processor.get_stack_pointer().wrapping_sub(stack_pointer) as i16 >= 0,
+            Target::Address(address) => pc == address,
+        }
+    }
+}
 
 pub struct Debugger<'a>
 {
     breakpoints: Vec<u16>,
     disassembler: disassembler::Disassembler<'a>,
+    machine: Option<Box<dyn Machine + 'a>>,
+    screen_mode: RenderMode,
+    screen_limiter: FrameLimiter,
+    symbols: SymbolTable,
+    /// Lines the instructions pane is scrolled down from PC, up when negative.
+    scroll: i32,
+    /// Whether the instructions pane follows jumps.
+    follow: bool,
+    /// How the instructions run so far used memory.
+    coverage: Coverage,
+    profiler: Profiler,
+    /// Whether instructions are counted in the profile as they run.
+    profiling: bool,
+    /// Where the folded stacks of the profile are written on quitting.
+    profile_output: Option<std::path::PathBuf>,
+    /// The calls followed as instructions run, for the backtrace pane.
+    call_stack: CallStack,
 }
 
 impl<'a> Debugger<'a>
@@ -17,10 +87,44 @@ impl<'a> Debugger<'a>
         {
             breakpoints: Vec::new(),
             disassembler: disassembler::Disassembler::default(),
+            machine: None,
+            screen_mode: RenderMode::Braille,
+            screen_limiter: FrameLimiter::new(10),
+            symbols: SymbolTable::default(),
+            scroll: 0,
+            follow: true,
+            coverage: Coverage::default(),
+            profiler: Profiler::default(),
+            profiling: false,
+            profile_output: None,
+            call_stack: CallStack::default(),
         };
         return dgb
     }
 
+    /// Runs the processor inside `machine`, so its devices and interrupts work, and shows the
+    /// machine's screen next to the memory pane. While running, the screen is refreshed at
+    /// most `fps` times per second.
+    pub fn attach_machine(&mut self, machine: Box<dyn Machine + 'a>, mode: RenderMode, fps: u32)
+    {
+        self.machine = Some(machine);
+        self.screen_mode = mode;
+        self.screen_limiter = FrameLimiter::new(fps);
+    }
+
+    /// Adds symbols to show in the disassembly and to use in addresses.
+    pub fn add_symbols(&mut self, symbols: &SymbolTable)
+    {
+        self.symbols.extend(symbols);
+    }
+
+    /// Profiles from the first instruction, writing the folded stacks to `output` on quitting.
+    pub fn profile_to(&mut self, output: std::path::PathBuf)
+    {
+        self.profiling = true;
+        self.profile_output = Some(output);
+    }
+
     pub fn execute(&mut self, processor: &mut Processor, first_execution: bool) -> Option<u8>
     {
         if first_execution
@@ -30,7 +134,8 @@ impl<'a> Debugger<'a>
         }
 
         let mut ret: Option<u8> = Some(0);
-        let inputs = get_input();
+        self.disassembler.set_output(Vec::new());
+        let inputs = get_input("> ");
         let mut inputs = inputs.split_whitespace();
         let input;
 
@@ -43,12 +148,24 @@ impl<'a> Debugger<'a>
 
         match input
         {
-            "s" | "step"       => step(processor, inputs.next()),
-            "q" | "quit"       => ret = None,
-            "c" | "continue"   => self.run_processor(processor),
-            "b" | "breakpoint" => self.add_breakpoint(inputs.next()),
-            "r" | "reset"      => reset_processor(processor),
-            "m" | "mem"        => self.disassembler.set_memory(input_to_u16(inputs.next()), &processor.get_memory()),
+            "s" | "step"       => self.step(processor, inputs.next()),
+            "q" | "quit"       => ret = self.quit(),
+            "c" | "continue"   => self.run_processor(processor, Target::None),
+            "n" | "next"       => self.next(processor),
+            "o" | "finish"     => self.finish(processor),
+            "u" | "until"      => self.until(processor, inputs.next()),
+            "b" | "breakpoint" => self.add_breakpoint(processor, inputs.next()),
+            "r" | "reset"      => self.reset(processor),
+            "m" | "mem"        => self.show_memory(processor, inputs.next()),
+            "d" | "dump"       => self.dump_screen(processor, inputs.next(), inputs.next()),
+            "w" | "write"      => self.write_memory(processor, inputs.next(), inputs.next(), inputs.next(), inputs.next()),
+            "a" | "assemble"   => self.assemble(processor, inputs.next()),
+            "y" | "symbols"    => self.load_symbols(inputs.next()),
+            "k" | "up"         => self.scroll_by(-inputs.next().and_then(|lines| lines.parse::<i32>().ok()).unwrap_or(10).saturating_abs()),
+            "j" | "down"       => self.scroll_by(inputs.next().and_then(|lines| lines.parse::<i32>().ok()).unwrap_or(10).saturating_abs()),
+            "f" | "follow"     => self.follow = !self.follow,
+            "v" | "coverage"   => self.show_coverage(processor, inputs.next(), inputs.next()),
+            "p" | "profile"    => self.profile(inputs.next(), inputs.next()),
         
             _ => (),
         }
@@ -57,17 +174,17 @@ impl<'a> Debugger<'a>
         return ret
     }
 
-    fn add_breakpoint(&mut self, breakpoint: Option<&str>)
+    fn add_breakpoint(&mut self, processor: &Processor, breakpoint: Option<&str>)
     {
 
         match breakpoint
         {
             Some(breakpoint) => 
             {
-                let breakpoint = match breakpoint.parse::<u16>()
+                let breakpoint = match self.address(processor, breakpoint)
                 {
                     Ok(breakpoint) => breakpoint,
-                    Err(_) => return,
+                    Err(e) => return self.disassembler.set_output(vec![e]),
                 };
                 self.breakpoints.push(breakpoint)
             },
@@ -75,12 +192,295 @@ impl<'a> Debugger<'a>
         };
     }
 
+    /// Points the memory pane at an address, 0 if none is given.
+    fn show_memory(&mut self, processor: &Processor, address: Option<&str>)
+    {
+        match self.address(processor, address.unwrap_or("0"))
+        {
+            Ok(address) => self.disassembler.set_memory(address, &processor.get_memory()),
+            Err(e) => self.disassembler.set_output(vec![e]),
+        }
+    }
+
+    /// Scrolls the instructions pane by `lines`, down when positive, no further than
+    /// MAX_SCROLL from PC.
+    fn scroll_by(&mut self, lines: i32)
+    {
+        self.scroll = self.scroll.saturating_add(lines).clamp(-MAX_SCROLL, MAX_SCROLL);
+    }
+
+    /// Reads a symbol file, in any format `SymbolTable::parse` knows.
+    fn load_symbols(&mut self, path: Option<&str>)
+    {
+        let message = match path.map(|path| SymbolTable::load(std::path::Path::new(path)))
+        {
+            Some(Ok(symbols)) =>
+            {
+                self.symbols.extend(&symbols);
+                format!("Loaded {} symbols", symbols.len())
+            },
+            Some(Err(e)) => e,
+            None => "Usage: symbols <file>".to_string(),
+        };
+        self.disassembler.set_output(vec![message]);
+    }
+
+    /// An address typed in a command: a number in decimal, in hex with 0x or H, a symbol, or
+    /// an expression of them, with `$` for PC.
+    fn address(&self, processor: &Processor, text: &str) -> Result<u16, String>
+    {
+        let value = evaluate_defined(text, processor.get_pc(), &|name| self.symbols.get_address(name))?;
+        Ok(value as u16)
+    }
+
+
+    /// Saves the machine's screen to a .png or .ppm file, through its colour overlay if
+    /// "color" follows the file name.
+    fn dump_screen(&mut self, processor: &Processor, path: Option<&str>, color: Option<&str>)
+    {
+        let message = match (self.machine.as_ref(), path)
+        {
+            (None, _) => "There is no machine with a screen".to_string(),
+            (_, None) => "Usage: dump <file.png|file.ppm> [color]".to_string(),
+            (Some(machine), Some(path)) =>
+            {
+                let overlay = if color == Some("color") { machine.color_overlay() } else { None };
+                let saved = match machine.framebuffer(processor)
+                {
+                    Some(framebuffer) => save(std::path::Path::new(path), &framebuffer, overlay.as_ref()),
+                    None => Err("The machine has no screen".to_string()),
+                };
+                match saved
+                {
+                    Ok(()) => format!("Saved {}", path),
+                    Err(e) => e,
+                }
+            },
+        };
+        self.disassembler.set_output(vec![message]);
+    }
+
+    /// Saves memory from `start` to `end` inclusive as a binary or Intel HEX file, picked by
+    /// the extension of the file name or by "bin" or "hex" after the range.
+    fn write_memory(&mut self, processor: &Processor, path: Option<&str>, start: Option<&str>, end: Option<&str>,
+                    format: Option<&str>)
+    {
+        let message = match (path, start, end)
+        {
+            (Some(path), Some(start), Some(end)) =>
+            {
+                let saved = format.map(Format::parse).transpose().and_then(|format|
+                {
+                    let range = (self.address(processor, start)?, self.address(processor, end)?);
+                    Image::from_memory(processor, &[range]).save(std::path::Path::new(path), format)
+                });
+                match saved
+                {
+                    Ok(()) => format!("Saved {}", path),
+                    Err(e) => e,
+                }
+            },
+            _ => "Usage: write <file> <start> <end> [bin|hex]".to_string(),
+        };
+        self.disassembler.set_output(vec![message]);
+    }
+
+    /// Assembles the lines typed into memory from `address`, or from PC, each at the address
+    /// after the one before, until an empty line.
+    fn assemble(&mut self, processor: &mut Processor, address: Option<&str>)
+    {
+        let mut address = match address.map(|address| self.address(processor, address))
+        {
+            Some(Ok(address)) => address,
+            Some(Err(e))      => return self.disassembler.set_output(vec![e]),
+            None              => processor.get_pc(),
+        };
+        loop
+        {
+            let line = get_input(&format!("{:04X}> ", address));
+            if line.trim().is_empty()
+            {
+                return;
+            }
+            match assemble_instruction(&line, address, &self.symbols)
+            {
+                Ok(bytes) =>
+                {
+                    let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    self.disassembler.set_output(vec![format!("{:04X}: {}", address, hex.join(" "))]);
+                    for byte in bytes
+                    {
+                        processor.set_memory_at(address, byte);
+                        address = address.wrapping_add(1);
+                    }
+                },
+                Err(e) => self.disassembler.set_output(vec![e]),
+            }
+            self.disassembler.refresh_memory(&processor.get_memory());
+            self.update_disassembler(processor);
+        }
+    }
+
+    fn clock(&mut self, processor: &mut Processor)
+    {
+        let step = Step::new(processor);
+        match self.machine.as_mut()
+        {
+            Some(machine) => machine.clock(processor),
+            None          => processor.clock(),
+        }
+        self.coverage.record(&step, processor);
+        self.call_stack.update(&step, processor);
+        if self.profiling
+        {
+            self.profiler.record(&step, processor);
+        }
+    }
+
+    /// Shows how much of memory was executed, read and written: of the range from `first`
+    /// to `second`, or of each 1K that was used at all. `save <file>` writes the coverage,
+    /// `load <file>` merges in that of another run and `clear` forgets it.
+    fn show_coverage(&mut self, processor: &Processor, first: Option<&str>, second: Option<&str>)
+    {
+        let output = match (first, second)
+        {
+            (Some("save"), Some(path)) => vec![self.coverage.save(std::path::Path::new(path)).map_or_else(|e| e, |()| format!("Saved {}", path))],
+            (Some("load"), Some(path)) => match Coverage::load(std::path::Path::new(path))
+            {
+                Ok(coverage) =>
+                {
+                    self.coverage.merge(&coverage);
+                    vec![format!("Merged {}", path)]
+                },
+                Err(e) => vec![e],
+            },
+            (Some("clear"), None) =>
+            {
+                self.coverage = Coverage::default();
+                vec!["Cleared coverage".to_string()]
+            },
+            (Some(start), Some(end)) => match (self.address(processor, start), self.address(processor, end))
+            {
+                (Ok(start), Ok(end)) => vec![self.coverage_line(start, end)],
+                (Err(e), _) | (_, Err(e)) => vec![e],
+            },
+            (None, _) =>
+            {
+                let used = (0..=0xFFFFu16).step_by(COVERAGE_REGION as usize).filter(|start| self.coverage.percentages(*start, start + (COVERAGE_REGION - 1))[3] < 100.0);
+                let lines: Vec<String> = used.map(|start| self.coverage_line(start, start + (COVERAGE_REGION - 1))).collect();
+                if lines.is_empty() { vec!["Nothing has run".to_string()] } else { lines }
+            },
+            _ => vec!["Usage: coverage [<start> <end> | save <file> | load <file> | clear]".to_string()],
+        };
+        self.disassembler.set_output(output);
+    }
+
+    /// `start` profiles anew, `stop` pauses, `report [N]` shows the top N addresses and
+    /// subroutines by cycles and `save <file>` writes the folded stacks.
+    fn profile(&mut self, command: Option<&str>, argument: Option<&str>)
+    {
+        let output = match (command, argument)
+        {
+            (Some("start"), _) =>
+            {
+                self.profiler = Profiler::default();
+                self.profiling = true;
+                vec!["Profiling".to_string()]
+            },
+            (Some("stop"), _) =>
+            {
+                self.profiling = false;
+                vec!["Stopped profiling".to_string()]
+            },
+            (Some("report"), count) => self.profiler.report(&self.symbols, count.and_then(|count| count.parse().ok()).unwrap_or(PROFILE_LINES)),
+            (Some("save"), Some(path)) => vec![self.profiler.save_folded(std::path::Path::new(path), &self.symbols).map_or_else(|e| e, |()| format!("Saved {}", path))],
+            _ => vec!["Usage: profile start | stop | report [N] | save <file>".to_string()],
+        };
+        self.disassembler.set_output(output);
+    }
+
+    /// Writes the profile if it was asked for from the command line.
+    fn quit(&mut self) -> Option<u8>
+    {
+        if let Some(path) = self.profile_output.as_ref()
+        {
+            if let Err(e) = self.profiler.save_folded(path, &self.symbols)
+            {
+                eprintln!("{}", e);
+            }
+        }
+        None
+    }
+
+    fn coverage_line(&self, start: u16, end: u16) -> String
+    {
+        let [executed, read, written, unused] = self.coverage.percentages(start, end);
+        format!("{:04X}-{:04X}  executed {:5.1}%  read {:5.1}%  written {:5.1}%  unused {:5.1}%", start, end, executed, read, written, unused)
+    }
+
+    fn step(&mut self, processor: &mut Processor, steps: Option<&str>)
+    {
+        self.scroll = 0;
+        let steps = match steps
+        {
+            Some(steps) => steps.parse::<u32>().unwrap_or(1),
+            None        => 1,
+        };
+
+        for _ in 0..steps
+        {
+            self.clock(processor);
+        }
+    }
+
+    /// Steps over a CALL, conditional call or RST, running until it returns to the next
+    /// instruction at the same stack depth. Other instructions are stepped.
+    fn next(&mut self, processor: &mut Processor)
+    {
+        let line = decode(&|address| processor.get_memory_at(address), processor.get_pc());
+        match line.get_flow()
+        {
+            Flow::Call(_) => self.run_processor(processor, Target::Return { address: line.get_next(), stack_pointer: processor.get_stack_pointer() }),
+            _ => self.step(processor, None),
+        }
+    }
+
+    /// Runs until the subroutine or interrupt the backtrace shows innermost returns.
+    fn finish(&mut self, processor: &mut Processor)
+    {
+        match self.call_stack.get_frames().last().copied()
+        {
+            Some(frame) => self.run_processor(processor, Target::Return { address: frame.return_address, stack_pointer: frame.stack_pointer.wrapping_add(2) }),
+            None => self.disassembler.set_output(vec!["Not in a subroutine".to_string()]),
+        }
+    }
+
+    /// Runs until PC is at `address`.
+    fn until(&mut self, processor: &mut Processor, address: Option<&str>)
+    {
+        match address.map(|address| self.address(processor, address))
+        {
+            Some(Ok(address)) => self.run_processor(processor, Target::Address(address)),
+            Some(Err(e))      => self.disassembler.set_output(vec![e]),
+            None              => self.disassembler.set_output(vec!["Usage: until <address>".to_string()]),
+        }
+    }
 
-    fn run_processor(&mut self, processor: &mut Processor)
+    /// Runs until `target` is reached or a breakpoint is hit.
+    fn run_processor(&mut self, processor: &mut Processor, target: Target)
     {
+        self.scroll = 0;
         loop
         {
-            processor.clock();
+            self.clock(processor);
+            if self.machine.is_some() && self.screen_limiter.ready()
+            {
+                self.update_disassembler(processor);
+            }
+            if target.reached(processor)
+            {
+                return;
+            }
             let pc = processor.get_pc();
             let instruction_length = processor.get_current_op().get_length() ;
 
@@ -95,52 +495,90 @@ impl<'a> Debugger<'a>
         }
     }
 
+    /// The instructions pane: the instructions around PC, decoded from memory without running
+    /// them, with a line for each symbol and breakpoints marked with `*`.
+    fn get_instructions(&self, processor: &Processor) -> Vec<String>
+    {
+        let read = |address| processor.get_memory_at(address);
+        let pc = processor.get_pc();
+        let first = self.scroll - INSTRUCTIONS_BEFORE_PC;
+        let mut lines = decode_before(&read, pc, (-first).max(0) as usize);
+        let skipped = first.max(0) as usize;
+        lines.extend(decode_from(&read, pc, INSTRUCTION_LINES + skipped, self.follow).into_iter().skip(skipped));
+
+        let mut instructions = vec!["".to_string()];
+        for line in lines.iter()
+        {
+            if let Some(label) = self.symbols.get_name(line.address)
+            {
+                instructions.push(format!("{}:", label));
+            }
+            let marker = match (line.address == pc, self.breakpoints.contains(&line.address))
+            {
+                (true, _)      => ">>>",
+                (false, true)  => " * ",
+                (false, false) => "   ",
+            };
+            let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            instructions.push(format!("{}{:04X}  {:<8}  {}", marker, line.address, bytes.join(" "), line.format(&self.symbols)));
+        }
+        instructions.truncate(INSTRUCTION_LINES + 1);
+        instructions
+    }
+
+    /// The backtrace pane: PC, then the return address of each call with the subroutine it
+    /// is in, innermost first, and the latest mismatches marked with `!`.
+    fn get_backtrace(&self, processor: &Processor) -> Vec<String>
+    {
+        let describe = |address: u16| self.symbols.describe(address).unwrap_or_default();
+        let mut lines = vec![format!("    {:04X}  {}", processor.get_pc(), describe(processor.get_pc()))];
+        for frame in self.call_stack.get_frames().iter().rev()
+        {
+            let entry = self.symbols.get_name(frame.entry).map_or_else(|| format!("{:04X}", frame.entry), str::to_string);
+            let kind = if frame.interrupt { "interrupt" } else { "called" };
+            lines.push(format!("    {:04X}  {:<16} {} {}", frame.return_address, describe(frame.return_address), kind, entry));
+        }
+        for mismatch in self.call_stack.get_mismatches().iter().rev().take(BACKTRACE_MISMATCHES)
+        {
+            lines.push(format!("!   {:04X}  {}", mismatch.address, mismatch.message));
+        }
+        lines
+    }
+
+    fn reset(&mut self, processor: &mut Processor)
+    {
+        reset_processor(processor);
+        self.call_stack = CallStack::default();
+    }
+
     fn update_disassembler(&mut self, processor: &mut Processor)
     {
-        self.disassembler.update_instructions(get_instructions(processor));
+        self.disassembler.update_instructions(self.get_instructions(processor));
+        self.disassembler.set_backtrace(self.get_backtrace(processor));
         self.disassembler.set_stack_pointer(processor.get_stack_pointer());
         self.disassembler.set_flags(&processor.get_flags());
         self.disassembler.set_regs(&processor.get_registers());
         self.disassembler.set_pc(processor.get_pc());
         self.disassembler.set_direct_address(processor.get_direct_address());
         self.disassembler.set_immediate(processor.get_immediate());
+        let screen = self.machine.as_ref().and_then(|machine| machine.framebuffer(processor));
+        self.disassembler.set_screen(screen.map(|framebuffer| (framebuffer, self.screen_mode)));
 
         clear();
         self.disassembler.update_dissambler()
     }
 }
 
-fn get_input() -> String
+fn get_input(prompt: &str) -> String
 {
     let mut input = String::new(); 
-    print!("> "); 
+    print!("{}", prompt); 
     stdout().flush().unwrap(); 
     stdin().read_line(&mut input).expect("Failed to read line"); 
     return input;
 }
 
 
-fn step(processor: &mut Processor, steps: Option<&str>)
-{
-    match steps 
-    {
-        Some(steps) => 
-        {
-            let steps = match steps.parse::<u32>()
-            {
-                Ok(steps) => steps,
-                Err(_) => 1,
-            };
-
-            for _ in 0..steps
-            {
-                processor.clock();
-            }
-        },
-        None => processor.clock(),
-    }
-}
-
 fn reset_processor(processor: &mut Processor)
 {
     processor.reset();
@@ -156,59 +594,3 @@ fn clear()
 }
 
 
-// Create a copy of the processor and clock it and read out each instruction name into a vector
-// Then return the vector
-fn get_instructions(processor: &mut Processor) -> Vec<String>
-{
-    let mut instructions: Vec<String> = Vec::new();
-    let mut processor = processor.clone();
-    processor.set_clock_frequency(0);
-    instructions.push("".to_string());
-
-    processor.fetch_instruction();
-    let instruction = processor.get_current_op();
-    let (byte, name) = instruction.get_name_byte();
-
-    instructions.push(String::from(format!(">>>{a:>4}:     0x{b:02X} {c:}", 
-                                           a=(processor.get_pc() as usize), b=byte, c=name)));
-    processor.clock();
-    for _ in 1 .. 47
-    {
-        processor.fetch_instruction();
-        let instruction = processor.get_current_op();
-        let (byte, name) = instruction.get_name_byte();
-
-        instructions.push(String::from(format!("{a:>6}:     0x{b:02X} {c:}", 
-                                               a=(processor.get_pc() as usize), b=byte, c=name)));
-        processor.clock();
-    }
-    return instructions
-}
-
-fn input_to_u16(input: Option<&str>) -> u16 
-{
-    match input 
-    {
-        Some(value) => 
-        {
-            if value.starts_with("0x") || value.starts_with("0X") 
-            {
-                match u16::from_str_radix(&value[2..], 16) 
-                {
-                    Ok(value) => return value,
-                    Err(_) => return 0,
-                }
-            } 
-            else 
-            {
-                match value.parse::<u16>() 
-                {
-                    Ok(value) => return value,
-                    Err(_) => return 0,
-                }
-            }
-        },
-        None => return 0,
-    }
-}
-
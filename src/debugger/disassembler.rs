@@ -8,14 +8,20 @@ use tui::style::{Style, Color};
 use tui::Terminal;
 use crate::i8080::registers::Registers;
 use crate::i8080::flags::StatusFlags;
+use crate::machines::Framebuffer;
+use super::screen::{RenderMode, Screen};
 
 pub struct Disassembler<'a>
 {
     mem:   Vec<Vec<String>>,
+    mem_address: u16,
     inst:  Vec<String>,
     flags: Vec<Vec<&'a str>>,
     regs:  Vec<Vec<String>>,
     pc:    Vec<Vec<String>>,
+    screen: Option<(Framebuffer, RenderMode)>,
+    output: Vec<String>,
+    backtrace: Vec<String>,
 }
 
 impl<'a> Disassembler<'a>
@@ -25,6 +31,7 @@ impl<'a> Disassembler<'a>
         let t = Disassembler
         {
             mem: vec![ vec!["00".to_string(); 17]; 44],
+            mem_address: 0,
             inst: Vec::new(),
             regs: vec![
                 vec!["Accumulator".to_string(), "B".to_string(),"C".to_string()],
@@ -46,6 +53,9 @@ impl<'a> Disassembler<'a>
                 vec!["0x0".to_string()],
                 vec!["0".to_string()],
             ],
+            screen: None,
+            output: Vec::new(),
+            backtrace: Vec::new(),
         };
         t
     }
@@ -95,6 +105,24 @@ impl<'a> Disassembler<'a>
         self.flags[3][1] = match f.parity_flag    { true => "1", _ => "0" };
     }
 
+    /// Shows the machine's screen next to the memory pane, or hides it when None.
+    pub fn set_screen(&mut self, screen: Option<(Framebuffer, RenderMode)>)
+    {
+        self.screen = screen;
+    }
+
+    /// Lines printed by the last command, shown under the prompt in the shell pane.
+    pub fn set_output(&mut self, output: Vec<String>)
+    {
+        self.output = output;
+    }
+
+    /// The calls that led to PC, innermost first, shown under the instructions.
+    pub fn set_backtrace(&mut self, backtrace: Vec<String>)
+    {
+        self.backtrace = backtrace;
+    }
+
     pub fn update_instructions(&mut self, instructions: Vec<String>)
     {
         self.inst.clear();
@@ -106,6 +134,7 @@ impl<'a> Disassembler<'a>
 
     pub fn set_memory(&mut self, address: u16, memory: &[u8])
     {
+        self.mem_address = address;
         for i in 0..self.mem.len()
         {
             self.mem[i][0] = format!("{:04X}", address + (i as u16 * 16));
@@ -118,6 +147,12 @@ impl<'a> Disassembler<'a>
         }
     }
 
+    /// Reads the bytes of the memory pane again, after memory has changed.
+    pub fn refresh_memory(&mut self, memory: &[u8])
+    {
+        self.set_memory(self.mem_address, memory);
+    }
+
     pub fn update_dissambler(&self) 
     {
         // Initiate
@@ -207,7 +242,7 @@ impl<'a> Disassembler<'a>
         let box_layout_inst = Layout::default()
             .direction(Direction::Vertical)
             .margin(1)
-            .constraints([Constraint::Percentage(30)].as_ref());
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref());
 
 
         let mem_pc_border       = Block::default().title("Memory").borders(Borders::ALL);
@@ -216,6 +251,8 @@ impl<'a> Disassembler<'a>
         let registers_border    = Block::default().title("Registers").borders(Borders::ALL);
         let flags_border        = Block::default().title("Flags").borders(Borders::ALL);
         let instructions_border = Block::default().title("Instructions").borders(Borders::ALL);
+        let backtrace_border    = Block::default().title("Backtrace").borders(Borders::ALL);
+        let backtrace           = List::new(self.backtrace.iter().map(Text::raw));
 
         let mut shell_widget_position = Default::default();
 
@@ -227,7 +264,17 @@ impl<'a> Disassembler<'a>
             let width = rect.right();
             let height = rect.bottom();
 
-            let mem_rect = Rect::new(y, x, width/2, height);
+            // The screen, if there is one, takes the left half of the memory pane
+            let mut mem_rect = Rect::new(y, x, width/2, height);
+            if let Some((framebuffer, mode)) = &self.screen
+            {
+                let screen_rect = Rect::new(y, x, width/4, height);
+                mem_rect = Rect::new(y+width/4, x, width/4, height);
+                let screen_border = Block::default().title("Screen").borders(Borders::ALL);
+                let screen_inner = screen_border.inner(screen_rect);
+                f.render_widget(screen_border, screen_rect);
+                f.render_widget(Screen::new(framebuffer, *mode), screen_inner);
+            }
             let inst_rect = Rect::new(y+width/2+width/4, x, width/4, height);
             let rect = Rect::new(y+width/2, x, width/4, height);
             let rect_in = Rect::new(y+width/2, x, width/4, height);
@@ -268,8 +315,17 @@ impl<'a> Disassembler<'a>
 
 
             f.render_widget(shell_border, box_multi[3]);
+            let shell = box_multi[3];
+            if shell.height > 6 && shell.width > 4
+            {
+                let output_rect = Rect::new(shell.x + 2, shell.y + 4, shell.width - 4, shell.height - 5);
+                let output = List::new(self.output.iter().map(Text::raw));
+                f.render_widget(output, output_rect);
+            }
             f.render_widget(instructions_border, box_inst[0]);
             f.render_widget(instructions, box_inst_in[0]);
+            f.render_widget(backtrace_border, box_inst[1]);
+            f.render_widget(backtrace, box_inst_in[1]);
 
             shell_widget_position = box_multi[3];
 
@@ -0,0 +1,170 @@
+use std::io::{stdout, Stdout, Write};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::AlternateScreen;
+use tui::backend::TermionBackend;
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Style;
+use tui::widgets::{Block, Borders, Widget};
+use tui::Terminal;
+use crate::debugger::screen::FrameLimiter;
+use crate::machines::altair::{PanelLights, STATUS_LIGHTS};
+
+const LIT: char = '●';
+const UNLIT: char = '○';
+const SWITCH_UP: char = '▲';
+const SWITCH_DOWN: char = '▼';
+
+/// The keys that work the panel, shown under it.
+const HELP: [&str; 3] = [
+    "0-9 a-f: shift into switches   left/right: move   space: flip   backspace: clear",
+    "x: EXAMINE  X: EXAMINE NEXT  p: DEPOSIT  P: DEPOSIT NEXT",
+    "r: RUN  s: STOP  n: SINGLE STEP  R: RESET  q: quit",
+];
+
+fn light(on: bool) -> char
+{
+    if on { LIT } else { UNLIT }
+}
+
+/// A row of `count` lights for the low bits of `value`, most significant bit first, in
+/// groups of three like the octal markings on the panel.
+fn light_row(value: u16, count: usize) -> String
+{
+    let mut row = String::new();
+    for bit in (0..count).rev()
+    {
+        row.push(light(value & (1 << bit) != 0));
+        row.push(' ');
+        if bit % 3 == 0 && bit != 0
+        {
+            row.push(' ');
+        }
+    }
+    row
+}
+
+/// Draws the front panel as lines of text: status lights, address and data lights and the
+/// switch row with a marker under the switch at `cursor`.
+pub fn panel_lines(lights: &PanelLights, switches: u16, cursor: u8) -> Vec<String>
+{
+    let status_names: String = STATUS_LIGHTS.iter().map(|name| format!("{:<6}", name)).collect();
+    let status_lights: String = lights.status.iter().map(|on| format!("{:<6}", light(*on))).collect();
+
+    // The data lights line up with the low eight address lights
+    let address = light_row(lights.address, 16);
+    let data = light_row(lights.data as u16, 8);
+    let data_indent = address.chars().count() - data.chars().count();
+
+    let mut switch_row = String::new();
+    let mut marker_row = String::new();
+    for bit in (0..16u8).rev()
+    {
+        switch_row.push(if switches & (1 << bit) != 0 { SWITCH_UP } else { SWITCH_DOWN });
+        switch_row.push(' ');
+        marker_row.push_str(if bit == cursor { "^ " } else { "  " });
+        if bit % 3 == 0 && bit != 0
+        {
+            switch_row.push(' ');
+            marker_row.push(' ');
+        }
+    }
+
+    vec![
+        format!("STATUS   {}", status_names),
+        format!("         {}", status_lights),
+        String::new(),
+        format!("DATA     {}{}", " ".repeat(data_indent), data),
+        format!("         {}D7 - D0 {:02X}", " ".repeat(data_indent), lights.data),
+        String::new(),
+        format!("WAIT {}   HLDA {}", light(lights.wait), light(lights.hlda)),
+        format!("ADDRESS  {}", address),
+        format!("         A15 - A0 {:04X}", lights.address),
+        String::new(),
+        format!("SWITCH   {}", switch_row),
+        format!("         {}{:04X}", marker_row, switches),
+    ]
+}
+
+/// A tui widget showing the front panel.
+pub struct FrontPanel<'a>
+{
+    lights:   &'a PanelLights,
+    switches: u16,
+    cursor:   u8,
+}
+
+impl<'a> FrontPanel<'a>
+{
+    pub fn new(lights: &'a PanelLights, switches: u16, cursor: u8) -> FrontPanel<'a>
+    {
+        FrontPanel { lights, switches, cursor }
+    }
+}
+
+impl<'a> Widget for FrontPanel<'a>
+{
+    fn render(self, area: Rect, buf: &mut Buffer)
+    {
+        let lines = panel_lines(self.lights, self.switches, self.cursor);
+        let help = HELP.iter().map(|line| line.to_string());
+        let all: Vec<String> = lines.into_iter().chain(std::iter::once(String::new())).chain(help).collect();
+        for (i, line) in all.iter().take(area.height as usize).enumerate()
+        {
+            buf.set_stringn(area.x, area.y + i as u16, line, area.width as usize, Style::default());
+        }
+    }
+}
+
+/// The whole terminal given over to the front panel, on the alternate screen in raw mode.
+/// The terminal is restored when it is dropped.
+pub struct PanelWindow
+{
+    terminal: Terminal<TermionBackend<AlternateScreen<RawTerminal<Stdout>>>>,
+    limiter:  FrameLimiter,
+    title:    String,
+}
+
+impl PanelWindow
+{
+    pub fn new(title: &str, fps: u32) -> PanelWindow
+    {
+        let stdout = stdout().into_raw_mode().expect("Failed to put the terminal in raw mode");
+        let backend = TermionBackend::new(AlternateScreen::from(stdout));
+        let mut terminal = Terminal::new(backend).expect("Failed to create new Terminal (Tui)");
+        terminal.hide_cursor().expect("Failed to hide the cursor");
+
+        PanelWindow
+        {
+            terminal,
+            limiter: FrameLimiter::new(fps),
+            title:   title.to_string(),
+        }
+    }
+
+    /// Draws the panel, unless it was drawn too recently and `force` is not set.
+    pub fn draw(&mut self, lights: &PanelLights, switches: u16, cursor: u8, force: bool)
+    {
+        if !self.limiter.ready() && !force
+        {
+            return;
+        }
+        let block = Block::default().title(&self.title).borders(Borders::ALL);
+        self.terminal.draw(|mut f|
+        {
+            let area = f.size();
+            let inner = block.inner(area);
+            f.render_widget(block, area);
+            f.render_widget(FrontPanel::new(lights, switches, cursor), inner);
+        }).expect("Failed to draw!");
+    }
+}
+
+impl Drop for PanelWindow
+{
+    fn drop(&mut self)
+    {
+        let _ = self.terminal.show_cursor();
+        let _ = self.terminal.backend_mut().flush();
+    }
+}
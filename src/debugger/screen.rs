@@ -0,0 +1,223 @@
+use std::io::{stdout, Stdout, Write};
+use std::time::{Duration, Instant};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::AlternateScreen;
+use tui::backend::TermionBackend;
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Style;
+use tui::widgets::{Block, Borders, Widget};
+use tui::Terminal;
+use crate::machines::Framebuffer;
+
+/// How pixels are packed into character cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode
+{
+    /// Braille patterns, 2x4 pixels per cell.
+    Braille,
+    /// Upper and lower half blocks, 1x2 pixels per cell.
+    HalfBlock,
+}
+
+impl RenderMode
+{
+    /// Width and height in pixels of one character cell.
+    pub fn cell_size(&self) -> (usize, usize)
+    {
+        match self
+        {
+            RenderMode::Braille   => (2, 4),
+            RenderMode::HalfBlock => (1, 2),
+        }
+    }
+}
+
+/// Bits of the braille dots, indexed by row and column within the cell.
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// The smallest whole scale at which the framebuffer fits in `columns` by `rows` cells.
+pub fn fit_scale(framebuffer: &Framebuffer, mode: RenderMode, columns: usize, rows: usize) -> usize
+{
+    let (cell_width, cell_height) = mode.cell_size();
+    let scale_x = framebuffer.width.div_ceil((columns * cell_width).max(1));
+    let scale_y = framebuffer.height.div_ceil((rows * cell_height).max(1));
+    scale_x.max(scale_y).max(1)
+}
+
+/// Draws the framebuffer as lines of text. When scaled down, every dot stands for a square of
+/// `scale` by `scale` pixels and is lit if any of them is, so single pixel bullets stay visible.
+pub fn render_lines(framebuffer: &Framebuffer, mode: RenderMode, scale: usize) -> Vec<String>
+{
+    let scale = scale.max(1);
+    let (cell_width, cell_height) = mode.cell_size();
+    let dots_wide = framebuffer.width.div_ceil(scale);
+    let dots_high = framebuffer.height.div_ceil(scale);
+
+    let lit = |dot_x: usize, dot_y: usize| -> bool
+    {
+        if dot_x >= dots_wide || dot_y >= dots_high
+        {
+            return false;
+        }
+        let right = ((dot_x + 1) * scale).min(framebuffer.width);
+        let bottom = ((dot_y + 1) * scale).min(framebuffer.height);
+        (dot_y * scale..bottom).any(|y| (dot_x * scale..right).any(|x| framebuffer.get(x, y)))
+    };
+
+    let mut lines = Vec::new();
+    for row in 0..dots_high.div_ceil(cell_height)
+    {
+        let mut line = String::new();
+        for column in 0..dots_wide.div_ceil(cell_width)
+        {
+            let (x, y) = (column * cell_width, row * cell_height);
+            let symbol = match mode
+            {
+                RenderMode::Braille =>
+                {
+                    let mut bits = 0;
+                    for (dy, dots) in BRAILLE_DOTS.iter().enumerate()
+                    {
+                        for (dx, dot) in dots.iter().enumerate()
+                        {
+                            if lit(x + dx, y + dy)
+                            {
+                                bits |= dot;
+                            }
+                        }
+                    }
+                    std::char::from_u32(0x2800 + bits).unwrap_or(' ')
+                },
+                RenderMode::HalfBlock => match (lit(x, y), lit(x, y + 1))
+                {
+                    (true, true)  => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    _             => ' ',
+                },
+            };
+            line.push(symbol);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// A tui widget showing a framebuffer, scaled down to fit its area if needed.
+pub struct Screen<'a>
+{
+    framebuffer: &'a Framebuffer,
+    mode:        RenderMode,
+}
+
+impl<'a> Screen<'a>
+{
+    pub fn new(framebuffer: &'a Framebuffer, mode: RenderMode) -> Screen<'a>
+    {
+        Screen { framebuffer, mode }
+    }
+}
+
+impl<'a> Widget for Screen<'a>
+{
+    fn render(self, area: Rect, buf: &mut Buffer)
+    {
+        let scale = fit_scale(self.framebuffer, self.mode, area.width as usize, area.height as usize);
+        let lines = render_lines(self.framebuffer, self.mode, scale);
+        for (i, line) in lines.iter().take(area.height as usize).enumerate()
+        {
+            buf.set_stringn(area.x, area.y + i as u16, line, area.width as usize, Style::default());
+        }
+    }
+}
+
+/// Lets through at most `fps` frames per second.
+pub struct FrameLimiter
+{
+    interval:  Duration,
+    last_draw: Option<Instant>,
+}
+
+impl FrameLimiter
+{
+    pub fn new(fps: u32) -> FrameLimiter
+    {
+        FrameLimiter
+        {
+            interval:  Duration::from_secs(1) / fps.max(1),
+            last_draw: None,
+        }
+    }
+
+    /// True if a frame is due, in which case the next one is due an interval from now.
+    pub fn ready(&mut self) -> bool
+    {
+        let now = Instant::now();
+        match self.last_draw
+        {
+            Some(last_draw) if now.duration_since(last_draw) < self.interval => false,
+            _ =>
+            {
+                self.last_draw = Some(now);
+                true
+            },
+        }
+    }
+}
+
+/// The whole terminal given over to a machine's screen, on the alternate screen in raw mode.
+/// The terminal is restored when it is dropped.
+pub struct ScreenWindow
+{
+    terminal: Terminal<TermionBackend<AlternateScreen<RawTerminal<Stdout>>>>,
+    mode:     RenderMode,
+    limiter:  FrameLimiter,
+    title:    String,
+}
+
+impl ScreenWindow
+{
+    pub fn new(title: &str, mode: RenderMode, fps: u32) -> ScreenWindow
+    {
+        let stdout = stdout().into_raw_mode().expect("Failed to put the terminal in raw mode");
+        let backend = TermionBackend::new(AlternateScreen::from(stdout));
+        let mut terminal = Terminal::new(backend).expect("Failed to create new Terminal (Tui)");
+        terminal.hide_cursor().expect("Failed to hide the cursor");
+
+        ScreenWindow
+        {
+            terminal,
+            mode,
+            limiter: FrameLimiter::new(fps),
+            title:   title.to_string(),
+        }
+    }
+
+    /// Draws the framebuffer, unless the last frame was drawn too recently.
+    pub fn draw(&mut self, framebuffer: &Framebuffer)
+    {
+        if !self.limiter.ready()
+        {
+            return;
+        }
+        let mode = self.mode;
+        let block = Block::default().title(&self.title).borders(Borders::ALL);
+        self.terminal.draw(|mut f|
+        {
+            let area = f.size();
+            let inner = block.inner(area);
+            f.render_widget(block, area);
+            f.render_widget(Screen::new(framebuffer, mode), inner);
+        }).expect("Failed to draw!");
+    }
+}
+
+impl Drop for ScreenWindow
+{
+    fn drop(&mut self)
+    {
+        let _ = self.terminal.show_cursor();
+        let _ = self.terminal.backend_mut().flush();
+    }
+}
@@ -0,0 +1,188 @@
+pub mod graph;
+pub mod traversal;
+
+use crate::assembler::opcodes::Operands;
+use crate::i8080::instructions::{Instruction, InstructionTypes};
+use crate::symbols::SymbolTable;
+
+const REGISTERS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "M", "A"];
+const PAIRS: [&str; 4] = ["B", "D", "H", "SP"];
+
+/// Where execution can go after an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow
+{
+    /// On to the next instruction.
+    Next,
+    Jump(u16),
+    /// A conditional jump, which may also go on to the next instruction.
+    ConditionalJump(u16),
+    /// CALL, a conditional call or RST, which come back to the next instruction.
+    Call(u16),
+    Return,
+    ConditionalReturn,
+    /// PCHL, to wherever HL points.
+    Indirect,
+}
+
+/// An instruction decoded from memory, or a byte that is not one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line
+{
+    pub address:     u16,
+    pub bytes:       Vec<u8>,
+    /// None for the opcodes the 8080 does not have.
+    pub instruction: Option<InstructionTypes>,
+}
+
+impl Line
+{
+    /// The address after the instruction.
+    pub fn get_next(&self) -> u16
+    {
+        self.address.wrapping_add(self.bytes.len() as u16)
+    }
+
+    /// The two byte operand, or the byte one.
+    pub fn get_operand(&self) -> u16
+    {
+        match self.bytes.len()
+        {
+            3 => self.bytes[1] as u16 | ((self.bytes[2] as u16) << 8),
+            2 => self.bytes[1] as u16,
+            _ => 0,
+        }
+    }
+
+    pub fn get_flow(&self) -> Flow
+    {
+        use InstructionTypes::*;
+        let operand = self.get_operand();
+        match &self.instruction
+        {
+            Some(JMP) => Flow::Jump(operand),
+            Some(JNZ) | Some(JZ) | Some(JNC) | Some(JC) | Some(JPO) | Some(JPE) | Some(JP) | Some(JM) => Flow::ConditionalJump(operand),
+            Some(CALL) | Some(CNZ) | Some(CZ) | Some(CNC) | Some(CC) | Some(CPO) | Some(CPE) | Some(CP) | Some(CM) => Flow::Call(operand),
+            Some(RST) => Flow::Call((self.bytes[0] & 0x38) as u16),
+            Some(RET) => Flow::Return,
+            Some(RNZ) | Some(RZ) | Some(RNC) | Some(RC) | Some(RPO) | Some(RPE) | Some(RP) | Some(RM) => Flow::ConditionalReturn,
+            Some(PCHL) => Flow::Indirect,
+            _ => Flow::Next,
+        }
+    }
+
+    /// The instruction in the assembler's syntax, numbers in hex. Jumps, calls and the
+    /// addresses of LDA, STA, LHLD and SHLD are given as the nearest symbol, LXI's operand only
+    /// if a symbol is at exactly that address.
+    pub fn format(&self, symbols: &SymbolTable) -> String
+    {
+        let instruction = match &self.instruction
+        {
+            Some(instruction) => instruction,
+            None => return format!("DB   {}", hex_byte(self.bytes[0])),
+        };
+        let opcode = self.bytes[0];
+        let register = |shift: u8| REGISTERS[((opcode >> shift) & 7) as usize];
+        let pair = PAIRS[((opcode >> 4) & 3) as usize];
+        let operand = self.get_operand();
+        let operands = match Operands::of(instruction)
+        {
+            Operands::None => String::new(),
+            Operands::Destination => register(3).to_string(),
+            Operands::Source => register(0).to_string(),
+            Operands::Move => format!("{},{}", register(3), register(0)),
+            Operands::DestinationByte => format!("{},{}", register(3), hex_byte(operand as u8)),
+            Operands::Pair | Operands::IndexPair => pair.to_string(),
+            Operands::StackPair => if pair == "SP" { "PSW".to_string() } else { pair.to_string() },
+            Operands::PairWord =>
+            {
+                let value = symbols.get_name(operand).map_or_else(|| hex_word(operand), str::to_string);
+                format!("{},{}", pair, value)
+            },
+            Operands::Byte => hex_byte(operand as u8),
+            Operands::Word => symbols.describe(operand).unwrap_or_else(|| hex_word(operand)),
+            Operands::Restart => ((opcode >> 3) & 7).to_string(),
+        };
+        let name = format!("{:?}", instruction);
+        format!("{:<4} {}", name, operands).trim_end().to_string()
+    }
+}
+
+/// A byte in hex as the assembler reads it, like 0FFH.
+pub fn hex_byte(byte: u8) -> String
+{
+    hex_number(format!("{:02X}", byte))
+}
+
+/// A word in hex as the assembler reads it, like 0C000H.
+pub fn hex_word(word: u16) -> String
+{
+    hex_number(format!("{:04X}", word))
+}
+
+fn hex_number(digits: String) -> String
+{
+    if digits.starts_with(|c: char| c.is_ascii_alphabetic()) { format!("0{}H", digits) } else { format!("{}H", digits) }
+}
+
+/// Decodes the instruction at `address`, reading memory with `read`.
+pub fn decode<F>(read: &F, address: u16) -> Line
+    where F: Fn(u16) -> u8
+{
+    let opcode = read(address);
+    let instruction = Some(Instruction::from_byte(opcode).instruction_type).filter(|instruction| *instruction != InstructionTypes::Unknown);
+    let length = match instruction.as_ref().map(Operands::of)
+    {
+        Some(Operands::DestinationByte) | Some(Operands::Byte) => 2,
+        Some(Operands::PairWord) | Some(Operands::Word) => 3,
+        _ => 1,
+    };
+    let bytes = (0..length).map(|i| read(address.wrapping_add(i))).collect();
+    Line { address, bytes, instruction }
+}
+
+/// `count` instructions one after the other from `address`. If `follow` is set, JMP goes on
+/// at its target instead, unless that is listed already.
+pub fn decode_from<F>(read: &F, address: u16, count: usize, follow: bool) -> Vec<Line>
+    where F: Fn(u16) -> u8
+{
+    let mut lines: Vec<Line> = Vec::new();
+    let mut address = address;
+    while lines.len() < count
+    {
+        let line = decode(read, address);
+        address = match line.get_flow()
+        {
+            Flow::Jump(target) if follow && !lines.iter().any(|line| line.address == target) => target,
+            _ => line.get_next(),
+        };
+        lines.push(line);
+    }
+    lines
+}
+
+/// Up to `count` instructions that end right at `address`. As instructions are one to three
+/// bytes long, this decodes from a little further back, from the first address that lines up
+/// with `address`.
+pub fn decode_before<F>(read: &F, address: u16, count: usize) -> Vec<Line>
+    where F: Fn(u16) -> u8
+{
+    let reach = (count * 3) as u16;
+    for back in (1..=reach.min(address)).rev()
+    {
+        let mut lines = Vec::new();
+        let mut at = (address - back) as u32;
+        while at < address as u32
+        {
+            let line = decode(read, at as u16);
+            at += line.bytes.len() as u32;
+            lines.push(line);
+        }
+        if at == address as u32
+        {
+            let skip = lines.len().saturating_sub(count);
+            return lines.split_off(skip);
+        }
+    }
+    Vec::new()
+}
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, BTreeSet};
+use super::traversal::Program;
+use super::{hex_word, Flow};
+
+/// How a block is left for another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind
+{
+    /// On to the instruction after, also after a call or a conditional branch.
+    FallThrough,
+    Jump,
+    /// A conditional jump taken.
+    Branch,
+    /// CALL, a conditional call or RST.
+    Call,
+    /// PCHL, to somewhere not known.
+    Indirect,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge
+{
+    pub kind: EdgeKind,
+    /// The instruction the edge leaves from.
+    pub from: u16,
+    /// None for PCHL.
+    pub to:   Option<u16>,
+}
+
+/// Instructions run one after the other, entered only at the first and left only after the
+/// last. Calls end a block too.
+#[derive(Clone, Debug, Default)]
+pub struct Block
+{
+    pub start:        u16,
+    /// The addresses of the instructions.
+    pub instructions: Vec<u16>,
+    pub edges:        Vec<Edge>,
+}
+
+/// The control flow graph of a program: its basic blocks and the subroutines they make up.
+pub struct Graph<'a>
+{
+    program:         &'a Program,
+    pub blocks:      BTreeMap<u16, Block>,
+    /// The entry points and every address called.
+    pub subroutines: BTreeSet<u16>,
+}
+
+impl<'a> Graph<'a>
+{
+    /// Splits the instructions of `program` into blocks.
+    pub fn new(program: &'a Program) -> Graph<'a>
+    {
+        let instructions = &program.instructions;
+        let mut leaders: BTreeSet<u16> = program.entries.iter().copied().collect();
+        let mut subroutines: BTreeSet<u16> = leaders.clone();
+        for line in instructions.values()
+        {
+            match line.get_flow()
+            {
+                Flow::Next => (),
+                Flow::Jump(target) | Flow::ConditionalJump(target) =>
+                {
+                    leaders.insert(target);
+                    leaders.insert(line.get_next());
+                },
+                Flow::Call(target) =>
+                {
+                    leaders.insert(target);
+                    leaders.insert(line.get_next());
+                    subroutines.insert(target);
+                },
+                Flow::Return | Flow::ConditionalReturn | Flow::Indirect => { leaders.insert(line.get_next()); },
+            }
+        }
+
+        let mut blocks: BTreeMap<u16, Block> = BTreeMap::new();
+        let mut block: Option<Block> = None;
+        for (address, line) in instructions.iter()
+        {
+            let mut current = match block.take()
+            {
+                Some(current) if !leaders.contains(address) => current,
+                _ => Block { start: *address, ..Block::default() },
+            };
+            current.instructions.push(*address);
+            let next = line.get_next();
+            let falls = instructions.contains_key(&next);
+            let fall_through = Edge { kind: EdgeKind::FallThrough, from: *address, to: Some(next) };
+            let edge = |kind, target| Edge { kind, from: *address, to: Some(target) };
+            let edges = match line.get_flow()
+            {
+                Flow::Next if falls && !leaders.contains(&next) =>
+                {
+                    block = Some(current);
+                    continue;
+                },
+                Flow::Next | Flow::ConditionalReturn => vec![fall_through],
+                Flow::Jump(target) => vec![edge(EdgeKind::Jump, target)],
+                Flow::ConditionalJump(target) => vec![edge(EdgeKind::Branch, target), fall_through],
+                Flow::Call(target) => vec![edge(EdgeKind::Call, target), fall_through],
+                Flow::Return => vec![],
+                Flow::Indirect => vec![Edge { kind: EdgeKind::Indirect, from: *address, to: None }],
+            };
+            current.edges = edges.into_iter().filter(|edge| edge.kind != EdgeKind::FallThrough || falls).collect();
+            blocks.insert(current.start, current);
+        }
+        if let Some(current) = block
+        {
+            blocks.insert(current.start, current);
+        }
+        subroutines.retain(|address| blocks.contains_key(address));
+        Graph { program, blocks, subroutines }
+    }
+
+    /// The blocks of the subroutine at `start`: those reached from it without a call, up to
+    /// the start of another subroutine.
+    pub fn subroutine(&self, start: u16) -> Vec<&Block>
+    {
+        let mut reached = BTreeSet::new();
+        let mut pending = vec![start];
+        while let Some(address) = pending.pop()
+        {
+            if !self.blocks.contains_key(&address) || !reached.insert(address)
+            {
+                continue;
+            }
+            for edge in self.blocks[&address].edges.iter().filter(|edge| edge.kind != EdgeKind::Call)
+            {
+                match edge.to
+                {
+                    Some(to) if !self.subroutines.contains(&to) => pending.push(to),
+                    _ => (),
+                }
+            }
+        }
+        reached.iter().map(|address| &self.blocks[address]).collect()
+    }
+
+    /// A DOT digraph for each subroutine, its blocks as boxes holding their instructions.
+    /// Calls, jumps into other subroutines and addresses outside the program go to ellipses
+    /// named after them, PCHL to a dashed "indirect".
+    pub fn to_dot(&self) -> String
+    {
+        let mut dot = String::new();
+        for start in self.subroutines.iter()
+        {
+            let blocks = self.subroutine(*start);
+            let inside: BTreeSet<u16> = blocks.iter().map(|block| block.start).collect();
+            let mut outside = BTreeSet::new();
+            dot += &format!("digraph \"{}\"\n{{\n    node [shape=box, fontname=\"monospace\"];\n", self.name(*start));
+            for block in blocks.iter()
+            {
+                dot += &format!("    n{:04X} [label=\"{}\"];\n", block.start, self.block_label(block));
+                for edge in block.edges.iter()
+                {
+                    let node = match edge.to
+                    {
+                        Some(to) if inside.contains(&to) && edge.kind != EdgeKind::Call => format!("n{:04X}", to),
+                        Some(to) =>
+                        {
+                            outside.insert(to);
+                            format!("x{:04X}", to)
+                        },
+                        None => "indirect".to_string(),
+                    };
+                    dot += &format!("    n{:04X} -> {}{};\n", block.start, node, self.edge_style(edge));
+                }
+            }
+            for address in outside
+            {
+                dot += &format!("    x{:04X} [label=\"{}\", shape=ellipse];\n", address, self.name(address));
+            }
+            if blocks.iter().any(|block| block.edges.iter().any(|edge| edge.kind == EdgeKind::Indirect))
+            {
+                dot += "    indirect [shape=ellipse, style=dashed];\n";
+            }
+            dot += "}\n";
+        }
+        dot
+    }
+
+    /// A DOT digraph of which subroutine calls which, RST included. Subroutines that jump
+    /// through PCHL have a dashed edge to "indirect".
+    pub fn to_call_graph(&self) -> String
+    {
+        let mut calls = BTreeSet::new();
+        let mut nodes: BTreeSet<u16> = self.subroutines.clone();
+        let mut indirect = BTreeSet::new();
+        for start in self.subroutines.iter()
+        {
+            for edge in self.subroutine(*start).iter().flat_map(|block| block.edges.iter())
+            {
+                match (edge.kind, edge.to)
+                {
+                    (EdgeKind::Call, Some(to)) =>
+                    {
+                        calls.insert((*start, to));
+                        nodes.insert(to);
+                    },
+                    (EdgeKind::Indirect, _) => { indirect.insert(*start); },
+                    _ => (),
+                }
+            }
+        }
+
+        let mut dot = "digraph calls\n{\n    node [shape=box, fontname=\"monospace\"];\n".to_string();
+        for address in nodes.iter()
+        {
+            dot += &format!("    n{:04X} [label=\"{}\"];\n", address, self.name(*address));
+        }
+        for (from, to) in calls
+        {
+            dot += &format!("    n{:04X} -> n{:04X};\n", from, to);
+        }
+        for from in indirect.iter()
+        {
+            dot += &format!("    n{:04X} -> indirect [style=dashed];\n", from);
+        }
+        if !indirect.is_empty()
+        {
+            dot += "    indirect [shape=ellipse, style=dashed];\n";
+        }
+        dot + "}\n"
+    }
+
+    fn name(&self, address: u16) -> String
+    {
+        self.program.labels.get_name(address).map_or_else(|| hex_word(address), str::to_string)
+    }
+
+    /// The label of a block, its name and instructions left aligned.
+    fn block_label(&self, block: &Block) -> String
+    {
+        let mut label = String::new();
+        if let Some(name) = self.program.labels.get_name(block.start)
+        {
+            label += &format!("{}:\\l", name);
+        }
+        for address in block.instructions.iter()
+        {
+            let text = self.program.instructions[address].format(&self.program.labels);
+            label += &format!("{:04X}  {}\\l", address, text.replace('"', "\\\""));
+        }
+        label
+    }
+
+    fn edge_style(&self, edge: &Edge) -> String
+    {
+        let mnemonic = self.program.instructions.get(&edge.from).and_then(|line| line.instruction.as_ref())
+            .map(|instruction| format!("{:?}", instruction)).unwrap_or_default();
+        match edge.kind
+        {
+            EdgeKind::FallThrough => String::new(),
+            EdgeKind::Jump | EdgeKind::Branch => format!(" [label=\"{}\"]", mnemonic),
+            EdgeKind::Call => format!(" [label=\"{}\", style=bold]", mnemonic),
+            EdgeKind::Indirect => format!(" [label=\"{}\", style=dashed]", mnemonic),
+        }
+    }
+}
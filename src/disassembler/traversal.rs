@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::i8080::instructions::InstructionTypes;
+use crate::symbols::SymbolTable;
+use crate::coverage::{Coverage, OPCODE, OPERAND, READ, WRITTEN};
+use super::{decode, hex_byte, hex_word, Flow, Line};
+
+/// Bytes per DB line.
+const BYTES_PER_LINE: usize = 8;
+/// The shortest run of printable characters put out as a string.
+const MIN_STRING: usize = 4;
+
+/// A program taken apart from its entry points: the instructions reached and labels for the
+/// addresses they use.
+#[derive(Clone, Debug, Default)]
+pub struct Program
+{
+    pub origin:       u16,
+    pub bytes:        Vec<u8>,
+    /// Where execution starts.
+    pub entries:      Vec<u16>,
+    /// The instructions reached, by address.
+    pub instructions: BTreeMap<u16, Line>,
+    /// The names of addresses: the symbols given and a label for every address in the
+    /// program that is jumped to, called or used, `L` and the address for code and `D` for
+    /// data.
+    pub labels:       SymbolTable,
+    /// Data read and written a word at a time, by LHLD and SHLD.
+    words:            BTreeSet<u16>,
+}
+
+impl Program
+{
+    /// Whether `address` is in the program.
+    pub fn contains(&self, address: u16) -> bool
+    {
+        address >= self.origin && ((address - self.origin) as usize) < self.bytes.len()
+    }
+
+    fn byte(&self, address: u16) -> u8
+    {
+        if self.contains(address) { self.bytes[(address - self.origin) as usize] } else { 0 }
+    }
+
+    /// Source the assembler turns back into the same bytes. Symbols not at the start of an
+    /// instruction or a line of data are defined with EQU.
+    pub fn to_source(&self) -> String
+    {
+        let end = self.origin as u32 + self.bytes.len() as u32;
+        let starts: BTreeSet<u16> = self.line_starts();
+        let mut source = String::new();
+        for (name, value) in self.labels.iter()
+        {
+            if !starts.contains(&value) || self.labels.get_name(value) != Some(name)
+            {
+                source += &format!("{}\tEQU\t{}\n", name, hex_word(value));
+            }
+        }
+        source += &format!("\n\tORG\t{}\n\n", hex_word(self.origin));
+
+        let mut address = self.origin as u32;
+        while address < end
+        {
+            if let Some(label) = self.labels.get_name(address as u16)
+            {
+                source += &format!("{}:\n", label);
+            }
+            match self.instructions.get(&(address as u16))
+            {
+                Some(line) =>
+                {
+                    let text = line.format(&self.labels);
+                    let (mnemonic, operands) = text.split_once(' ').unwrap_or((&text, ""));
+                    source += &format!("\t{}\t{}\n", mnemonic, operands.trim_start()).replace("\t\n", "\n");
+                    address += line.bytes.len() as u32;
+                },
+                None =>
+                {
+                    let next = starts.range(address as u16..).map(|start| *start as u32).find(|start| *start > address);
+                    let data_end = next.map_or(end, |next| next.min(end));
+                    source += &self.data(address as u16, (data_end - address) as usize);
+                    address = data_end;
+                },
+            }
+        }
+        source + "\n\tEND\n"
+    }
+
+    /// Where instructions and the labels between them start.
+    fn line_starts(&self) -> BTreeSet<u16>
+    {
+        let mut starts: BTreeSet<u16> = self.instructions.keys().copied().collect();
+        let data = |address: &u16| self.contains(*address) && !self.in_instruction(*address);
+        starts.extend(self.labels.iter().map(|(_, value)| value).filter(data));
+        starts.extend(self.instructions.values().map(Line::get_next).filter(data));
+        starts
+    }
+
+    /// Whether `address` is one of the bytes of an instruction.
+    fn in_instruction(&self, address: u16) -> bool
+    {
+        self.instructions.range(..=address).next_back().is_some_and(|(start, line)| (address - start) < line.bytes.len() as u16)
+    }
+
+    /// Whether `address` is inside an instruction but not where it starts.
+    fn in_instruction_middle(&self, address: u16) -> bool
+    {
+        self.in_instruction(address) && !self.instructions.contains_key(&address)
+    }
+
+    /// DB and DW lines for `length` bytes of data from `address`, with strings for runs of
+    /// printable characters.
+    fn data(&self, address: u16, length: usize) -> String
+    {
+        let bytes: Vec<u8> = (0..length).map(|i| self.byte(address.wrapping_add(i as u16))).collect();
+        let mut source = String::new();
+        let mut i = 0;
+        while i < length
+        {
+            if self.words.contains(&address.wrapping_add(i as u16)) && i + 1 < length
+            {
+                source += &format!("\tDW\t{}\n", hex_word(bytes[i] as u16 | ((bytes[i + 1] as u16) << 8)));
+                i += 2;
+                continue;
+            }
+            let printable = bytes[i..].iter().take_while(|byte| (0x20..0x7F).contains(*byte)).count();
+            if printable >= MIN_STRING
+            {
+                let text: String = bytes[i..i + printable].iter().map(|byte| *byte as char).collect();
+                source += &format!("\tDB\t'{}'\n", text.replace('\'', "''"));
+                i += printable;
+                continue;
+            }
+            let mut row = Vec::new();
+            while i < length && row.len() < BYTES_PER_LINE
+            {
+                let string = bytes[i..].iter().take_while(|byte| (0x20..0x7F).contains(*byte)).count() >= MIN_STRING;
+                if !row.is_empty() && (string || self.words.contains(&address.wrapping_add(i as u16)))
+                {
+                    break;
+                }
+                row.push(hex_byte(bytes[i]));
+                i += 1;
+            }
+            source += &format!("\tDB\t{}\n", row.join(","));
+        }
+        source
+    }
+}
+
+/// Takes `bytes` loaded at `origin` apart by following every jump, call, RST and conditional
+/// branch from `entries`. What is not reached is taken for data. `symbols` name addresses in
+/// place of the labels that would be made for them.
+pub fn disassemble(bytes: &[u8], origin: u16, entries: &[u16], symbols: &SymbolTable) -> Program
+{
+    disassemble_with_hints(bytes, origin, entries, symbols, &Coverage::default())
+}
+
+/// Like `disassemble`, taking every opcode `coverage` saw executed for code too, and bytes it
+/// saw read or written but never executed for data.
+pub fn disassemble_with_hints(bytes: &[u8], origin: u16, entries: &[u16], symbols: &SymbolTable, coverage: &Coverage) -> Program
+{
+    let mut program = Program { origin, bytes: bytes.to_vec(), entries: entries.to_vec(), ..Program::default() };
+    let read = |address: u16| program.byte(address);
+    let mut instructions: BTreeMap<u16, Line> = BTreeMap::new();
+    let mut claimed = vec![false; bytes.len()];
+    let mut code_targets = BTreeSet::new();
+    let mut data_targets = BTreeSet::new();
+    let mut words = BTreeSet::new();
+    let hint = |offset: usize| coverage.get(origin.wrapping_add(offset as u16));
+    let data: Vec<bool> = (0..bytes.len()).map(|offset| hint(offset) & (READ | WRITTEN) != 0 && hint(offset) & (OPCODE | OPERAND) == 0).collect();
+    let mut pending: Vec<u16> = (0..bytes.len()).filter(|offset| hint(*offset) & OPCODE != 0).map(|offset| origin.wrapping_add(offset as u16)).collect();
+    pending.extend(entries.iter().copied());
+    code_targets.extend(entries.iter().copied());
+
+    while let Some(address) = pending.pop()
+    {
+        if !program.contains(address) || instructions.contains_key(&address)
+        {
+            continue;
+        }
+        let line = decode(&read, address);
+        let offset = (address - origin) as usize;
+        let fits = line.instruction.is_some() && offset + line.bytes.len() <= bytes.len();
+        let range = offset..offset + line.bytes.len();
+        if !fits || claimed[range.clone()].iter().chain(data[range].iter()).any(|taken| *taken)
+        {
+            continue;
+        }
+        claimed[offset..offset + line.bytes.len()].iter_mut().for_each(|claimed| *claimed = true);
+
+        match line.get_flow()
+        {
+            Flow::Next | Flow::ConditionalReturn => pending.push(line.get_next()),
+            Flow::Jump(target) =>
+            {
+                pending.push(target);
+                code_targets.insert(target);
+            },
+            Flow::ConditionalJump(target) | Flow::Call(target) =>
+            {
+                pending.push(line.get_next());
+                pending.push(target);
+                code_targets.insert(target);
+            },
+            Flow::Return | Flow::Indirect => (),
+        }
+        use InstructionTypes::*;
+        match line.instruction
+        {
+            Some(LXI) | Some(LDA) | Some(STA) => { data_targets.insert(line.get_operand()); },
+            Some(LHLD) | Some(SHLD) =>
+            {
+                data_targets.insert(line.get_operand());
+                words.insert(line.get_operand());
+            },
+            _ => (),
+        }
+        instructions.insert(address, line);
+    }
+
+    program.instructions = instructions;
+    program.words = words.into_iter().filter(|address| !claimed_at(&claimed, origin, *address) && !claimed_at(&claimed, origin, address.wrapping_add(1))).collect();
+    program.labels = symbols.clone();
+    for (address, prefix) in code_targets.iter().map(|address| (address, "L")).chain(data_targets.iter().map(|address| (address, "D")))
+    {
+        if program.contains(*address) && program.labels.get_name(*address).is_none() && !program.in_instruction_middle(*address)
+        {
+            program.labels.add(&format!("{}{:04X}", prefix, address), *address);
+        }
+    }
+    program
+}
+
+fn claimed_at(claimed: &[bool], origin: u16, address: u16) -> bool
+{
+    address.checked_sub(origin).and_then(|offset| claimed.get(offset as usize)).copied().unwrap_or(true)
+}
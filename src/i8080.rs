@@ -4,7 +4,6 @@ pub mod flags;
 
 use std::fs::File;
 use std::io::Read;
-use std::process::exit;
 use std::u16;
 
 use crate::i8080::instructions::*;
@@ -25,8 +24,12 @@ pub struct Processor
     registers         : Registers,
     flags             : StatusFlags,
     current_op        : Instruction,
-    out               : u8, 
     interrupts_enabled: bool,
+    halted            : bool,
+    cycles            : u64,
+    input_ports       : [u8; 256],
+    last_input        : Option<u8>,
+    last_output       : Option<(u8, u8)>,
 }
 
 impl Processor 
@@ -42,8 +45,12 @@ impl Processor
             flags             : StatusFlags::new(),
             current_op        : Instruction::new(),
             registers         : Registers  ::new(),
-            out               : 0,
             interrupts_enabled: false,
+            halted            : false,
+            cycles            : 0,
+            input_ports       : [0; 256],
+            last_input        : None,
+            last_output       : None,
         };
 
         let mut file = File::open(p).expect("No such file");
@@ -58,8 +65,11 @@ impl Processor
         self.flags              = StatusFlags::new();
         self.current_op         = Instruction::new();
         self.registers          = Registers::new();
-        self.out                = 0;
         self.interrupts_enabled = false;
+        self.halted             = false;
+        self.cycles             = 0;
+        self.last_input         = None;
+        self.last_output        = None;
     }
 
     pub fn from_bytes(bytes: Vec<u8>, hz: u32) -> Processor 
@@ -73,8 +83,12 @@ impl Processor
             flags             : StatusFlags::new(),
             current_op        : Instruction::new(),
             registers         : Registers  ::new(),
-            out               : 0,
             interrupts_enabled: false,
+            halted            : false,
+            cycles            : 0,
+            input_ports       : [0; 256],
+            last_input        : None,
+            last_output       : None,
         };
 
         for (i, byte) in bytes.iter().enumerate()
@@ -86,15 +100,79 @@ impl Processor
 
     pub fn clock(&mut self) 
     {
+        // A halted processor idles until an interrupt arrives
+        if self.halted
+        {
+            self.cycles += 4;
+            return;
+        }
         self.fetch_instruction();
         self.execute_instruction();
         self.update_program_counter();
+        self.cycles += self.current_op.get_cycles() as u64;
         if self.clock_frequency > 0
         {
             std::thread::sleep(std::time::Duration::from_secs(1) / self.clock_frequency);
         }
     }
 
+    /// Requests an interrupt, which the 8080 answers by executing RST `rst`. It is ignored
+    /// while interrupts are disabled. Returns true if the interrupt was taken.
+    pub fn interrupt(&mut self, rst: u8) -> bool
+    {
+        if !self.interrupts_enabled
+        {
+            return false;
+        }
+        self.interrupts_enabled = false;
+        self.halted = false;
+        self.push_word(self.program_counter);
+        self.program_counter = ((rst & 0b111) as u16) << 3;
+        self.cycles += 11;
+        true
+    }
+
+    /// True after a HLT instruction, until an interrupt or reset.
+    pub fn get_halted(&self) -> bool
+    {
+        self.halted
+    }
+
+    pub fn get_interrupts_enabled(&self) -> bool
+    {
+        self.interrupts_enabled
+    }
+
+    /// Clock periods executed since the processor was created or reset.
+    pub fn get_cycles(&self) -> u64
+    {
+        self.cycles
+    }
+
+    /// Sets the value the IN instruction reads from `port`.
+    pub fn set_input_port(&mut self, port: u8, value: u8)
+    {
+        self.input_ports[port as usize] = value;
+    }
+
+    pub fn get_input_port(&self, port: u8) -> u8
+    {
+        self.input_ports[port as usize]
+    }
+
+    /// The port read by an IN instruction since the last call, for devices that react to
+    /// being read.
+    pub fn take_input(&mut self) -> Option<u8>
+    {
+        self.last_input.take()
+    }
+
+    /// The port and value written by an OUT instruction since the last call.
+    pub fn take_output(&mut self) -> Option<(u8, u8)>
+    {
+        self.last_output.take()
+    }
+
     pub fn get_instruction_length(&self) -> u8
     {
         return self.current_op.get_length();
@@ -105,11 +183,21 @@ impl Processor
         self.program_counter = 0;
     }
 
+    pub fn set_pc(&mut self, pc: u16)
+    {
+        self.program_counter = pc;
+    }
+
+    pub fn set_stack_pointer(&mut self, stack_pointer: u16)
+    {
+        self.stack_pointer = stack_pointer;
+    }
+
     pub fn fetch_instruction(&mut self)  
     {
         let op = self.memory[self.program_counter as usize];
-        let immediate_lsb = self.memory[(self.program_counter + 1) as usize];
-        let immediate_msb = self.memory[(self.program_counter + 2) as usize];
+        let immediate_lsb = self.memory[self.program_counter.wrapping_add(1) as usize];
+        let immediate_msb = self.memory[self.program_counter.wrapping_add(2) as usize];
         self.current_op.byte_to_op(op, immediate_lsb, immediate_msb);
     }
 
@@ -161,6 +249,8 @@ impl Processor
             InstructionTypes::RET  => self.ret_op(),
             InstructionTypes::XCHG => self.xchg_op(),
             InstructionTypes::OUT  => self.out_op(),
+            InstructionTypes::IN   => self.in_op(),
+            InstructionTypes::HLT  => self.halted = true,
             InstructionTypes::EI   => self.ei_op(),
             InstructionTypes::DI   => self.di_op(),
             InstructionTypes::INR  => self.inr_op(),
@@ -199,12 +289,7 @@ impl Processor
 
     fn update_program_counter(&mut self)
     {
-        if self.program_counter == u16::MAX
-        {
-            println!("Reached end of program memory, shutting down.");
-            exit(0);
-        }
-        self.program_counter += 1;
+        self.program_counter = self.program_counter.wrapping_add(1);
     }
 
     pub fn get_flags(&self) -> StatusFlags
@@ -365,6 +450,15 @@ impl Processor
         self.memory[addr as usize] = val;
     }
 
+    /// Copies `bytes` into memory starting at `addr`, e.g. a CP/M program loaded at 0x100.
+    pub fn load_bytes_at(&mut self, addr: u16, bytes: &[u8])
+    {
+        for (i, byte) in bytes.iter().enumerate()
+        {
+            self.memory[addr as usize + i] = *byte;
+        }
+    }
+
     /// The move (MOV) instruction copies the value of the second register into the first register.
     fn mov_op(&mut self)
     {
@@ -554,12 +648,8 @@ impl Processor
     /// bytes in the memory.
     fn jmp_op(&mut self)
     {
-        let mut addr = self.get_direct_address();
-        if addr > 0
-        {
-            addr = addr - 1;
-        }
-        self.program_counter = addr; 
+        // The program counter is incremented after every instruction, so land one byte short.
+        self.program_counter = self.get_direct_address().wrapping_sub(1);
     }
     
     /// Jump not zero (JNZ) instruction sets the program counter to the address specified in the next 
@@ -849,7 +939,11 @@ impl Processor
         self.memory[( self.stack_pointer - 2 ) as usize] = lsb_next_addr;
 
         self.stack_pointer = self.stack_pointer - 2;
-        self.program_counter = addr - 1;
+        self.program_counter = addr.wrapping_sub(1);
+        if self.current_op.instruction_type != InstructionTypes::CALL
+        {
+            self.cycles += 6;
+        }
     }
 
     /// call non zero (CNZ) instruction calls the address specified in the next two bytes in the 
@@ -902,7 +996,11 @@ impl Processor
         let addr: u16 = bytes_to_word(msb_addr, lsb_addr);
 
         self.stack_pointer += 2;
-        self.program_counter = addr - 1;
+        self.program_counter = addr.wrapping_sub(1);
+        if self.current_op.instruction_type != InstructionTypes::RET
+        {
+            self.cycles += 6;
+        }
     }
 
 
@@ -916,10 +1014,21 @@ impl Processor
         self.set_reg(L_REG, regs.e);
     }
 
-    /// Output (OUT) instruction outputs the value of the accumulator to out.
+    /// Output (OUT) instruction writes the accumulator to the port given in the next byte.
     fn out_op(&mut self)
     {
-        self.out = self.get_reg(A_REG);
+        let port = self.get_immediate();
+        self.program_counter = self.program_counter.wrapping_add(1);
+        self.last_output = Some((port, self.get_reg(A_REG)));
+    }
+
+    /// Input (IN) instruction reads the port given in the next byte into the accumulator.
+    fn in_op(&mut self)
+    {
+        let port = self.get_immediate();
+        self.program_counter = self.program_counter.wrapping_add(1);
+        self.set_reg(A_REG, self.input_ports[port as usize]);
+        self.last_input = Some(port);
     }
 
     /// The enable interrupts (EI) instruction enables interrupts.
@@ -1204,7 +1313,7 @@ impl Processor
     fn pchl_op(&mut self)
     {
         let program_counter: u16 = ((self.registers.h as u16) << 8) + self.registers.l as u16;
-        self.program_counter = program_counter - 1;
+        self.program_counter = program_counter.wrapping_sub(1);
     }
 
     /// reset (RST) instruction pushes the address of the next instruction onto the stack and sets 
@@ -1212,10 +1321,15 @@ impl Processor
     fn rst_op(&mut self)
     {
         let reset_addr = self.current_op.machine_code & 0b00111000;
-        self.memory[(self.stack_pointer - 1) as usize] = (self.program_counter & 0x0F) as u8;
-        self.memory[(self.stack_pointer - 2) as usize] = (( self.program_counter >> 8 ) & 0x0F) as u8;
-        self.stack_pointer -= 2;
-        self.program_counter = ( reset_addr - 1 ) as u16;
+        self.push_word(self.program_counter.wrapping_add(1));
+        self.program_counter = (reset_addr as u16).wrapping_sub(1);
+    }
+
+    fn push_word(&mut self, value: u16)
+    {
+        self.stack_pointer = self.stack_pointer.wrapping_sub(2);
+        self.memory[self.stack_pointer.wrapping_add(1) as usize] = (value >> 8) as u8;
+        self.memory[self.stack_pointer as usize] = value as u8;
     }
 
     pub fn get_immediate(&mut self) -> u8
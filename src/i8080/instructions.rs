@@ -1,4 +1,5 @@
 use crate::i8080::registers::*;
+use crate::symbols::SymbolTable;
 
 #[derive(Clone, Debug)]
 pub enum AddressMode 
@@ -21,7 +22,7 @@ pub enum InstructionTypes
     LXI, DCR, DAD, RRC, RLC, RAL, RAR, INX, DCX, LDA, LDAX, STA, PUSH,
     POP, CALL, NOP, RET, XCHG, OUT, EI, DI, INR, CP, CNZ, CC, CNC, CPO,
     CPE, CM, CZ, RC, RNC, RZ, RNZ, RM, RP, RPE, RPO, LHLD, SHLD, STAX,
-    STC, CMC, CMA, DAA, SPHL, XTHL, PCHL, RST,
+    STC, CMC, CMA, DAA, SPHL, XTHL, PCHL, RST, IN, HLT,
     Unknown,
 }
 
@@ -42,6 +43,28 @@ const XRA_GROUP: u8 = 0b10101000;
 const ORA_GROUP: u8 = 0b10110000;
 const CMP_GROUP: u8 = 0b10111000;
 
+/// Duration of every opcode in clock periods (T-states). Conditional calls and returns are
+/// listed with the time they take when the condition fails; taking them costs 6 more.
+const CYCLES: [u8; 256] =
+[
+    4, 10,  7,  5,  5,  5,  7,  4,  4, 10,  7,  5,  5,  5,  7,  4,
+    4, 10,  7,  5,  5,  5,  7,  4,  4, 10,  7,  5,  5,  5,  7,  4,
+    4, 10, 16,  5,  5,  5,  7,  4,  4, 10, 16,  5,  5,  5,  7,  4,
+    4, 10, 13,  5, 10, 10, 10,  4,  4, 10, 13,  5,  5,  5,  7,  4,
+    5,  5,  5,  5,  5,  5,  7,  5,  5,  5,  5,  5,  5,  5,  7,  5,
+    5,  5,  5,  5,  5,  5,  7,  5,  5,  5,  5,  5,  5,  5,  7,  5,
+    5,  5,  5,  5,  5,  5,  7,  5,  5,  5,  5,  5,  5,  5,  7,  5,
+    7,  7,  7,  7,  7,  7,  7,  7,  5,  5,  5,  5,  5,  5,  7,  5,
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4,
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4,
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4,
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4,
+    5, 10, 10, 10, 11, 11,  7, 11,  5, 10, 10, 10, 11, 17,  7, 11,
+    5, 10, 10, 10, 11, 11,  7, 11,  5, 10, 10, 10, 11, 17,  7, 11,
+    5, 10, 10, 18, 11, 11,  7, 11,  5,  5, 10,  4, 11, 17,  7, 11,
+    5, 10, 10,  4, 11, 11,  7, 11,  5,  5, 10,  4, 11, 17,  7, 11,
+];
+
 #[derive(Clone, Debug)]
 pub struct Instruction 
 {
@@ -87,6 +110,40 @@ impl Instruction
         (self.machine_code, self.name.clone())
     }
 
+    /// The name, with the address of a jump or call given as the symbol it is at or after.
+    pub fn get_name_with_symbols(&self, symbols: &SymbolTable) -> String
+    {
+        match (self.is_branch(), symbols.describe(self.get_address()))
+        {
+            (true, Some(symbol)) => self.branch_name(symbol),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// The two byte operand of the instruction.
+    pub fn get_address(&self) -> u16
+    {
+        self.immediate_lsb as u16 | ((self.immediate_msb as u16) << 8)
+    }
+
+    /// Jumps and calls to an address, conditional or not.
+    pub fn is_branch(&self) -> bool
+    {
+        matches!(self.machine_code & 0xC7, 0xC2 | 0xC4) || self.machine_code == 0xC3 || self.machine_code == 0xCD
+    }
+
+    fn branch_name(&self, target: String) -> String
+    {
+        format!("{:<4} {}", Instruction::instruction_to_string(self.instruction_type.clone()), target)
+    }
+
+    /// Clock periods the instruction takes, not counting the extra time of a taken
+    /// conditional call or return.
+    pub fn get_cycles(&self) -> u8
+    {
+        CYCLES[self.machine_code as usize]
+    }
+
     pub fn get_length(&self) -> u8
     {
         return match self.address_mode
@@ -113,6 +170,8 @@ impl Instruction
             // Move Instructions
             // low_nibble holds the destination register
             // high_nibble holds the source register
+            // MOV M,M would be 0x76, which is HLT instead
+            MOVE_INSTRUCTION_GROUP if b == 0x76 => self.set_instruction(InstructionTypes::HLT, "", 1, AddressMode::Unknown),
             MOVE_INSTRUCTION_GROUP => 
             {
 
@@ -201,11 +260,11 @@ impl Instruction
                     0xCB => self.name = "?? NOT IMP".to_string(),
                     0xCF => self.set_instruction(InstructionTypes::RST, "", 1, AddressMode::Direct),
                     0xD0 => self.set_instruction(InstructionTypes::RNC, "", 1, AddressMode::Direct),
-                    0xD3 => self.set_instruction(InstructionTypes::OUT, "", 2, AddressMode::Unknown),
+                    0xD3 => self.decode_io(InstructionTypes::OUT),
                     0xD7 => self.set_instruction(InstructionTypes::RST, "", 1, AddressMode::Direct),
                     0xD8 => self.set_instruction(InstructionTypes::RC, "", 1, AddressMode::Direct),
                     0xD9 => self.name = "?? NOT IMP".to_string(),
-                    0xDB => self.name = "?? NOT IMP".to_string(),
+                    0xDB => self.decode_io(InstructionTypes::IN),
                     0xDD => self.name = "?? NOT IMP".to_string(),
                     0xDF => self.set_instruction(InstructionTypes::RST, "", 1, AddressMode::Direct),
                     0xE0 => self.set_instruction(InstructionTypes::RPO, "", 1, AddressMode::StackPointer),
@@ -350,9 +409,8 @@ impl Instruction
 
         self.address_mode = AddressMode::ImmediateTwoBytes;
         self.cycles = 3;
-        self.instruction_type = instruction.clone();
-        let address = self.immediate_lsb as u16 | ((self.immediate_msb as u16) << 8);
-        self.name = format!("{:<4} {}", Instruction::instruction_to_string(instruction), address);
+        self.instruction_type = instruction;
+        self.name = self.branch_name(self.get_address().to_string());
     }
 
     fn immediate_op_helper(&mut self, name1: String, op1: InstructionTypes, name2: String, op2: InstructionTypes)
@@ -432,9 +490,15 @@ impl Instruction
 
         self.address_mode = AddressMode::ImmediateTwoBytes;
         self.cycles = 3;
-        self.instruction_type = instruction.clone();
-        let address = self.immediate_lsb as u16 | ((self.immediate_msb as u16) << 8);
-        self.name = format!("{:<4} {}", Instruction::instruction_to_string(instruction), address);
+        self.instruction_type = instruction;
+        self.name = self.branch_name(self.get_address().to_string());
+    }
+
+    /// IN and OUT take the port number as an immediate byte.
+    fn decode_io(&mut self, instruction: InstructionTypes)
+    {
+        self.set_instruction(instruction, "", 2, AddressMode::ImmediateOneByte);
+        self.name = format!("{:<4} {}", self.name, self.immediate_lsb);
     }
 
     fn set_instruction(&mut self, inst: InstructionTypes, suffix: &str, cycles: u8, address_mode: AddressMode)
@@ -0,0 +1,176 @@
+pub mod ihex;
+
+use std::path::Path;
+use crate::i8080::Processor;
+
+/// File formats programs and memory images are read from and written to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format
+{
+    /// Raw bytes, loaded at an origin given separately.
+    Binary,
+    /// Intel HEX records, which carry their own addresses and entry point.
+    IntelHex,
+}
+
+impl Format
+{
+    /// Parses a format name as given to `--format`.
+    pub fn parse(name: &str) -> Result<Format, String>
+    {
+        match name.to_ascii_lowercase().as_str()
+        {
+            "bin" | "binary"       => Ok(Format::Binary),
+            "hex" | "ihex" | "ihx" => Ok(Format::IntelHex),
+            _ => Err(format!("unknown format {}, expected bin or hex", name)),
+        }
+    }
+
+    /// The format files with the extension of `path` are in: .hex, .ihx and .ihex are Intel
+    /// HEX, anything else is raw binary.
+    pub fn from_path(path: &Path) -> Format
+    {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        match extension.as_str()
+        {
+            "hex" | "ihx" | "ihex" => Format::IntelHex,
+            _ => Format::Binary,
+        }
+    }
+}
+
+/// Bytes to put at an address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment
+{
+    pub address: u16,
+    pub bytes:   Vec<u8>,
+}
+
+/// A program or memory image: the parts of memory it fills, which need not be contiguous, and
+/// where to start running it if the file says.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Image
+{
+    pub segments: Vec<Segment>,
+    pub entry:    Option<u16>,
+}
+
+impl Image
+{
+    /// A raw binary loaded at `origin`.
+    pub fn from_binary(bytes: &[u8], origin: u16) -> Image
+    {
+        let mut image = Image::default();
+        image.add(origin, bytes);
+        image
+    }
+
+    /// The bytes of `start..=end` in the processor's memory for each range.
+    pub fn from_memory(processor: &Processor, ranges: &[(u16, u16)]) -> Image
+    {
+        let mut image = Image::default();
+        for (start, end) in ranges.iter().filter(|(start, end)| start <= end)
+        {
+            let bytes: Vec<u8> = (*start..=*end).map(|address| processor.get_memory_at(address)).collect();
+            image.add(*start, &bytes);
+        }
+        image
+    }
+
+    /// Reads a file in `format`, or the format its extension implies. Binaries are loaded at
+    /// `origin`.
+    pub fn load(path: &Path, format: Option<Format>, origin: u16) -> Result<Image, String>
+    {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let image = match format.unwrap_or_else(|| Format::from_path(path))
+        {
+            Format::Binary if bytes.len() > 0x10000 - origin as usize =>
+            {
+                return Err(format!("{} does not fit in memory at {:#06X}", path.display(), origin));
+            },
+            Format::Binary => Image::from_binary(&bytes, origin),
+            Format::IntelHex =>
+            {
+                let text = String::from_utf8_lossy(&bytes);
+                ihex::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+            },
+        };
+        Ok(image)
+    }
+
+    /// Writes the image in `format`, or the format the extension of `path` implies. Binaries
+    /// hold the bytes from the lowest address to the highest, gaps filled with zeros.
+    pub fn save(&self, path: &Path, format: Option<Format>) -> Result<(), String>
+    {
+        let bytes = match format.unwrap_or_else(|| Format::from_path(path))
+        {
+            Format::Binary   => self.to_binary(),
+            Format::IntelHex => ihex::write(self, ihex::DEFAULT_RECORD_SIZE).into_bytes(),
+        };
+        std::fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Adds bytes at `address`, joining them to a segment they continue.
+    pub fn add(&mut self, address: u16, bytes: &[u8])
+    {
+        if bytes.is_empty()
+        {
+            return;
+        }
+        match self.segments.last_mut()
+        {
+            Some(last) if last.address as usize + last.bytes.len() == address as usize => last.bytes.extend_from_slice(bytes),
+            _ => self.segments.push(Segment { address, bytes: bytes.to_vec() }),
+        }
+    }
+
+    /// The number of bytes in all segments.
+    pub fn len(&self) -> usize
+    {
+        self.segments.iter().map(|segment| segment.bytes.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    /// The lowest address the image fills.
+    pub fn start(&self) -> Option<u16>
+    {
+        self.segments.iter().map(|segment| segment.address).min()
+    }
+
+    /// The bytes from the lowest address to the highest, gaps filled with zeros.
+    pub fn to_binary(&self) -> Vec<u8>
+    {
+        let start = match self.start()
+        {
+            Some(start) => start as usize,
+            None => return Vec::new(),
+        };
+        let end = self.segments.iter().map(|segment| segment.address as usize + segment.bytes.len()).max().unwrap_or(start);
+        let mut binary = vec![0; end - start];
+        for segment in self.segments.iter()
+        {
+            let offset = segment.address as usize - start;
+            binary[offset..offset + segment.bytes.len()].copy_from_slice(&segment.bytes);
+        }
+        binary
+    }
+
+    /// Copies the segments into memory and, if the image has an entry point, points the
+    /// program counter at it.
+    pub fn load_into(&self, processor: &mut Processor)
+    {
+        for segment in self.segments.iter()
+        {
+            processor.load_bytes_at(segment.address, &segment.bytes);
+        }
+        if let Some(entry) = self.entry
+        {
+            processor.set_pc(entry);
+        }
+    }
+}
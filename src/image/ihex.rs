@@ -0,0 +1,165 @@
+use crate::image::Image;
+
+/// Data bytes per record written, the usual choice of assemblers and EPROM programmers.
+pub const DEFAULT_RECORD_SIZE: usize = 16;
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+/// Reads Intel HEX records. Data records may come in any order and leave gaps. Extended
+/// address records are accepted as long as the data stays within the 8080's 64K. The entry
+/// point comes from a start address record or, as 8080 tools write it, from the address of
+/// the end of file record.
+pub fn parse(text: &str) -> Result<Image, String>
+{
+    let mut image = Image::default();
+    let mut base: u32 = 0;
+    let mut ended = false;
+
+    for (number, line) in text.lines().enumerate()
+    {
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let line = line.trim();
+        if line.is_empty()
+        {
+            continue;
+        }
+        if ended
+        {
+            return Err(error("record after the end of file record".to_string()));
+        }
+
+        let hex = line.strip_prefix(':').ok_or_else(|| error("records start with :".to_string()))?;
+        if !hex.is_ascii()
+        {
+            return Err(error(format!("invalid hex digits in {}", line)));
+        }
+        if hex.len() % 2 != 0 || hex.len() < 10
+        {
+            return Err(error("record is too short or has an odd number of digits".to_string()));
+        }
+        let bytes = (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| error(format!("invalid hex digits in {}", line)))?;
+
+        let length = bytes[0] as usize;
+        if bytes.len() != length + 5
+        {
+            return Err(error(format!("record says {} data bytes but has {}", length, bytes.len() as isize - 5)));
+        }
+        let sum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if sum != 0
+        {
+            let expected = bytes[length + 4].wrapping_sub(sum);
+            return Err(error(format!("checksum is {:02X}, should be {:02X}", bytes[length + 4], expected)));
+        }
+
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]);
+        let data = &bytes[4..4 + length];
+        let word = |data: &[u8]| -> Result<u32, String>
+        {
+            match data
+            {
+                [high, low] => Ok(u16::from_be_bytes([*high, *low]) as u32),
+                _ => Err(error(format!("record type {:02X} should have 2 data bytes", bytes[3]))),
+            }
+        };
+
+        match bytes[3]
+        {
+            DATA =>
+            {
+                let start = base as u64 + address as u64;
+                if start + length as u64 > 0x10000
+                {
+                    return Err(error(format!("data at {:#X} is beyond the 64K the 8080 addresses", start)));
+                }
+                image.add(start as u16, data);
+            },
+            END_OF_FILE =>
+            {
+                if address != 0 && image.entry.is_none()
+                {
+                    image.entry = Some(address);
+                }
+                ended = true;
+            },
+            EXTENDED_SEGMENT_ADDRESS => base = word(data)? << 4,
+            EXTENDED_LINEAR_ADDRESS => base = word(data)? << 16,
+            START_SEGMENT_ADDRESS | START_LINEAR_ADDRESS =>
+            {
+                let entry = match data
+                {
+                    [a, b, c, d] if bytes[3] == START_SEGMENT_ADDRESS =>
+                        ((u16::from_be_bytes([*a, *b]) as u32) << 4) + u16::from_be_bytes([*c, *d]) as u32,
+                    [a, b, c, d] => u32::from_be_bytes([*a, *b, *c, *d]),
+                    _ => return Err(error("start address records have 4 data bytes".to_string())),
+                };
+                if entry > 0xFFFF
+                {
+                    return Err(error(format!("start address {:#X} is beyond 64K", entry)));
+                }
+                image.entry = Some(entry as u16);
+            },
+            other => return Err(error(format!("unknown record type {:02X}", other))),
+        }
+    }
+
+    if !ended
+    {
+        return Err("missing end of file record".to_string());
+    }
+    sort_segments(&mut image);
+    Ok(image)
+}
+
+/// Orders the segments by address and joins the ones that touch.
+fn sort_segments(image: &mut Image)
+{
+    let mut segments = std::mem::take(&mut image.segments);
+    segments.sort_by_key(|segment| segment.address);
+    for segment in segments
+    {
+        image.add(segment.address, &segment.bytes);
+    }
+}
+
+/// Writes the image as data records of up to `record_size` bytes and an end of file record,
+/// which carries the entry point if there is one.
+pub fn write(image: &Image, record_size: usize) -> String
+{
+    let mut text = String::new();
+    for segment in image.segments.iter()
+    {
+        for (i, chunk) in segment.bytes.chunks(record_size.clamp(1, 255)).enumerate()
+        {
+            let address = segment.address.wrapping_add((i * record_size.clamp(1, 255)) as u16);
+            text.push_str(&record(DATA, address, chunk));
+        }
+    }
+    text.push_str(&record(END_OF_FILE, image.entry.unwrap_or(0), &[]));
+    text
+}
+
+fn record(kind: u8, address: u16, data: &[u8]) -> String
+{
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(data);
+    let checksum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    bytes.push(checksum);
+
+    let mut line = String::from(":");
+    for byte in bytes
+    {
+        line.push_str(&format!("{:02X}", byte));
+    }
+    line.push('\n');
+    line
+}
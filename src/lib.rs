@@ -1,3 +1,14 @@
 pub mod i8080;
 pub mod debugger;
 pub mod utils;
+pub mod cpm;
+pub mod console;
+pub mod machines;
+pub mod image;
+pub mod assembler;
+pub mod linker;
+pub mod symbols;
+pub mod disassembler;
+pub mod coverage;
+pub mod call_stack;
+pub mod profiler;
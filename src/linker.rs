@@ -0,0 +1,349 @@
+pub mod rel;
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::image::Image;
+use rel::{Address, Item, Module, SegmentType};
+
+/// Where the linker puts the segments.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout
+{
+    /// The program segment of the first module, the others following it.
+    pub program: u16,
+    /// The data segments, by default after the last program segment.
+    pub data:    Option<u16>,
+}
+
+impl Default for Layout
+{
+    /// A CP/M program at 100H.
+    fn default() -> Layout
+    {
+        Layout { program: 0x100, data: None }
+    }
+}
+
+/// A linked program and the addresses of its public symbols.
+#[derive(Clone, Debug, Default)]
+pub struct Link
+{
+    pub image:   Image,
+    /// The entry points of every module, by address.
+    pub symbols: Vec<(String, u16)>,
+}
+
+impl Link
+{
+    /// The symbol map as `ADDR NAME` lines, the format the assembler writes too.
+    pub fn write_symbols(&self) -> String
+    {
+        self.symbols.iter().map(|(name, value)| format!("{:04X} {}\n", value, name)).collect()
+    }
+}
+
+/// Reads the modules of a REL file or library.
+pub fn load_modules(path: &Path) -> Result<Vec<Module>, String>
+{
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    rel::parse(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Where a module's segments went.
+struct Bases
+{
+    program: u16,
+    data:    u16,
+}
+
+/// Links `modules`, and the modules of `libraries` that define symbols they need, like L80:
+/// the program segments one after the other from `layout.program`, then the data segments,
+/// then the common blocks.
+pub fn link(modules: &[Module], libraries: &[Module], layout: Layout) -> Result<Link, String>
+{
+    let modules = select_modules(modules, libraries);
+
+    let mut bases = Vec::new();
+    let mut next = layout.program as u32;
+    for module in modules.iter()
+    {
+        bases.push(Bases { program: next as u16, data: 0 });
+        next += module.get_program_size() as u32;
+    }
+    let mut next_data = layout.data.map_or(next, |data| data as u32);
+    for (module, bases) in modules.iter().zip(bases.iter_mut())
+    {
+        bases.data = next_data as u16;
+        next_data += module.get_data_size() as u32;
+    }
+
+    let mut common_sizes: Vec<(String, u16)> = Vec::new();
+    for item in modules.iter().flat_map(|module| module.items.iter())
+    {
+        if let Item::CommonSize(size, name) = item
+        {
+            match common_sizes.iter_mut().find(|(common, _)| common == name)
+            {
+                Some((_, largest)) => *largest = (*largest).max(size.offset),
+                None => common_sizes.push((name.clone(), size.offset)),
+            }
+        }
+    }
+    let mut next_common = next.max(next_data);
+    let mut commons = HashMap::new();
+    for (name, size) in common_sizes
+    {
+        commons.insert(name, next_common as u16);
+        next_common += size as u32;
+    }
+    if next_common > 0x10000
+    {
+        return Err(format!("the program needs {} bytes more than there is memory", next_common - 0x10000));
+    }
+
+    let mut linker = Linker
+    {
+        memory:    vec![0; 0x10000],
+        used:      vec![false; 0x10000],
+        relocated: HashSet::new(),
+        commons,
+        common:    0,
+        symbols:   HashMap::new(),
+        chains:    Vec::new(),
+        offsets:   Vec::new(),
+    };
+    for (module, bases) in modules.iter().zip(bases.iter())
+    {
+        linker.define_symbols(module, bases)?;
+    }
+    let mut entry = None;
+    for (module, bases) in modules.iter().zip(bases.iter())
+    {
+        linker.load(module, bases)?;
+        if let (None, Some(start)) = (entry, module.get_start())
+        {
+            entry = Some(linker.resolve(start, bases));
+        }
+    }
+    linker.fix_up()?;
+
+    let mut symbols: Vec<(String, u16)> = linker.symbols.iter().map(|(name, (value, _))| (name.clone(), *value)).collect();
+    symbols.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+    let mut image = linker.image();
+    image.entry = entry;
+    Ok(Link { image, symbols })
+}
+
+/// The modules and, in the order they are in the libraries, the library modules that define
+/// a symbol still needed.
+fn select_modules<'a>(modules: &'a [Module], libraries: &'a [Module]) -> Vec<&'a Module>
+{
+    let mut selected: Vec<&Module> = modules.iter().collect();
+    let mut taken = vec![false; libraries.len()];
+    loop
+    {
+        let defined: HashSet<&str> = selected.iter().flat_map(|module| module.get_entry_points()).map(|(name, _)| name).collect();
+        let needed: HashSet<&str> = selected.iter().flat_map(|module| module.get_externals())
+            .filter(|name| !defined.contains(name))
+            .collect();
+        let found = libraries.iter().enumerate().position(|(i, module)|
+        {
+            !taken[i] && module.get_entry_points().iter().any(|(name, _)| needed.contains(name))
+        });
+        match found
+        {
+            Some(i) =>
+            {
+                taken[i] = true;
+                selected.push(&libraries[i]);
+            },
+            None => return selected,
+        }
+    }
+}
+
+/// A chain of references and what to fill it with.
+enum Target
+{
+    Symbol(String),
+    Value(u16),
+}
+
+struct Linker
+{
+    memory:    Vec<u8>,
+    used:      Vec<bool>,
+    /// Addresses of the relocatable words, which do not end a chain even when they are 0.
+    relocated: HashSet<u16>,
+    commons:   HashMap<String, u16>,
+    /// The base of the common block selected last.
+    common:    u16,
+    /// Every entry symbol, its address and the module defining it.
+    symbols:   HashMap<String, (u16, String)>,
+    chains:    Vec<(u16, Target)>,
+    offsets:   Vec<(u16, i32)>,
+}
+
+impl Linker
+{
+    fn resolve(&self, address: Address, bases: &Bases) -> u16
+    {
+        let base = match address.segment
+        {
+            SegmentType::Absolute => 0,
+            SegmentType::Program => bases.program,
+            SegmentType::Data => bases.data,
+            SegmentType::Common => self.common,
+        };
+        base.wrapping_add(address.offset)
+    }
+
+    fn define_symbols(&mut self, module: &Module, bases: &Bases) -> Result<(), String>
+    {
+        let name = module.get_name().unwrap_or("?").to_string();
+        for item in module.items.iter()
+        {
+            match item
+            {
+                Item::SelectCommon(common) => self.select_common(common)?,
+                Item::EntryPoint(address, symbol) =>
+                {
+                    let value = self.resolve(*address, bases);
+                    if let Some((_, other)) = self.symbols.get(symbol)
+                    {
+                        return Err(format!("{} is defined in both {} and {}", symbol, other, name));
+                    }
+                    self.symbols.insert(symbol.clone(), (value, name.clone()));
+                },
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn select_common(&mut self, name: &str) -> Result<(), String>
+    {
+        self.common = *self.commons.get(name).ok_or_else(|| format!("common block /{}/ has no size", name))?;
+        Ok(())
+    }
+
+    /// Puts a module's bytes in memory and notes its references to fill in.
+    fn load(&mut self, module: &Module, bases: &Bases) -> Result<(), String>
+    {
+        let mut location = Address { segment: SegmentType::Program, offset: 0 };
+        for item in module.items.iter()
+        {
+            let here = self.resolve(location, bases);
+            match item
+            {
+                Item::Byte(byte) =>
+                {
+                    self.write(here, *byte);
+                    location.offset = location.offset.wrapping_add(1);
+                },
+                Item::Word(address) =>
+                {
+                    let value = self.resolve(*address, bases);
+                    self.write(here, value as u8);
+                    self.write(here.wrapping_add(1), (value >> 8) as u8);
+                    self.relocated.insert(here);
+                    location.offset = location.offset.wrapping_add(2);
+                },
+                Item::SelectCommon(common) => self.select_common(common)?,
+                Item::ChainExternal(head, name) if *head != (Address { segment: SegmentType::Absolute, offset: 0 }) =>
+                {
+                    self.chains.push((self.resolve(*head, bases), Target::Symbol(name.clone())));
+                },
+                Item::ChainAddress(head) => self.chains.push((self.resolve(*head, bases), Target::Value(here))),
+                Item::ExternalPlusOffset(offset) => self.offsets.push((here, self.resolve(*offset, bases) as i32)),
+                Item::ExternalMinusOffset(offset) => self.offsets.push((here, -(self.resolve(*offset, bases) as i32))),
+                Item::SetLocation(address) => location = *address,
+                Item::EndModule(_) => break,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, address: u16, byte: u8)
+    {
+        self.memory[address as usize] = byte;
+        self.used[address as usize] = true;
+    }
+
+    fn read_word(&self, address: u16) -> u16
+    {
+        self.memory[address as usize] as u16 | ((self.memory[address.wrapping_add(1) as usize] as u16) << 8)
+    }
+
+    /// Fills in the chains of references and adds the offsets to them.
+    fn fix_up(&mut self) -> Result<(), String>
+    {
+        let mut undefined: Vec<&str> = Vec::new();
+        let chains = std::mem::take(&mut self.chains);
+        for (head, target) in chains.iter()
+        {
+            let value = match target
+            {
+                Target::Symbol(name) => match self.symbols.get(name)
+                {
+                    Some((value, _)) => *value,
+                    None =>
+                    {
+                        if !undefined.contains(&name.as_str())
+                        {
+                            undefined.push(name);
+                        }
+                        continue;
+                    },
+                },
+                Target::Value(value) => *value,
+            };
+
+            let mut address = *head;
+            for _ in 0..0x8000
+            {
+                let next = self.read_word(address);
+                let end = next == 0 && !self.relocated.contains(&address);
+                self.write(address, value as u8);
+                self.write(address.wrapping_add(1), (value >> 8) as u8);
+                if end
+                {
+                    break;
+                }
+                address = next;
+            }
+        }
+        if !undefined.is_empty()
+        {
+            return Err(format!("undefined symbols: {}", undefined.join(", ")));
+        }
+
+        for (address, offset) in std::mem::take(&mut self.offsets)
+        {
+            let value = (self.read_word(address) as i32 + offset) as u16;
+            self.write(address, value as u8);
+            self.write(address.wrapping_add(1), (value >> 8) as u8);
+        }
+        Ok(())
+    }
+
+    /// The memory written, in runs of contiguous bytes.
+    fn image(&self) -> Image
+    {
+        let mut image = Image::default();
+        let mut address = 0;
+        while address < self.used.len()
+        {
+            if !self.used[address]
+            {
+                address += 1;
+                continue;
+            }
+            let end = self.used[address..].iter().position(|used| !used).map_or(self.used.len(), |length| address + length);
+            image.add(address as u16, &self.memory[address..end]);
+            address = end;
+        }
+        image
+    }
+}
@@ -0,0 +1,270 @@
+/// The segments a relocatable address can be in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SegmentType
+{
+    Absolute,
+    /// Code, CSEG in M80.
+    Program,
+    /// DSEG in M80.
+    Data,
+    /// The COMMON block selected last.
+    Common,
+}
+
+impl SegmentType
+{
+    fn from_bits(bits: u16) -> SegmentType
+    {
+        match bits & 3
+        {
+            0 => SegmentType::Absolute,
+            1 => SegmentType::Program,
+            2 => SegmentType::Data,
+            _ => SegmentType::Common,
+        }
+    }
+}
+
+/// An offset in a segment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Address
+{
+    pub segment: SegmentType,
+    pub offset:  u16,
+}
+
+/// The items of a REL file. The special link items are numbered as in the M80 manual.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item
+{
+    /// A byte loaded as it is.
+    Byte(u8),
+    /// A word relative to the start of a segment.
+    Word(Address),
+    /// 0: a symbol the module defines, listed before the code.
+    EntrySymbol(String),
+    /// 1: the common block `Common` addresses are relative to from here on.
+    SelectCommon(String),
+    /// 2: the module name.
+    ProgramName(String),
+    /// 3: a library to search.
+    RequestLibrary(String),
+    /// 4: reserved for extensions.
+    Extension(String),
+    /// 5: the size of a common block.
+    CommonSize(Address, String),
+    /// 6: the last reference to an external symbol, each reference holding the address of
+    /// the one before and the first an absolute 0.
+    ChainExternal(Address, String),
+    /// 7: the address of an entry symbol.
+    EntryPoint(Address, String),
+    /// 8: subtract from the external reference at the location counter.
+    ExternalMinusOffset(Address),
+    /// 9: add to the external reference at the location counter.
+    ExternalPlusOffset(Address),
+    /// 10: the size of the data segment.
+    DataSize(Address),
+    /// 11: sets the location counter.
+    SetLocation(Address),
+    /// 12: a chain of references to fill with the location counter.
+    ChainAddress(Address),
+    /// 13: the size of the program segment.
+    ProgramSize(Address),
+    /// 14: the end of the module, with its start address unless it is absolute 0.
+    EndModule(Address),
+}
+
+/// A module of a REL file or library.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Module
+{
+    pub items: Vec<Item>,
+}
+
+impl Module
+{
+    pub fn get_name(&self) -> Option<&str>
+    {
+        self.items.iter().find_map(|item| match item
+        {
+            Item::ProgramName(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn get_program_size(&self) -> u16
+    {
+        self.items.iter().find_map(|item| match item
+        {
+            Item::ProgramSize(size) => Some(size.offset),
+            _ => None,
+        }).unwrap_or(0)
+    }
+
+    pub fn get_data_size(&self) -> u16
+    {
+        self.items.iter().find_map(|item| match item
+        {
+            Item::DataSize(size) => Some(size.offset),
+            _ => None,
+        }).unwrap_or(0)
+    }
+
+    /// The symbols the module defines and where.
+    pub fn get_entry_points(&self) -> Vec<(&str, Address)>
+    {
+        self.items.iter().filter_map(|item| match item
+        {
+            Item::EntryPoint(address, name) => Some((name.as_str(), *address)),
+            _ => None,
+        }).collect()
+    }
+
+    /// The external symbols the module refers to.
+    pub fn get_externals(&self) -> Vec<&str>
+    {
+        self.items.iter().filter_map(|item| match item
+        {
+            Item::ChainExternal(_, name) => Some(name.as_str()),
+            _ => None,
+        }).collect()
+    }
+
+    /// Where the program starts, if this is the main module.
+    pub fn get_start(&self) -> Option<Address>
+    {
+        self.items.iter().find_map(|item| match item
+        {
+            Item::EndModule(address) if *address != Address { segment: SegmentType::Absolute, offset: 0 } => Some(*address),
+            _ => None,
+        })
+    }
+}
+
+/// Reads the bits of a REL file, most significant first.
+struct BitReader<'a>
+{
+    bytes:    &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a>
+{
+    fn read(&mut self, bits: usize) -> Result<u16, String>
+    {
+        let mut value = 0;
+        for _ in 0..bits
+        {
+            let byte = self.bytes.get(self.position / 8)
+                .ok_or_else(|| format!("the file ends in the middle of an item at byte {}", self.position / 8))?;
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as u16;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, String>
+    {
+        Ok(self.read(8)? as u8)
+    }
+
+    /// A 16 bit value, low byte first.
+    fn read_word(&mut self) -> Result<u16, String>
+    {
+        let low = self.read_byte()? as u16;
+        Ok(low | ((self.read_byte()? as u16) << 8))
+    }
+
+    /// The A field of a special link item.
+    fn read_address(&mut self) -> Result<Address, String>
+    {
+        let segment = SegmentType::from_bits(self.read(2)?);
+        Ok(Address { segment, offset: self.read_word()? })
+    }
+
+    /// The B field of a special link item: a 3 bit length and that many characters.
+    fn read_name(&mut self) -> Result<String, String>
+    {
+        let length = match self.read(3)?
+        {
+            0 => 8,
+            length => length,
+        };
+        let mut name = String::new();
+        for _ in 0..length
+        {
+            name.push((self.read_byte()? & 0x7F) as char);
+        }
+        Ok(name)
+    }
+
+    fn align(&mut self)
+    {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+}
+
+/// Parses a REL file, which holds one module, or a library of them, up to the end of file
+/// item.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Module>, String>
+{
+    let mut reader = BitReader { bytes, position: 0 };
+    let mut modules = Vec::new();
+    let mut module = Module::default();
+    loop
+    {
+        // Some tools leave out the end of file item and pad the last record instead
+        let rest = &bytes[reader.position.div_ceil(8).min(bytes.len())..];
+        if module.items.is_empty() && !modules.is_empty() && rest.iter().all(|byte| *byte == 0x1A || *byte == 0)
+        {
+            return Ok(modules);
+        }
+
+        let item = if reader.read(1)? == 0
+        {
+            Item::Byte(reader.read_byte()?)
+        }
+        else
+        {
+            match reader.read(2)?
+            {
+                0 => match reader.read(4)?
+                {
+                    0  => Item::EntrySymbol(reader.read_name()?),
+                    1  => Item::SelectCommon(reader.read_name()?),
+                    2  => Item::ProgramName(reader.read_name()?),
+                    3  => Item::RequestLibrary(reader.read_name()?),
+                    4  => Item::Extension(reader.read_name()?),
+                    5  => { let address = reader.read_address()?; Item::CommonSize(address, reader.read_name()?) },
+                    6  => { let address = reader.read_address()?; Item::ChainExternal(address, reader.read_name()?) },
+                    7  => { let address = reader.read_address()?; Item::EntryPoint(address, reader.read_name()?) },
+                    8  => Item::ExternalMinusOffset(reader.read_address()?),
+                    9  => Item::ExternalPlusOffset(reader.read_address()?),
+                    10 => Item::DataSize(reader.read_address()?),
+                    11 => Item::SetLocation(reader.read_address()?),
+                    12 => Item::ChainAddress(reader.read_address()?),
+                    13 => Item::ProgramSize(reader.read_address()?),
+                    14 => Item::EndModule(reader.read_address()?),
+                    _  =>
+                    {
+                        if !module.items.is_empty()
+                        {
+                            return Err("the file ends inside a module".to_string());
+                        }
+                        return Ok(modules);
+                    },
+                },
+                segment => Item::Word(Address { segment: SegmentType::from_bits(segment), offset: reader.read_word()? }),
+            }
+        };
+
+        let end = matches!(item, Item::EndModule(_));
+        module.items.push(item);
+        if end
+        {
+            reader.align();
+            modules.push(std::mem::take(&mut module));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+pub mod altair;
+pub mod definition;
+pub mod invaders;
+pub mod keymap;
+pub mod screenshot;
+pub mod serial;
+pub mod shift_register;
+pub mod sound;
+pub mod toml;
+
+use crate::i8080::Processor;
+use keymap::InputBinding;
+use screenshot::ColorOverlay;
+
+/// A computer built around the 8080: the devices on its ports and the interrupts they raise.
+pub trait Machine
+{
+    /// Executes one instruction and lets the devices react to it.
+    fn clock(&mut self, processor: &mut Processor);
+
+    /// The picture the machine is currently showing, if it has a display.
+    fn framebuffer(&self, _processor: &Processor) -> Option<Framebuffer>
+    {
+        None
+    }
+
+    /// The coloured overlay in front of the monitor, if the cabinet has one.
+    fn color_overlay(&self) -> Option<ColorOverlay>
+    {
+        None
+    }
+
+    /// Presses or releases the buttons in `mask` on input port `port`.
+    fn set_input(&mut self, _port: u8, _mask: u8, _pressed: bool)
+    {
+    }
+
+    /// The inputs key maps can refer to by name, such as `coin`.
+    fn named_inputs(&self) -> Vec<(String, InputBinding)>
+    {
+        Vec::new()
+    }
+}
+
+/// A machine lent out, such as to the debugger, and used again afterwards.
+impl<M: Machine + ?Sized> Machine for &mut M
+{
+    fn clock(&mut self, processor: &mut Processor)
+    {
+        (**self).clock(processor)
+    }
+
+    fn framebuffer(&self, processor: &Processor) -> Option<Framebuffer>
+    {
+        (**self).framebuffer(processor)
+    }
+
+    fn color_overlay(&self) -> Option<ColorOverlay>
+    {
+        (**self).color_overlay()
+    }
+
+    fn set_input(&mut self, port: u8, mask: u8, pressed: bool)
+    {
+        (**self).set_input(port, mask, pressed)
+    }
+
+    fn named_inputs(&self) -> Vec<(String, InputBinding)>
+    {
+        (**self).named_inputs()
+    }
+}
+
+/// A board on the I/O bus. Reading a port can have side effects, such as taking a received
+/// character, so the machine first asks for the byte an IN would read and tells the device
+/// once the processor has actually read it.
+pub trait PortDevice
+{
+    /// The byte an IN from `port` would read now, or None if the device does not decode `port`.
+    fn peek(&mut self, port: u8) -> Option<u8>;
+
+    /// An IN instruction read `port`.
+    fn read(&mut self, _port: u8)
+    {
+    }
+
+    /// An OUT instruction wrote `value` to `port`. Returns false if the device does not decode `port`.
+    fn write(&mut self, port: u8, value: u8) -> bool;
+
+    /// Lets the device catch up with the processor, which has run `cycle` clock periods.
+    fn update(&mut self, _cycle: u64)
+    {
+    }
+
+    /// The RST instruction the device is requesting an interrupt with, if any. Requests are
+    /// level triggered and stay up until the device is serviced.
+    fn interrupt(&mut self) -> Option<u8>
+    {
+        None
+    }
+
+    /// True once the device has lost its host side, such as a console that was closed.
+    fn is_closed(&self) -> bool
+    {
+        false
+    }
+}
+
+/// The port the next instruction reads if it is an IN, so devices whose reads have side
+/// effects can put their byte on the bus just before it executes.
+pub fn pending_input_port(processor: &Processor) -> Option<u8>
+{
+    let pc = processor.get_pc();
+    let is_input = !processor.get_halted() && processor.get_memory_at(pc) == 0xDB;
+    is_input.then(|| processor.get_memory_at(pc.wrapping_add(1)))
+}
+
+/// A one bit per pixel picture, stored row by row from the top left corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer
+{
+    pub width:  usize,
+    pub height: usize,
+    pub pixels: Vec<bool>,
+}
+
+impl Framebuffer
+{
+    pub fn new(width: usize, height: usize) -> Framebuffer
+    {
+        Framebuffer { width, height, pixels: vec![false; width * height] }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool
+    {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, lit: bool)
+    {
+        self.pixels[y * self.width + x] = lit;
+    }
+
+    /// Decodes a one bit per pixel picture from video RAM starting at `start`.
+    pub fn from_video_ram(processor: &Processor, start: u16, width: usize, height: usize, layout: VideoLayout) -> Framebuffer
+    {
+        let mut framebuffer = Framebuffer::new(width, height);
+        let (lines, line_bytes) = match layout
+        {
+            VideoLayout::Rows    => (height, width.div_ceil(8)),
+            VideoLayout::Columns => (width, height.div_ceil(8)),
+        };
+
+        for line in 0..lines
+        {
+            for byte_index in 0..line_bytes
+            {
+                let address = start.wrapping_add((line * line_bytes + byte_index) as u16);
+                let byte = processor.get_memory_at(address);
+                for bit in 0..8
+                {
+                    let lit = byte & (1 << bit) != 0;
+                    match layout
+                    {
+                        VideoLayout::Rows =>
+                        {
+                            let x = byte_index * 8 + 7 - bit;
+                            if x < width
+                            {
+                                framebuffer.set(x, line, lit);
+                            }
+                        },
+                        VideoLayout::Columns =>
+                        {
+                            let from_bottom = byte_index * 8 + bit;
+                            if from_bottom < height
+                            {
+                                framebuffer.set(line, height - 1 - from_bottom, lit);
+                            }
+                        },
+                    }
+                }
+            }
+        }
+        framebuffer
+    }
+}
+
+/// How video RAM maps onto the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VideoLayout
+{
+    /// Row by row from the top left corner, the highest bit of each byte leftmost.
+    Rows,
+    /// Column by column from the bottom left corner, the lowest bit of each byte lowest, as
+    /// on a monitor turned on its side.
+    Columns,
+}
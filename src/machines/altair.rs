@@ -0,0 +1,311 @@
+use std::path::PathBuf;
+
+use crate::i8080::Processor;
+use crate::i8080::instructions::InstructionTypes;
+use crate::machines::{pending_input_port, Machine, PortDevice};
+
+/// The Altair runs its 8080 at 2 MHz.
+pub const CLOCK_FREQUENCY: u32 = 2_000_000;
+
+/// IN from this port reads the sense switches, the upper eight address switches.
+pub const SENSE_SWITCH_PORT: u8 = 0xFF;
+
+/// The status lights in the order they sit on the front panel.
+pub const STATUS_LIGHTS: [&str; 10] = ["INTE", "PROT", "MEMR", "INP", "MI", "OUT", "HLTA", "STACK", "WO", "INT"];
+
+const INTE: usize = 0;
+const MEMR: usize = 2;
+const INP: usize = 3;
+const MI: usize = 4;
+const OUT: usize = 5;
+const HLTA: usize = 6;
+const WO: usize = 8;
+
+/// What the lights on the front panel show: the last machine cycle on the buses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PanelLights
+{
+    pub status:  [bool; 10],
+    pub wait:    bool,
+    pub hlda:    bool,
+    pub address: u16,
+    pub data:    u8,
+}
+
+/// Parses a `file@address` load request. Without an address the file is loaded at 0.
+pub fn parse_load(text: &str) -> Result<(PathBuf, u16), String>
+{
+    let (path, address) = match text.rsplit_once('@')
+    {
+        Some((path, address)) => (path, address),
+        None => return Ok((PathBuf::from(text), 0)),
+    };
+    let parsed = match address.strip_prefix("0x").or_else(|| address.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None      => address.parse::<u16>(),
+    };
+    parsed.map(|address| (PathBuf::from(path), address)).map_err(|e| format!("Invalid address {}: {}", address, e))
+}
+
+/// The MITS Altair 8800: 64K of RAM, the front panel switches and lights, the sense
+/// switches on port 0xFF and the boards plugged into the I/O bus. Ports no board decodes
+/// read 0xFF. The panel functions work on the processor the way the panel
+/// logic does, by jamming instructions and addresses onto the bus, which here means setting
+/// the program counter and memory directly.
+pub struct Altair
+{
+    switches: u16,
+    running:  bool,
+    lights:   PanelLights,
+    devices:  Vec<Box<dyn PortDevice>>,
+}
+
+impl Altair
+{
+    pub fn new() -> Altair
+    {
+        Altair
+        {
+            switches: 0,
+            running:  false,
+            lights:   PanelLights
+            {
+                status:  [false; 10],
+                wait:    true,
+                hlda:    false,
+                address: 0,
+                data:    0,
+            },
+            devices:  Vec::new(),
+        }
+    }
+
+    /// Plugs a board into the I/O bus.
+    pub fn attach(&mut self, device: Box<dyn PortDevice>)
+    {
+        self.devices.push(device);
+    }
+
+    /// True once a board has lost its host side, such as the console being closed.
+    pub fn is_closed(&self) -> bool
+    {
+        self.devices.iter().any(|device| device.is_closed())
+    }
+
+    /// The sixteen address switches, A15 to A0. The lower eight are also the data switches
+    /// and the upper eight the sense switches.
+    pub fn get_switches(&self) -> u16
+    {
+        self.switches
+    }
+
+    pub fn set_switches(&mut self, switches: u16)
+    {
+        self.switches = switches;
+    }
+
+    pub fn toggle_switch(&mut self, switch: u8)
+    {
+        self.switches ^= 1 << (switch & 0x0F);
+    }
+
+    pub fn get_lights(&self) -> PanelLights
+    {
+        self.lights
+    }
+
+    pub fn is_running(&self) -> bool
+    {
+        self.running
+    }
+
+    /// EXAMINE: jumps to the address on the switches and shows the byte there.
+    pub fn examine(&mut self, processor: &mut Processor)
+    {
+        if !self.running
+        {
+            processor.set_pc(self.switches);
+            self.show_fetch(processor);
+        }
+    }
+
+    /// EXAMINE NEXT: moves on to the next address.
+    pub fn examine_next(&mut self, processor: &mut Processor)
+    {
+        if !self.running
+        {
+            processor.set_pc(processor.get_pc().wrapping_add(1));
+            self.show_fetch(processor);
+        }
+    }
+
+    /// DEPOSIT: stores the data switches at the current address.
+    pub fn deposit(&mut self, processor: &mut Processor)
+    {
+        if !self.running
+        {
+            processor.set_memory_at(processor.get_pc(), self.switches as u8);
+            self.show_fetch(processor);
+        }
+    }
+
+    /// DEPOSIT NEXT: moves on to the next address and stores the data switches there.
+    pub fn deposit_next(&mut self, processor: &mut Processor)
+    {
+        if !self.running
+        {
+            processor.set_pc(processor.get_pc().wrapping_add(1));
+            self.deposit(processor);
+        }
+    }
+
+    pub fn run(&mut self)
+    {
+        self.running = true;
+        self.lights.wait = false;
+    }
+
+    pub fn stop(&mut self, processor: &Processor)
+    {
+        self.running = false;
+        self.show_fetch(processor);
+    }
+
+    /// SINGLE STEP: executes one instruction while stopped.
+    pub fn single_step(&mut self, processor: &mut Processor)
+    {
+        if !self.running
+        {
+            self.clock(processor);
+            self.lights.wait = true;
+        }
+    }
+
+    /// RESET: clears the program counter, registers and interrupt enable. Memory is kept.
+    pub fn reset(&mut self, processor: &mut Processor)
+    {
+        processor.reset();
+        self.show_fetch(processor);
+    }
+
+    /// Runs for `cycles` processor cycles, or until stopped.
+    pub fn run_for(&mut self, processor: &mut Processor, cycles: u64)
+    {
+        let end = processor.get_cycles() + cycles;
+        while self.running && processor.get_cycles() < end
+        {
+            self.clock(processor);
+        }
+    }
+
+    /// Puts the byte on the data bus for the IN instruction about to execute, if it is one.
+    fn prepare_input(&mut self, processor: &mut Processor)
+    {
+        let port = match pending_input_port(processor)
+        {
+            Some(port) => port,
+            None => return,
+        };
+        let value = match port
+        {
+            SENSE_SWITCH_PORT => (self.switches >> 8) as u8,
+            _ => self.devices.iter_mut().find_map(|device| device.peek(port)).unwrap_or(0xFF),
+        };
+        processor.set_input_port(port, value);
+    }
+
+    /// Shows the opcode fetch of the instruction at the program counter, the state the
+    /// processor waits in while stopped.
+    fn show_fetch(&mut self, processor: &Processor)
+    {
+        let pc = processor.get_pc();
+        let mut status = [false; 10];
+        status[INTE] = processor.get_interrupts_enabled();
+        status[HLTA] = processor.get_halted();
+        status[MEMR] = !status[HLTA];
+        status[MI] = !status[HLTA];
+        status[WO] = true;
+
+        self.lights = PanelLights
+        {
+            status,
+            wait:    !self.running,
+            hlda:    false,
+            address: pc,
+            data:    processor.get_memory_at(pc),
+        };
+    }
+
+    /// Shows the last instruction's I/O cycle, where the port number appears on both halves
+    /// of the address bus, or else the next opcode fetch.
+    fn update_lights(&mut self, processor: &Processor)
+    {
+        let op = processor.get_current_op();
+        let io = match op.instruction_type
+        {
+            InstructionTypes::IN  => Some(INP),
+            InstructionTypes::OUT => Some(OUT),
+            _ => None,
+        };
+
+        match io
+        {
+            Some(light) if !processor.get_halted() =>
+            {
+                let port = processor.get_memory_at(processor.get_pc().wrapping_sub(1));
+                let mut status = [false; 10];
+                status[INTE] = processor.get_interrupts_enabled();
+                status[light] = true;
+                status[WO] = light == INP;
+
+                self.lights = PanelLights
+                {
+                    status,
+                    wait:    !self.running,
+                    hlda:    false,
+                    address: (port as u16) << 8 | port as u16,
+                    data:    processor.get_registers().accumulator,
+                };
+            },
+            _ => self.show_fetch(processor),
+        }
+    }
+}
+
+impl Default for Altair
+{
+    fn default() -> Altair
+    {
+        Altair::new()
+    }
+}
+
+impl Machine for Altair
+{
+    fn clock(&mut self, processor: &mut Processor)
+    {
+        self.prepare_input(processor);
+        processor.clock();
+
+        if let Some(port) = processor.take_input()
+        {
+            self.devices.iter_mut().for_each(|device| device.read(port));
+        }
+        if let Some((port, value)) = processor.take_output()
+        {
+            self.devices.iter_mut().any(|device| device.write(port, value));
+        }
+        self.update_lights(processor);
+
+        let cycle = processor.get_cycles();
+        for device in self.devices.iter_mut()
+        {
+            device.update(cycle);
+            if let Some(rst) = device.interrupt()
+            {
+                processor.interrupt(rst);
+            }
+        }
+    }
+}
@@ -0,0 +1,588 @@
+use std::path::{Path, PathBuf};
+
+use crate::console::{Console, HostConsole, NullConsole};
+use crate::i8080::Processor;
+use crate::machines::{pending_input_port, Framebuffer, Machine, PortDevice, VideoLayout};
+use crate::machines::keymap::{parse_binding, InputBinding};
+use crate::machines::screenshot::{Band, ColorOverlay};
+use crate::machines::serial::{Sio, TwoSio, DEFAULT_BAUD_RATE, DEFAULT_INTERRUPT, SIO_PORT, TWO_SIO_PORT};
+use crate::machines::shift_register::ShiftRegister;
+use crate::machines::toml::{self, Table, Value};
+
+/// Clock of machines that do not give one.
+pub const DEFAULT_CLOCK: u32 = 2_000_000;
+
+/// A block of the address space, with the image loaded into it if it holds ROM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryRegion
+{
+    pub start: u16,
+    pub size:  u32,
+    pub rom:   Option<PathBuf>,
+}
+
+/// A board on the I/O bus and the ports it decodes. Memory-mapped devices are out of scope,
+/// and so is write protection: ROM is loaded into memory the program can write.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceDefinition
+{
+    /// An input port reading `value`, with the bits of pressed inputs set, or cleared if
+    /// the inputs are active low.
+    Input { port: u8, value: u8, active_low: bool },
+    ShiftRegister { amount_port: u8, data_port: u8, result_port: u8 },
+    /// An 88-SIO or 88-2SIO, on the host terminal or on nothing at all.
+    Sio { port: u8, interrupt: u8, baud: u32, terminal: bool },
+    TwoSio { port: u8, interrupt: u8, baud: u32, terminal: bool },
+}
+
+/// An interrupt raised by the machine's timing rather than a device, such as the video
+/// interrupts of arcade boards: RST `rst` at cycle `at` and every `period` cycles after.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimedInterrupt
+{
+    pub rst:    u8,
+    pub at:     u64,
+    pub period: u64,
+}
+
+/// A bitmapped screen in main memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplayDefinition
+{
+    pub start:  u16,
+    pub width:  usize,
+    pub height: usize,
+    pub layout: VideoLayout,
+}
+
+/// A machine described in a definition file rather than in Rust. The file is TOML:
+///
+/// ```toml
+/// name  = "Space Invaders"
+/// cpu   = "8080"
+/// clock = 1996800
+/// frame-cycles = 33536
+///
+/// [[memory]]
+/// start = 0x0000
+/// size  = 0x2000
+/// rom   = "invaders.rom"
+///
+/// [[device]]
+/// type  = "input"
+/// port  = 1
+/// value = 0x08
+///
+/// [[interrupt]]
+/// rst    = 2
+/// at     = 28672
+/// period = 33536
+///
+/// [display]
+/// start  = 0x2400
+/// width  = 224
+/// height = 256
+/// layout = "columns"
+///
+/// [inputs]
+/// coin = "1:0x01"
+///
+/// [keys]
+/// c = "coin"
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct MachineDefinition
+{
+    pub name:         String,
+    pub clock:        u32,
+    pub frame_cycles: u64,
+    pub memory:       Vec<MemoryRegion>,
+    pub devices:      Vec<DeviceDefinition>,
+    pub interrupts:   Vec<TimedInterrupt>,
+    pub display:      Option<DisplayDefinition>,
+    pub overlay:      Option<ColorOverlay>,
+    pub inputs:       Vec<(String, InputBinding)>,
+    /// The key map in the format of key map files.
+    pub keymap:       String,
+}
+
+impl MachineDefinition
+{
+    /// Reads a definition file. ROM images and key map files are found relative to it.
+    pub fn load(path: &Path) -> Result<MachineDefinition, String>
+    {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        MachineDefinition::parse(&text, directory).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Reads a definition, with relative paths taken from `directory`.
+    pub fn parse(text: &str, directory: &Path) -> Result<MachineDefinition, String>
+    {
+        let root = toml::parse(text)?;
+
+        let cpu = root.get_string("cpu")?.unwrap_or("8080");
+        if !cpu.eq_ignore_ascii_case("8080") && !cpu.eq_ignore_ascii_case("i8080")
+        {
+            return Err(format!("cpu {} is not emulated, only the 8080 is", cpu));
+        }
+        let clock = root.get_ranged("clock", u32::MAX as i64)?.unwrap_or(DEFAULT_CLOCK);
+        if clock == 0
+        {
+            return Err("clock = 0 is out of range, the processor would never run".to_string());
+        }
+        let frame_cycles = match root.get_ranged("frame-cycles", u32::MAX as i64)?
+        {
+            Some(cycles) => cycles as u64,
+            None => (clock / 60).max(1) as u64,
+        };
+
+        let mut memory = Vec::new();
+        for (i, table) in root.get_tables("memory")?.iter().enumerate()
+        {
+            memory.push(parse_memory(table, directory).map_err(|e| format!("memory {}: {}", i + 1, e))?);
+        }
+
+        let mut devices = Vec::new();
+        for (i, table) in root.get_tables("device")?.iter().enumerate()
+        {
+            devices.push(parse_device(table).map_err(|e| format!("device {}: {}", i + 1, e))?);
+        }
+
+        let mut interrupts = Vec::new();
+        for (i, table) in root.get_tables("interrupt")?.iter().enumerate()
+        {
+            interrupts.push(parse_interrupt(table).map_err(|e| format!("interrupt {}: {}", i + 1, e))?);
+        }
+
+        let display = match root.get_table("display")?
+        {
+            Some(table) => Some(parse_display(table).map_err(|e| format!("display: {}", e))?),
+            None => None,
+        };
+
+        let mut bands = Vec::new();
+        for (i, table) in root.get_tables("overlay")?.iter().enumerate()
+        {
+            bands.push(parse_band(table, display).map_err(|e| format!("overlay {}: {}", i + 1, e))?);
+        }
+        let overlay = (!bands.is_empty()).then_some(ColorOverlay { bands });
+
+        let mut inputs = Vec::new();
+        if let Some(table) = root.get_table("inputs")?
+        {
+            for (name, value) in table.entries.iter()
+            {
+                let binding = match value
+                {
+                    Value::String(text) => parse_binding(text).map_err(|e| format!("inputs: {}", e))?,
+                    _ => return Err(format!("inputs: {} should be a port:mask string", name)),
+                };
+                inputs.push((name.clone(), binding));
+            }
+        }
+
+        let keymap = match (root.get_string("keymap")?, root.get_table("keys")?)
+        {
+            (Some(_), Some(_)) => return Err("give either a keymap file or [keys], not both".to_string()),
+            (Some(file), None) =>
+            {
+                let path = directory.join(file);
+                std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?
+            },
+            (None, Some(table)) => keys_to_keymap(table)?,
+            (None, None) => String::new(),
+        };
+
+        Ok(MachineDefinition
+        {
+            name: root.get_string("name")?.unwrap_or("8080 machine").to_string(),
+            clock,
+            frame_cycles,
+            memory,
+            devices,
+            interrupts,
+            display,
+            overlay,
+            inputs,
+            keymap,
+        })
+    }
+
+    /// True if a serial board is attached to the host terminal.
+    pub fn has_terminal(&self) -> bool
+    {
+        self.devices.iter().any(|device| match device
+        {
+            DeviceDefinition::Sio { terminal, .. } | DeviceDefinition::TwoSio { terminal, .. } => *terminal,
+            _ => false,
+        })
+    }
+
+    /// Loads the ROM images into the processor's memory and puts the devices together.
+    pub fn build(&self, processor: &mut Processor) -> Result<ConfiguredMachine, String>
+    {
+        for region in self.memory.iter()
+        {
+            if let Some(path) = &region.rom
+            {
+                let image = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                if image.len() > region.size as usize
+                {
+                    return Err(format!("{} is {} bytes, larger than its {} byte region", path.display(), image.len(), region.size));
+                }
+                processor.load_bytes_at(region.start, &image);
+            }
+        }
+
+        let mut machine = ConfiguredMachine
+        {
+            name:         self.name.clone(),
+            inputs:       Vec::new(),
+            devices:      Vec::new(),
+            interrupts:   self.interrupts.iter().map(|interrupt| (*interrupt, interrupt.at)).collect(),
+            display:      self.display,
+            overlay:      self.overlay.clone(),
+            named_inputs: self.inputs.clone(),
+            frame_cycles: self.frame_cycles,
+            frame_start:  0,
+            frames:       0,
+        };
+
+        for device in self.devices.iter()
+        {
+            match *device
+            {
+                DeviceDefinition::Input { port, value, active_low } =>
+                {
+                    machine.inputs.push(InputPort { port, value, active_low, pressed: 0 });
+                },
+                DeviceDefinition::ShiftRegister { amount_port, data_port, result_port } =>
+                {
+                    machine.devices.push(Box::new(ShiftRegister::new(amount_port, data_port, result_port)));
+                },
+                DeviceDefinition::Sio { port, interrupt, baud, terminal } =>
+                {
+                    machine.devices.push(match terminal
+                    {
+                        true  => sio(Sio::new(HostConsole::new(), port), interrupt, baud, self.clock),
+                        false => sio(Sio::new(NullConsole, port), interrupt, baud, self.clock),
+                    });
+                },
+                DeviceDefinition::TwoSio { port, interrupt, baud, terminal } =>
+                {
+                    machine.devices.push(match terminal
+                    {
+                        true  => two_sio(TwoSio::new(HostConsole::new(), NullConsole, port), interrupt, baud, self.clock),
+                        false => two_sio(TwoSio::new(NullConsole, NullConsole, port), interrupt, baud, self.clock),
+                    });
+                },
+            }
+        }
+        Ok(machine)
+    }
+}
+
+fn sio<C: Console + 'static>(mut board: Sio<C>, interrupt: u8, baud: u32, clock: u32) -> Box<dyn PortDevice>
+{
+    board.set_baud_rate(baud, clock);
+    board.set_interrupt_vector(interrupt);
+    Box::new(board)
+}
+
+fn two_sio<C: Console + 'static>(mut board: TwoSio<C, NullConsole>, interrupt: u8, baud: u32, clock: u32) -> Box<dyn PortDevice>
+{
+    board.set_baud_rate(baud, clock);
+    board.set_interrupt_vector(interrupt);
+    Box::new(board)
+}
+
+fn get_port(table: &Table, key: &str) -> Result<u8, String>
+{
+    match table.get_ranged(key, 0xFF)?
+    {
+        Some(port) => Ok(port as u8),
+        None => Err(format!("{} is missing", key)),
+    }
+}
+
+fn get_rst(table: &Table, key: &str) -> Result<Option<u8>, String>
+{
+    Ok(table.get_ranged(key, 7)?.map(|rst| rst as u8))
+}
+
+fn parse_memory(table: &Table, directory: &Path) -> Result<MemoryRegion, String>
+{
+    let start = table.get_ranged("start", 0xFFFF)?.ok_or("start is missing")?;
+    let size = table.get_ranged("size", 0x10000)?.ok_or("size is missing")?;
+    if start + size > 0x10000
+    {
+        return Err(format!("{:#06X} plus {:#X} bytes runs past the end of memory", start, size));
+    }
+    let rom = table.get_string("rom")?.map(|file| directory.join(file));
+    Ok(MemoryRegion { start: start as u16, size, rom })
+}
+
+fn parse_device(table: &Table) -> Result<DeviceDefinition, String>
+{
+    let kind = table.get_string("type")?.ok_or("type is missing")?;
+    let baud = table.get_ranged("baud", u32::MAX as i64)?.unwrap_or(DEFAULT_BAUD_RATE);
+    let interrupt = get_rst(table, "interrupt")?.unwrap_or(DEFAULT_INTERRUPT);
+    let terminal = match table.get_string("console")?.unwrap_or("terminal")
+    {
+        "terminal" => true,
+        "none"     => false,
+        other => return Err(format!("console {} is neither terminal nor none", other)),
+    };
+
+    let device = match kind
+    {
+        "input" => DeviceDefinition::Input
+        {
+            port:       get_port(table, "port")?,
+            value:      table.get_ranged("value", 0xFF)?.unwrap_or(0) as u8,
+            active_low: table.get_boolean("active-low")?.unwrap_or(false),
+        },
+        "shift-register" => DeviceDefinition::ShiftRegister
+        {
+            amount_port: get_port(table, "amount-port")?,
+            data_port:   get_port(table, "data-port")?,
+            result_port: get_port(table, "result-port")?,
+        },
+        "sio" => DeviceDefinition::Sio
+        {
+            port: table.get_ranged("port", 0xFF)?.map_or(SIO_PORT, |port| port as u8),
+            interrupt,
+            baud,
+            terminal,
+        },
+        "2sio" => DeviceDefinition::TwoSio
+        {
+            port: table.get_ranged("port", 0xFF)?.map_or(TWO_SIO_PORT, |port| port as u8),
+            interrupt,
+            baud,
+            terminal,
+        },
+        other => return Err(format!("unknown device type {}, expected input, shift-register, sio or 2sio", other)),
+    };
+    Ok(device)
+}
+
+fn parse_interrupt(table: &Table) -> Result<TimedInterrupt, String>
+{
+    Ok(TimedInterrupt
+    {
+        rst:    get_rst(table, "rst")?.ok_or("rst is missing")?,
+        at:     table.get_ranged("at", u32::MAX as i64)?.unwrap_or(0) as u64,
+        period: table.get_ranged("period", u32::MAX as i64)?.unwrap_or(0) as u64,
+    })
+}
+
+fn parse_display(table: &Table) -> Result<DisplayDefinition, String>
+{
+    let layout = match table.get_string("layout")?.unwrap_or("rows")
+    {
+        "rows"    => VideoLayout::Rows,
+        "columns" => VideoLayout::Columns,
+        other => return Err(format!("layout {} is neither rows nor columns", other)),
+    };
+    Ok(DisplayDefinition
+    {
+        start:  table.get_ranged("start", 0xFFFF)?.ok_or("start is missing")? as u16,
+        width:  table.get_ranged("width", 0x1000)?.ok_or("width is missing")? as usize,
+        height: table.get_ranged("height", 0x1000)?.ok_or("height is missing")? as usize,
+        layout,
+    })
+}
+
+/// An overlay band; left, right and bottom default to the edges of the screen.
+fn parse_band(table: &Table, display: Option<DisplayDefinition>) -> Result<Band, String>
+{
+    let display = display.ok_or("an overlay needs a [display]")?;
+    let edge = |key: &str, default: usize| -> Result<usize, String>
+    {
+        Ok(table.get_ranged(key, 0x1000)?.map_or(default, |value| value as usize))
+    };
+    let color = match table.get("color")
+    {
+        Some(Value::Array(items)) if items.len() == 3 =>
+        {
+            let mut color = [0u8; 3];
+            for (channel, item) in color.iter_mut().zip(items)
+            {
+                *channel = match item
+                {
+                    Value::Integer(value) if (0..=255).contains(value) => *value as u8,
+                    _ => return Err("color should be three numbers from 0 to 255".to_string()),
+                };
+            }
+            color
+        },
+        _ => return Err("color should be [red, green, blue]".to_string()),
+    };
+    Ok(Band
+    {
+        top:    edge("top", 0)?,
+        bottom: edge("bottom", display.height)?,
+        left:   edge("left", 0)?,
+        right:  edge("right", display.width)?,
+        color,
+    })
+}
+
+/// Turns a `[keys]` table back into key map lines, so it is read like a key map file.
+fn keys_to_keymap(table: &Table) -> Result<String, String>
+{
+    let mut keymap = String::new();
+    for (key, value) in table.entries.iter()
+    {
+        let value = match value
+        {
+            Value::String(text) => text.clone(),
+            Value::Integer(number) => number.to_string(),
+            _ => return Err(format!("keys: {} should be an input name", key)),
+        };
+        keymap.push_str(&format!("{} = {}\n", key, value));
+    }
+    Ok(keymap)
+}
+
+/// An input port of a configured machine.
+struct InputPort
+{
+    port:       u8,
+    value:      u8,
+    active_low: bool,
+    pressed:    u8,
+}
+
+impl InputPort
+{
+    fn read(&self) -> u8
+    {
+        if self.active_low { self.value & !self.pressed } else { self.value | self.pressed }
+    }
+}
+
+/// A machine put together from a definition file.
+pub struct ConfiguredMachine
+{
+    name:         String,
+    inputs:       Vec<InputPort>,
+    devices:      Vec<Box<dyn PortDevice>>,
+    interrupts:   Vec<(TimedInterrupt, u64)>,
+    display:      Option<DisplayDefinition>,
+    overlay:      Option<ColorOverlay>,
+    named_inputs: Vec<(String, InputBinding)>,
+    frame_cycles: u64,
+    frame_start:  u64,
+    frames:       u64,
+}
+
+impl ConfiguredMachine
+{
+    pub fn get_name(&self) -> &str
+    {
+        &self.name
+    }
+
+    /// Frames completed since the machine started. A frame is `frame-cycles` long.
+    pub fn get_frames(&self) -> u64
+    {
+        self.frames
+    }
+
+    /// Runs until the end of the current frame.
+    pub fn run_frame(&mut self, processor: &mut Processor)
+    {
+        let frame = self.frames;
+        while self.frames == frame
+        {
+            self.clock(processor);
+        }
+    }
+
+    /// True once a serial board has lost its terminal.
+    pub fn is_closed(&self) -> bool
+    {
+        self.devices.iter().any(|device| device.is_closed())
+    }
+
+    /// The byte an IN from `port` reads. Ports nothing decodes float high.
+    pub fn read_port(&mut self, port: u8) -> u8
+    {
+        match self.inputs.iter().find(|input| input.port == port)
+        {
+            Some(input) => input.read(),
+            None => self.devices.iter_mut().find_map(|device| device.peek(port)).unwrap_or(0xFF),
+        }
+    }
+}
+
+impl Machine for ConfiguredMachine
+{
+    fn clock(&mut self, processor: &mut Processor)
+    {
+        if let Some(port) = pending_input_port(processor)
+        {
+            let value = self.read_port(port);
+            processor.set_input_port(port, value);
+        }
+        processor.clock();
+
+        if let Some(port) = processor.take_input()
+        {
+            self.devices.iter_mut().for_each(|device| device.read(port));
+        }
+        if let Some((port, value)) = processor.take_output()
+        {
+            self.devices.iter_mut().any(|device| device.write(port, value));
+        }
+
+        let cycle = processor.get_cycles();
+        for device in self.devices.iter_mut()
+        {
+            device.update(cycle);
+            if let Some(rst) = device.interrupt()
+            {
+                processor.interrupt(rst);
+            }
+        }
+        for (interrupt, next) in self.interrupts.iter_mut()
+        {
+            if cycle >= *next
+            {
+                processor.interrupt(interrupt.rst);
+                *next = if interrupt.period > 0 { *next + interrupt.period } else { u64::MAX };
+            }
+        }
+        if cycle - self.frame_start >= self.frame_cycles
+        {
+            self.frame_start += self.frame_cycles;
+            self.frames += 1;
+        }
+    }
+
+    fn framebuffer(&self, processor: &Processor) -> Option<Framebuffer>
+    {
+        self.display.map(|display| Framebuffer::from_video_ram(processor, display.start, display.width, display.height, display.layout))
+    }
+
+    fn color_overlay(&self) -> Option<ColorOverlay>
+    {
+        self.overlay.clone()
+    }
+
+    fn set_input(&mut self, port: u8, mask: u8, pressed: bool)
+    {
+        for input in self.inputs.iter_mut().filter(|input| input.port == port)
+        {
+            if pressed { input.pressed |= mask } else { input.pressed &= !mask }
+        }
+    }
+
+    fn named_inputs(&self) -> Vec<(String, InputBinding)>
+    {
+        self.named_inputs.clone()
+    }
+}
@@ -0,0 +1,349 @@
+use std::path::Path;
+
+use crate::i8080::Processor;
+use crate::machines::{Framebuffer, Machine, PortDevice, VideoLayout};
+use crate::machines::keymap::InputBinding;
+use crate::machines::screenshot::{Band, ColorOverlay};
+use crate::machines::shift_register::ShiftRegister;
+use crate::machines::sound::{Sound, SoundBank, SoundEvent, SoundRecorder};
+
+/// The CPU runs from the 19.968 MHz video crystal divided by ten.
+pub const CLOCK_FREQUENCY: u32 = 1_996_800;
+
+/// A scan line takes 128 CPU cycles and a frame has 262 lines, 224 of them visible.
+pub const CYCLES_PER_LINE: u64 = 128;
+pub const LINES_PER_FRAME: u64 = 262;
+pub const CYCLES_PER_FRAME: u64 = CYCLES_PER_LINE * LINES_PER_FRAME;
+
+/// RST 1 is raised when the beam reaches line 96, RST 2 when it enters the vertical blank.
+pub const MID_SCREEN_CYCLE: u64 = 96 * CYCLES_PER_LINE;
+pub const VBLANK_CYCLE: u64 = 224 * CYCLES_PER_LINE;
+
+pub const ROM_SIZE: usize = 0x2000;
+pub const VRAM_START: u16 = 0x2400;
+pub const VRAM_END: u16 = 0x4000;
+
+/// The monitor is turned on its side: the screen is 224 pixels wide and 256 high.
+pub const SCREEN_WIDTH: usize = 224;
+pub const SCREEN_HEIGHT: usize = 256;
+
+/// Bits of input port 1.
+pub const COIN: u8 = 0x01;
+pub const P2_START: u8 = 0x02;
+pub const P1_START: u8 = 0x04;
+pub const P1_FIRE: u8 = 0x10;
+pub const P1_LEFT: u8 = 0x20;
+pub const P1_RIGHT: u8 = 0x40;
+
+/// Bits of input port 2, which it shares with the DIP switches.
+pub const TILT: u8 = 0x04;
+pub const P2_FIRE: u8 = 0x10;
+pub const P2_LEFT: u8 = 0x20;
+pub const P2_RIGHT: u8 = 0x40;
+
+const SHIFT_AMOUNT_PORT: u8 = 2;
+const SHIFT_RESULT_PORT: u8 = 3;
+const SHIFT_DATA_PORT: u8 = 4;
+const SOUND_PORT_1: u8 = 3;
+const SOUND_PORT_2: u8 = 5;
+
+/// Coin and start on the number keys, the first player on the arrow keys and space and the
+/// second player on a, d and w.
+pub const DEFAULT_KEYMAP: &str = "\
+c     = coin
+1     = p1-start
+2     = p2-start
+left  = p1-left
+right = p1-right
+space = p1-fire
+a     = p2-left
+d     = p2-right
+w     = p2-fire
+t     = tilt
+";
+
+/// The sound lines: port, bit, file in the usual sample set, and the frequency, length and
+/// looping of the tone played when there is no sample. The UFO repeats while its bit is on,
+/// the other sounds play once.
+const SOUNDS: [(u8, u8, &str, f32, f32, bool); 10] =
+[
+    (SOUND_PORT_1, 0, "0.wav", 1200.0, 0.1, true),  // UFO
+    (SOUND_PORT_1, 1, "1.wav", 1000.0, 0.2, false), // Shot
+    (SOUND_PORT_1, 2, "2.wav",  150.0, 1.0, false), // Player dies
+    (SOUND_PORT_1, 3, "3.wav",  400.0, 0.3, false), // Invader dies
+    (SOUND_PORT_1, 4, "9.wav", 2000.0, 0.5, false), // Extra ship
+    (SOUND_PORT_2, 0, "4.wav",  110.0, 0.1, false), // Fleet movement 1 to 4
+    (SOUND_PORT_2, 1, "5.wav",  100.0, 0.1, false),
+    (SOUND_PORT_2, 2, "6.wav",   90.0, 0.1, false),
+    (SOUND_PORT_2, 3, "7.wav",   80.0, 0.1, false),
+    (SOUND_PORT_2, 4, "8.wav",  600.0, 0.8, false), // UFO hit
+];
+
+/// Bits of ports 0 and 1 that are pulled high on the board.
+const PORT_0_FIXED: u8 = 0x0E;
+const PORT_1_FIXED: u8 = 0x08;
+
+/// The DIP switches on the board, read through input port 2.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DipSwitches
+{
+    /// Ships per game, 3 to 6.
+    pub ships:          u8,
+    /// The score that earns an extra ship, 1000 or 1500.
+    pub extra_ship_at:  u16,
+    /// Show the coin information in the attract mode.
+    pub show_coin_info: bool,
+}
+
+impl Default for DipSwitches
+{
+    fn default() -> DipSwitches
+    {
+        DipSwitches
+        {
+            ships:          3,
+            extra_ship_at:  1500,
+            show_coin_info: true,
+        }
+    }
+}
+
+impl DipSwitches
+{
+    fn port_bits(&self) -> u8
+    {
+        let mut bits = self.ships.clamp(3, 6) - 3;
+        if self.extra_ship_at == 1000
+        {
+            bits |= 0x08;
+        }
+        if !self.show_coin_info
+        {
+            bits |= 0x80;
+        }
+        bits
+    }
+}
+
+/// Reads the 8K of program ROM, either from one file or from a directory holding the
+/// invaders.h, invaders.g, invaders.f and invaders.e ROM dumps.
+pub fn load_rom(path: &Path) -> Result<Vec<u8>, String>
+{
+    let read = |path: &Path| std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+
+    let rom = if path.is_dir()
+    {
+        let mut rom = Vec::new();
+        for part in ["invaders.h", "invaders.g", "invaders.f", "invaders.e"].iter()
+        {
+            rom.extend(read(&path.join(part))?);
+        }
+        rom
+    }
+    else
+    {
+        read(path)?
+    };
+
+    if rom.len() > ROM_SIZE
+    {
+        return Err(format!("{} is larger than the {} bytes of ROM", path.display(), ROM_SIZE));
+    }
+    Ok(rom)
+}
+
+/// The sounds for rendering recorded sound events, from the numbered WAV files of the usual
+/// sample set in `samples` where there is one and synthesized tones otherwise.
+pub fn sound_bank(samples: Option<&Path>, sample_rate: u32) -> Result<SoundBank, String>
+{
+    let mut bank = SoundBank::new();
+    for (port, bit, file, frequency, seconds, looped) in SOUNDS.iter()
+    {
+        let sample = samples.map(|directory| directory.join(file)).filter(|path| path.is_file());
+        let sound = match sample
+        {
+            Some(path) =>
+            {
+                let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                Sound::from_wav(&bytes, sample_rate, *looped).map_err(|e| format!("{}: {}", path.display(), e))?
+            },
+            None => Sound::tone(*frequency, *seconds, sample_rate, *looped),
+        };
+        bank.insert(*port, *bit, sound);
+    }
+    Ok(bank)
+}
+
+/// Taito's Space Invaders board: the 8080 with 8K ROM, 1K RAM and 7K of video RAM, a barrel
+/// shifter for drawing sprites at any bit offset and two interrupts per frame from the video
+/// timing.
+pub struct Invaders
+{
+    pub dip_switches: DipSwitches,
+    inputs:           [u8; 3],
+    shift_register:   ShiftRegister,
+    sound:            SoundRecorder,
+    frame_start:      u64,
+    frames:           u64,
+    mid_screen_done:  bool,
+    vblank_done:      bool,
+}
+
+impl Invaders
+{
+    pub fn new(dip_switches: DipSwitches) -> Invaders
+    {
+        Invaders
+        {
+            dip_switches,
+            inputs:          [0; 3],
+            shift_register:  ShiftRegister::new(SHIFT_AMOUNT_PORT, SHIFT_DATA_PORT, SHIFT_RESULT_PORT),
+            sound:           SoundRecorder::new(),
+            frame_start:     0,
+            frames:          0,
+            mid_screen_done: false,
+            vblank_done:     false,
+        }
+    }
+
+    /// The value read from an input port.
+    pub fn read_port(&self, port: u8) -> u8
+    {
+        match port
+        {
+            0 => PORT_0_FIXED | self.inputs[0],
+            1 => PORT_1_FIXED | self.inputs[1],
+            2 => self.dip_switches.port_bits() | self.inputs[2],
+            SHIFT_RESULT_PORT => self.shift_register.get_result(),
+            _ => 0,
+        }
+    }
+
+    /// The last value written to sound port 3 or 5.
+    pub fn get_sound_port(&self, port: u8) -> u8
+    {
+        self.sound.get_port(port)
+    }
+
+    /// Hands over the sound bits that changed since the last call, timestamped in processor
+    /// cycles.
+    pub fn take_sound_events(&mut self) -> Vec<SoundEvent>
+    {
+        self.sound.take_events()
+    }
+
+    /// Frames completed since the machine started.
+    pub fn get_frames(&self) -> u64
+    {
+        self.frames
+    }
+
+    /// Runs until the end of the current frame.
+    pub fn run_frame(&mut self, processor: &mut Processor)
+    {
+        let frame = self.frames;
+        while self.frames == frame
+        {
+            self.clock(processor);
+        }
+    }
+
+    fn write_port(&mut self, port: u8, value: u8, cycle: u64)
+    {
+        match port
+        {
+            SHIFT_AMOUNT_PORT | SHIFT_DATA_PORT => { self.shift_register.write(port, value); },
+            SOUND_PORT_1 | SOUND_PORT_2 => self.sound.record(cycle, port, value),
+            // Port 6 kicks the watchdog, which never bites here
+            _ => (),
+        }
+    }
+
+    /// Raises the interrupts the video timing has reached and starts a new frame at the end
+    /// of the current one.
+    fn update_video_timing(&mut self, processor: &mut Processor)
+    {
+        let position = processor.get_cycles() - self.frame_start;
+        if !self.mid_screen_done && position >= MID_SCREEN_CYCLE
+        {
+            self.mid_screen_done = true;
+            processor.interrupt(1);
+        }
+        if !self.vblank_done && position >= VBLANK_CYCLE
+        {
+            self.vblank_done = true;
+            processor.interrupt(2);
+        }
+        if position >= CYCLES_PER_FRAME
+        {
+            self.frame_start += CYCLES_PER_FRAME;
+            self.frames += 1;
+            self.mid_screen_done = false;
+            self.vblank_done = false;
+        }
+    }
+}
+
+impl Machine for Invaders
+{
+    fn clock(&mut self, processor: &mut Processor)
+    {
+        for port in 0..=SHIFT_RESULT_PORT
+        {
+            processor.set_input_port(port, self.read_port(port));
+        }
+        processor.clock();
+
+        if let Some((port, value)) = processor.take_output()
+        {
+            self.write_port(port, value, processor.get_cycles());
+        }
+        self.update_video_timing(processor);
+    }
+
+    fn framebuffer(&self, processor: &Processor) -> Option<Framebuffer>
+    {
+        Some(Framebuffer::from_video_ram(processor, VRAM_START, SCREEN_WIDTH, SCREEN_HEIGHT, VideoLayout::Columns))
+    }
+
+    /// A red strip where the UFO flies and green over the shields, the player and the ships
+    /// left at the bottom.
+    fn color_overlay(&self) -> Option<ColorOverlay>
+    {
+        let band = |top, bottom, left, right, color| Band { top, bottom, left, right, color };
+        Some(ColorOverlay
+        {
+            bands: vec![
+                band(32, 64, 0, SCREEN_WIDTH, [0xFF, 0x20, 0x20]),
+                band(184, 240, 0, SCREEN_WIDTH, [0x20, 0xFF, 0x20]),
+                band(240, SCREEN_HEIGHT, 16, 134, [0x20, 0xFF, 0x20]),
+            ],
+        })
+    }
+
+    fn set_input(&mut self, port: u8, mask: u8, pressed: bool)
+    {
+        if let Some(bits) = self.inputs.get_mut(port as usize)
+        {
+            if pressed { *bits |= mask } else { *bits &= !mask }
+        }
+    }
+
+    fn named_inputs(&self) -> Vec<(String, InputBinding)>
+    {
+        let input = |port, mask| InputBinding { port, mask };
+        let inputs = [
+            ("coin",     input(1, COIN)),
+            ("p1-start", input(1, P1_START)),
+            ("p2-start", input(1, P2_START)),
+            ("p1-left",  input(1, P1_LEFT)),
+            ("p1-right", input(1, P1_RIGHT)),
+            ("p1-fire",  input(1, P1_FIRE)),
+            ("p2-left",  input(2, P2_LEFT)),
+            ("p2-right", input(2, P2_RIGHT)),
+            ("p2-fire",  input(2, P2_FIRE)),
+            ("tilt",     input(2, TILT)),
+        ];
+        inputs.iter().map(|(name, binding)| (name.to_string(), *binding)).collect()
+    }
+}
@@ -0,0 +1,194 @@
+use std::time::{Duration, Instant};
+use termion::event::Key;
+use crate::machines::Machine;
+
+/// Terminals only report key presses, and repeats while a key is held down, so a press keeps
+/// the input down for this long after the last report.
+pub const DEFAULT_HOLD: Duration = Duration::from_millis(200);
+
+/// Bits on an input port that a key drives.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputBinding
+{
+    pub port: u8,
+    pub mask: u8,
+}
+
+/// Maps host keys to machine inputs. Key maps are written one binding per line as
+/// `key = input`, where the input is one of the machine's named inputs or a port and mask
+/// such as `1:0x10`. A `hold = <ms>` line sets the hold time.
+#[derive(Clone, Debug)]
+pub struct KeyMap
+{
+    bindings: Vec<(Key, InputBinding)>,
+    hold:     Duration,
+    held:     Vec<(InputBinding, Instant)>,
+}
+
+impl KeyMap
+{
+    pub fn new(hold: Duration) -> KeyMap
+    {
+        KeyMap
+        {
+            bindings: Vec::new(),
+            hold,
+            held:     Vec::new(),
+        }
+    }
+
+    /// Reads a key map, looking input names up among `inputs`.
+    pub fn parse<S: AsRef<str>>(text: &str, inputs: &[(S, InputBinding)]) -> Result<KeyMap, String>
+    {
+        let mut keymap = KeyMap::new(DEFAULT_HOLD);
+
+        for (number, line) in text.lines().enumerate()
+        {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty()
+            {
+                continue;
+            }
+            let error = |message: String| format!("key map line {}: {}", number + 1, message);
+
+            let (key, input) = match line.split_once('=')
+            {
+                Some((key, input)) => (key.trim(), input.trim()),
+                None => return Err(error(format!("expected key = input, got {}", line))),
+            };
+
+            if key == "hold"
+            {
+                let milliseconds = input.parse::<u64>().map_err(|_| error(format!("invalid hold time {}", input)))?;
+                keymap.hold = Duration::from_millis(milliseconds);
+                continue;
+            }
+
+            let key = parse_key(key).map_err(error)?;
+            let binding = match inputs.iter().find(|(name, _)| name.as_ref().eq_ignore_ascii_case(input))
+            {
+                Some((_, binding)) => *binding,
+                None => parse_binding(input).map_err(error)?,
+            };
+            keymap.bind(key, binding);
+        }
+        Ok(keymap)
+    }
+
+    pub fn bind(&mut self, key: Key, binding: InputBinding)
+    {
+        self.bindings.retain(|(bound, _)| *bound != key);
+        self.bindings.push((key, binding));
+    }
+
+    pub fn get_hold(&self) -> Duration
+    {
+        self.hold
+    }
+
+    pub fn set_hold(&mut self, hold: Duration)
+    {
+        self.hold = hold;
+    }
+
+    /// Presses the input bound to `key` until the hold time has passed without another press.
+    /// Returns false if the key is not bound.
+    pub fn press(&mut self, key: Key, machine: &mut dyn Machine, now: Instant) -> bool
+    {
+        let binding = match self.bindings.iter().find(|(bound, _)| *bound == key)
+        {
+            Some((_, binding)) => *binding,
+            None => return false,
+        };
+
+        let release_at = now + self.hold;
+        match self.held.iter_mut().find(|(held, _)| *held == binding)
+        {
+            Some((_, deadline)) => *deadline = release_at,
+            None => self.held.push((binding, release_at)),
+        }
+        machine.set_input(binding.port, binding.mask, true);
+        true
+    }
+
+    /// Releases the inputs whose hold time has run out.
+    pub fn update(&mut self, machine: &mut dyn Machine, now: Instant)
+    {
+        self.held.retain(|(binding, release_at)|
+        {
+            if now < *release_at
+            {
+                return true;
+            }
+            machine.set_input(binding.port, binding.mask, false);
+            false
+        });
+    }
+}
+
+/// Parses a key name: a single character, a name such as `space`, `left` or `enter`, a
+/// function key such as `f1` or a control key such as `ctrl-a`.
+pub fn parse_key(name: &str) -> Result<Key, String>
+{
+    let lower = name.to_ascii_lowercase();
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next())
+    {
+        return Ok(Key::Char(c));
+    }
+
+    let key = match lower.as_str()
+    {
+        "space"     => Key::Char(' '),
+        "enter"     => Key::Char('\n'),
+        "tab"       => Key::Char('\t'),
+        "left"      => Key::Left,
+        "right"     => Key::Right,
+        "up"        => Key::Up,
+        "down"      => Key::Down,
+        "backspace" => Key::Backspace,
+        "insert"    => Key::Insert,
+        "delete"    => Key::Delete,
+        "home"      => Key::Home,
+        "end"       => Key::End,
+        "pageup"    => Key::PageUp,
+        "pagedown"  => Key::PageDown,
+        _ =>
+        {
+            if let Some(number) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok())
+            {
+                Key::F(number)
+            }
+            else if let Some(c) = lower.strip_prefix("ctrl-").filter(|c| c.chars().count() == 1)
+            {
+                Key::Ctrl(c.chars().next().expect("Checked above"))
+            }
+            else
+            {
+                return Err(format!("unknown key {}", name));
+            }
+        },
+    };
+    Ok(key)
+}
+
+/// Parses a `port:mask` binding, both in decimal or 0x prefixed hex.
+pub fn parse_binding(text: &str) -> Result<InputBinding, String>
+{
+    let number = |value: &str| -> Result<u8, String>
+    {
+        let value = value.trim();
+        let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X"))
+        {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None      => value.parse::<u8>(),
+        };
+        parsed.map_err(|_| format!("unknown input {}", text))
+    };
+
+    match text.split_once(':')
+    {
+        Some((port, mask)) => Ok(InputBinding { port: number(port)?, mask: number(mask)? }),
+        None => Err(format!("unknown input {}", text)),
+    }
+}
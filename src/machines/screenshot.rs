@@ -0,0 +1,161 @@
+use std::io::Write;
+use std::path::Path;
+use crate::machines::Framebuffer;
+
+pub type Color = [u8; 3];
+
+pub const WHITE: Color = [0xFF, 0xFF, 0xFF];
+pub const BLACK: Color = [0x00, 0x00, 0x00];
+
+/// A rectangle of the screen, from `top` and `left` up to but not including `bottom` and `right`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Band
+{
+    pub top:    usize,
+    pub bottom: usize,
+    pub left:   usize,
+    pub right:  usize,
+    pub color:  Color,
+}
+
+/// Coloured cellophane stuck on a black and white monitor, as on many early arcade games.
+/// Pixels outside every band stay white.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColorOverlay
+{
+    pub bands: Vec<Band>,
+}
+
+impl ColorOverlay
+{
+    pub fn color_at(&self, x: usize, y: usize) -> Color
+    {
+        self.bands.iter()
+            .find(|band| y >= band.top && y < band.bottom && x >= band.left && x < band.right)
+            .map_or(WHITE, |band| band.color)
+    }
+}
+
+/// The framebuffer as rows of RGB pixels, lit pixels white or coloured by the overlay.
+pub fn to_rgb(framebuffer: &Framebuffer, overlay: Option<&ColorOverlay>) -> Vec<u8>
+{
+    let mut rgb = Vec::with_capacity(framebuffer.width * framebuffer.height * 3);
+    for y in 0..framebuffer.height
+    {
+        for x in 0..framebuffer.width
+        {
+            let color = match (framebuffer.get(x, y), overlay)
+            {
+                (false, _)            => BLACK,
+                (true, Some(overlay)) => overlay.color_at(x, y),
+                (true, None)          => WHITE,
+            };
+            rgb.extend_from_slice(&color);
+        }
+    }
+    rgb
+}
+
+/// Writes a binary PPM (P6) image.
+pub fn write_ppm<W: Write>(writer: &mut W, framebuffer: &Framebuffer, overlay: Option<&ColorOverlay>) -> std::io::Result<()>
+{
+    write!(writer, "P6\n{} {}\n255\n", framebuffer.width, framebuffer.height)?;
+    writer.write_all(&to_rgb(framebuffer, overlay))
+}
+
+/// Writes an RGB PNG image. The image data is stored without compression, which keeps the
+/// encoder small and is plenty for regression test screenshots.
+pub fn write_png<W: Write>(writer: &mut W, framebuffer: &Framebuffer, overlay: Option<&ColorOverlay>) -> std::io::Result<()>
+{
+    let rgb = to_rgb(framebuffer, overlay);
+    let row_size = framebuffer.width * 3;
+
+    // Every row starts with filter type 0, none
+    let mut scanlines = Vec::with_capacity(rgb.len() + framebuffer.height);
+    for row in rgb.chunks(row_size.max(1))
+    {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(framebuffer.width as u32).to_be_bytes());
+    header.extend_from_slice(&(framebuffer.height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    writer.write_all(b"\x89PNG\r\n\x1A\n")?;
+    write_chunk(writer, b"IHDR", &header)?;
+    write_chunk(writer, b"IDAT", &zlib_stored(&scanlines))?;
+    write_chunk(writer, b"IEND", &[])
+}
+
+/// Writes a PNG or PPM file depending on the extension of `path`.
+pub fn save(path: &Path, framebuffer: &Framebuffer, overlay: Option<&ColorOverlay>) -> Result<(), String>
+{
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    let write: fn(&mut std::fs::File, &Framebuffer, Option<&ColorOverlay>) -> std::io::Result<()> = match extension.as_str()
+    {
+        "png" => write_png,
+        "ppm" => write_ppm,
+        _ => return Err(format!("{}: screenshots are .png or .ppm files", path.display())),
+    };
+    let mut file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    write(&mut file, framebuffer, overlay).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()>
+{
+    let mut crc_data = kind.to_vec();
+    crc_data.extend_from_slice(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(&crc_data)?;
+    writer.write_all(&crc32(&crc_data).to_be_bytes())
+}
+
+/// A zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8>
+{
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none()
+    {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next()
+    {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+pub fn crc32(data: &[u8]) -> u32
+{
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data
+    {
+        crc ^= *byte as u32;
+        for _ in 0..8
+        {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32
+{
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data
+    {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
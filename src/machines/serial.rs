@@ -0,0 +1,386 @@
+use crate::console::Console;
+use crate::machines::PortDevice;
+
+/// Characters are sent at this many bits per second unless set otherwise.
+pub const DEFAULT_BAUD_RATE: u32 = 9600;
+
+/// Ports of the 88-SIO and of the first channel of the 88-2SIO as shipped.
+pub const SIO_PORT: u8 = 0x00;
+pub const TWO_SIO_PORT: u8 = 0x10;
+
+/// Without a vectored interrupt board the Altair's data bus floats high while an interrupt
+/// is acknowledged, which the 8080 executes as RST 7.
+pub const DEFAULT_INTERRUPT: u8 = 7;
+
+/// Start bit, eight data bits and a stop bit.
+const BITS_PER_CHARACTER: u64 = 10;
+
+/// Processor cycles it takes to send one character at `baud_rate`.
+pub fn character_cycles(baud_rate: u32, clock_frequency: u32) -> u64
+{
+    clock_frequency as u64 * BITS_PER_CHARACTER / baud_rate.max(1) as u64
+}
+
+/// One serial line to a console: a one character receive buffer and a transmitter that is
+/// busy for a character time after every character sent.
+struct Line<C: Console>
+{
+    console:          C,
+    received:         Option<u8>,
+    data:             u8,
+    cycle:            u64,
+    transmit_done_at: u64,
+    character_cycles: u64,
+    closed:           bool,
+}
+
+impl<C: Console> Line<C>
+{
+    fn new(console: C) -> Line<C>
+    {
+        Line
+        {
+            console,
+            received:         None,
+            data:             0,
+            cycle:            0,
+            transmit_done_at: 0,
+            character_cycles: character_cycles(DEFAULT_BAUD_RATE, super::altair::CLOCK_FREQUENCY),
+            closed:           false,
+        }
+    }
+
+    fn update(&mut self, cycle: u64)
+    {
+        self.cycle = cycle;
+        if self.received.is_none() && !self.closed && self.console.status()
+        {
+            match self.console.read()
+            {
+                Some(c) => self.received = Some(c),
+                None    => self.closed = true,
+            }
+        }
+    }
+
+    fn receive_ready(&self) -> bool
+    {
+        self.received.is_some()
+    }
+
+    fn transmit_ready(&self) -> bool
+    {
+        self.cycle >= self.transmit_done_at
+    }
+
+    /// The receive data register, which keeps the last character once it has been read.
+    fn data(&self) -> u8
+    {
+        self.received.unwrap_or(self.data)
+    }
+
+    fn take(&mut self)
+    {
+        if let Some(c) = self.received.take()
+        {
+            self.data = c;
+        }
+    }
+
+    fn transmit(&mut self, c: u8)
+    {
+        self.console.write(c);
+        self.transmit_done_at = self.cycle + self.character_cycles;
+    }
+}
+
+/// The MITS 88-SIO serial board. The status port has active low flags, bit 0 clear when a
+/// character has been received and bit 7 clear when the transmitter can take one. Writing
+/// the status port sets the interrupt enables, bit 0 for input and bit 1 for output.
+pub struct Sio<C: Console>
+{
+    line:             Line<C>,
+    port:             u8,
+    input_interrupt:  bool,
+    output_interrupt: bool,
+    rst:              u8,
+}
+
+impl<C: Console> Sio<C>
+{
+    /// A board with its status port at `port` and data port at `port + 1`.
+    pub fn new(console: C, port: u8) -> Sio<C>
+    {
+        Sio
+        {
+            line:             Line::new(console),
+            port,
+            input_interrupt:  false,
+            output_interrupt: false,
+            rst:              DEFAULT_INTERRUPT,
+        }
+    }
+
+    pub fn set_baud_rate(&mut self, baud_rate: u32, clock_frequency: u32)
+    {
+        self.line.character_cycles = character_cycles(baud_rate, clock_frequency);
+    }
+
+    pub fn set_interrupt_vector(&mut self, rst: u8)
+    {
+        self.rst = rst;
+    }
+
+    pub fn console(&self) -> &C
+    {
+        &self.line.console
+    }
+
+    pub fn get_status(&self) -> u8
+    {
+        let receive = if self.line.receive_ready() { 0x00 } else { 0x01 };
+        let transmit = if self.line.transmit_ready() { 0x00 } else { 0x80 };
+        receive | transmit
+    }
+}
+
+impl<C: Console> PortDevice for Sio<C>
+{
+    fn peek(&mut self, port: u8) -> Option<u8>
+    {
+        match port.wrapping_sub(self.port)
+        {
+            0 => Some(self.get_status()),
+            1 => Some(self.line.data()),
+            _ => None,
+        }
+    }
+
+    fn read(&mut self, port: u8)
+    {
+        if port == self.port.wrapping_add(1)
+        {
+            self.line.take();
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) -> bool
+    {
+        match port.wrapping_sub(self.port)
+        {
+            0 =>
+            {
+                self.input_interrupt = value & 0x01 != 0;
+                self.output_interrupt = value & 0x02 != 0;
+            },
+            1 => self.line.transmit(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn update(&mut self, cycle: u64)
+    {
+        self.line.update(cycle);
+    }
+
+    fn interrupt(&mut self) -> Option<u8>
+    {
+        let requested = (self.input_interrupt && self.line.receive_ready())
+            || (self.output_interrupt && self.line.transmit_ready());
+        requested.then_some(self.rst)
+    }
+
+    fn is_closed(&self) -> bool
+    {
+        self.line.closed
+    }
+}
+
+/// ACIA status register bits.
+pub const RECEIVE_FULL: u8 = 0x01;
+pub const TRANSMIT_EMPTY: u8 = 0x02;
+pub const INTERRUPT_REQUEST: u8 = 0x80;
+
+/// One channel of the 88-2SIO, a Motorola 6850 ACIA with its control and status register at
+/// `port` and its data register at `port + 1`. Control bits 0 and 1 both set is a master
+/// reset, bits 5 and 6 set to 01 enable the transmit interrupt and bit 7 the receive interrupt.
+pub struct Acia<C: Console>
+{
+    line:    Line<C>,
+    port:    u8,
+    control: u8,
+}
+
+impl<C: Console> Acia<C>
+{
+    pub fn new(console: C, port: u8) -> Acia<C>
+    {
+        Acia
+        {
+            line:    Line::new(console),
+            port,
+            control: 0x03,
+        }
+    }
+
+    pub fn console(&self) -> &C
+    {
+        &self.line.console
+    }
+
+    fn receive_interrupt(&self) -> bool
+    {
+        self.control & 0x80 != 0 && self.line.receive_ready()
+    }
+
+    fn transmit_interrupt(&self) -> bool
+    {
+        self.control & 0x60 == 0x20 && self.line.transmit_ready()
+    }
+
+    /// The status register. Carrier detect and clear to send read as asserted.
+    pub fn get_status(&self) -> u8
+    {
+        let mut status = 0;
+        if self.line.receive_ready()
+        {
+            status |= RECEIVE_FULL;
+        }
+        if self.line.transmit_ready()
+        {
+            status |= TRANSMIT_EMPTY;
+        }
+        if self.receive_interrupt() || self.transmit_interrupt()
+        {
+            status |= INTERRUPT_REQUEST;
+        }
+        status
+    }
+}
+
+impl<C: Console> PortDevice for Acia<C>
+{
+    fn peek(&mut self, port: u8) -> Option<u8>
+    {
+        match port.wrapping_sub(self.port)
+        {
+            0 => Some(self.get_status()),
+            1 => Some(self.line.data()),
+            _ => None,
+        }
+    }
+
+    fn read(&mut self, port: u8)
+    {
+        if port == self.port.wrapping_add(1)
+        {
+            self.line.take();
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) -> bool
+    {
+        match port.wrapping_sub(self.port)
+        {
+            0 => self.control = value,
+            1 => self.line.transmit(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn update(&mut self, cycle: u64)
+    {
+        self.line.update(cycle);
+    }
+
+    fn interrupt(&mut self) -> Option<u8>
+    {
+        (self.receive_interrupt() || self.transmit_interrupt()).then_some(DEFAULT_INTERRUPT)
+    }
+
+    fn is_closed(&self) -> bool
+    {
+        self.line.closed
+    }
+}
+
+/// The MITS 88-2SIO: two ACIA channels on consecutive port pairs, sharing one interrupt line.
+pub struct TwoSio<A: Console, B: Console>
+{
+    channel_a: Acia<A>,
+    channel_b: Acia<B>,
+    rst:       u8,
+}
+
+impl<A: Console, B: Console> TwoSio<A, B>
+{
+    /// A board with channel A at `port` and `port + 1` and channel B at `port + 2` and `port + 3`.
+    pub fn new(console_a: A, console_b: B, port: u8) -> TwoSio<A, B>
+    {
+        TwoSio
+        {
+            channel_a: Acia::new(console_a, port),
+            channel_b: Acia::new(console_b, port.wrapping_add(2)),
+            rst:       DEFAULT_INTERRUPT,
+        }
+    }
+
+    pub fn set_baud_rate(&mut self, baud_rate: u32, clock_frequency: u32)
+    {
+        self.channel_a.line.character_cycles = character_cycles(baud_rate, clock_frequency);
+        self.channel_b.line.character_cycles = character_cycles(baud_rate, clock_frequency);
+    }
+
+    pub fn set_interrupt_vector(&mut self, rst: u8)
+    {
+        self.rst = rst;
+    }
+
+    pub fn channel_a(&self) -> &Acia<A>
+    {
+        &self.channel_a
+    }
+
+    pub fn channel_b(&self) -> &Acia<B>
+    {
+        &self.channel_b
+    }
+}
+
+impl<A: Console, B: Console> PortDevice for TwoSio<A, B>
+{
+    fn peek(&mut self, port: u8) -> Option<u8>
+    {
+        self.channel_a.peek(port).or_else(|| self.channel_b.peek(port))
+    }
+
+    fn read(&mut self, port: u8)
+    {
+        self.channel_a.read(port);
+        self.channel_b.read(port);
+    }
+
+    fn write(&mut self, port: u8, value: u8) -> bool
+    {
+        self.channel_a.write(port, value) || self.channel_b.write(port, value)
+    }
+
+    fn update(&mut self, cycle: u64)
+    {
+        self.channel_a.update(cycle);
+        self.channel_b.update(cycle);
+    }
+
+    fn interrupt(&mut self) -> Option<u8>
+    {
+        let requested = self.channel_a.interrupt().is_some() || self.channel_b.interrupt().is_some();
+        requested.then_some(self.rst)
+    }
+
+    fn is_closed(&self) -> bool
+    {
+        self.channel_a.is_closed()
+    }
+}
@@ -0,0 +1,59 @@
+use crate::machines::PortDevice;
+
+/// The 16 bit barrel shifter of Midway's 8080 boards, which lets the CPU draw sprites at any
+/// bit offset. Bytes written to the data port shift in from the top, the amount port picks
+/// which 8 bits the result port reads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShiftRegister
+{
+    amount_port: u8,
+    data_port:   u8,
+    result_port: u8,
+    value:       u16,
+    amount:      u8,
+}
+
+impl ShiftRegister
+{
+    pub fn new(amount_port: u8, data_port: u8, result_port: u8) -> ShiftRegister
+    {
+        ShiftRegister
+        {
+            amount_port,
+            data_port,
+            result_port,
+            value:  0,
+            amount: 0,
+        }
+    }
+
+    pub fn get_result(&self) -> u8
+    {
+        (self.value >> (8 - self.amount)) as u8
+    }
+}
+
+impl PortDevice for ShiftRegister
+{
+    fn peek(&mut self, port: u8) -> Option<u8>
+    {
+        (port == self.result_port).then(|| self.get_result())
+    }
+
+    fn write(&mut self, port: u8, value: u8) -> bool
+    {
+        if port == self.amount_port
+        {
+            self.amount = value & 0x07;
+        }
+        else if port == self.data_port
+        {
+            self.value = ((value as u16) << 8) | (self.value >> 8);
+        }
+        else
+        {
+            return false;
+        }
+        true
+    }
+}
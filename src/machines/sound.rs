@@ -0,0 +1,269 @@
+use std::io::Write;
+
+/// Sample rate of rendered sound, in Hz.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Amplitude of synthesized tones, loud enough to hear and leaving room to mix several.
+const TONE_AMPLITUDE: i16 = 6000;
+
+/// A sound line on an output port turning on or off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundEvent
+{
+    /// Processor cycle at which the OUT instruction finished.
+    pub cycle: u64,
+    pub port:  u8,
+    pub bit:   u8,
+    pub on:    bool,
+}
+
+/// Watches the ports that drive sound circuits and records every bit that changes.
+#[derive(Clone, Debug)]
+pub struct SoundRecorder
+{
+    ports:  [u8; 256],
+    events: Vec<SoundEvent>,
+}
+
+impl SoundRecorder
+{
+    pub fn new() -> SoundRecorder
+    {
+        SoundRecorder
+        {
+            ports:  [0; 256],
+            events: Vec::new(),
+        }
+    }
+
+    /// Records a write of `value` to `port` at processor cycle `cycle`.
+    pub fn record(&mut self, cycle: u64, port: u8, value: u8)
+    {
+        let changed = self.ports[port as usize] ^ value;
+        for bit in (0..8).filter(|bit| changed & (1 << bit) != 0)
+        {
+            self.events.push(SoundEvent { cycle, port, bit, on: value & (1 << bit) != 0 });
+        }
+        self.ports[port as usize] = value;
+    }
+
+    /// The last value written to `port`.
+    pub fn get_port(&self, port: u8) -> u8
+    {
+        self.ports[port as usize]
+    }
+
+    pub fn events(&self) -> &[SoundEvent]
+    {
+        &self.events
+    }
+
+    /// Hands over the events recorded so far.
+    pub fn take_events(&mut self) -> Vec<SoundEvent>
+    {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl Default for SoundRecorder
+{
+    fn default() -> SoundRecorder
+    {
+        SoundRecorder::new()
+    }
+}
+
+/// A sound played while a port bit is on. One shot sounds start when the bit turns on and
+/// play to the end, looped sounds repeat until the bit turns off.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sound
+{
+    pub samples: Vec<i16>,
+    pub looped:  bool,
+}
+
+impl Sound
+{
+    /// A square wave of `frequency` Hz lasting `seconds`.
+    pub fn tone(frequency: f32, seconds: f32, sample_rate: u32, looped: bool) -> Sound
+    {
+        let length = (seconds * sample_rate as f32) as usize;
+        let half_period = (sample_rate as f32 / frequency / 2.0).max(1.0);
+        let samples = (0..length)
+            .map(|i| if ((i as f32 / half_period) as usize).is_multiple_of(2) { TONE_AMPLITUDE } else { -TONE_AMPLITUDE })
+            .collect();
+        Sound { samples, looped }
+    }
+
+    /// Reads a PCM WAV file, mixing it down to mono and resampling it to `sample_rate`.
+    pub fn from_wav(bytes: &[u8], sample_rate: u32, looped: bool) -> Result<Sound, String>
+    {
+        let wav = parse_wav(bytes)?;
+        let step = wav.sample_rate as f64 / sample_rate as f64;
+        let length = (wav.samples.len() as f64 / step) as usize;
+        let samples = (0..length)
+            .map(|i| wav.samples[((i as f64 * step) as usize).min(wav.samples.len() - 1)])
+            .collect();
+        Ok(Sound { samples, looped })
+    }
+}
+
+/// The sound of each port bit.
+#[derive(Clone, Debug, Default)]
+pub struct SoundBank
+{
+    sounds: Vec<((u8, u8), Sound)>,
+}
+
+impl SoundBank
+{
+    pub fn new() -> SoundBank
+    {
+        SoundBank { sounds: Vec::new() }
+    }
+
+    pub fn insert(&mut self, port: u8, bit: u8, sound: Sound)
+    {
+        self.sounds.retain(|(line, _)| *line != (port, bit));
+        self.sounds.push(((port, bit), sound));
+    }
+
+    pub fn get(&self, port: u8, bit: u8) -> Option<&Sound>
+    {
+        self.sounds.iter().find(|(line, _)| *line == (port, bit)).map(|(_, sound)| sound)
+    }
+}
+
+/// Mixes the sounds the events start and stop into mono samples. The result covers
+/// `end_cycle` processor cycles at `clock_frequency`, plus the tail of any one shot sound
+/// still playing at the end.
+pub fn render(events: &[SoundEvent], bank: &SoundBank, clock_frequency: u32, end_cycle: u64, sample_rate: u32) -> Vec<i16>
+{
+    let to_sample = |cycle: u64| (cycle as u128 * sample_rate as u128 / clock_frequency.max(1) as u128) as usize;
+    let end = to_sample(end_cycle);
+    let mut mix: Vec<i32> = vec![0; end];
+
+    for (i, event) in events.iter().enumerate().filter(|(_, event)| event.on)
+    {
+        let sound = match bank.get(event.port, event.bit)
+        {
+            Some(sound) if !sound.samples.is_empty() => sound,
+            _ => continue,
+        };
+
+        let start = to_sample(event.cycle);
+        let stop = if sound.looped
+        {
+            events[i + 1..].iter()
+                .find(|off| off.port == event.port && off.bit == event.bit)
+                .map_or(end, |off| to_sample(off.cycle))
+        }
+        else
+        {
+            start + sound.samples.len()
+        }.max(start);
+
+        if mix.len() < stop
+        {
+            mix.resize(stop, 0);
+        }
+        for (position, sample) in mix[start..stop].iter_mut().enumerate()
+        {
+            *sample += sound.samples[position % sound.samples.len()] as i32;
+        }
+    }
+
+    mix.into_iter().map(|sample| sample.clamp(i16::MIN as i32, i16::MAX as i32) as i16).collect()
+}
+
+/// Writes mono 16 bit PCM samples as a WAV file.
+pub fn write_wav<W: Write>(writer: &mut W, samples: &[i16], sample_rate: u32) -> std::io::Result<()>
+{
+    let data_size = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples
+    {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    writer.write_all(&bytes)
+}
+
+/// Mono samples and sample rate read from a WAV file.
+pub struct Wav
+{
+    pub sample_rate: u32,
+    pub samples:     Vec<i16>,
+}
+
+/// Reads an 8 or 16 bit PCM WAV file, averaging the channels into one.
+pub fn parse_wav(bytes: &[u8]) -> Result<Wav, String>
+{
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE"
+    {
+        return Err("Not a WAV file".to_string());
+    }
+
+    let word = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    let long = |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut position = 12;
+    while position + 8 <= bytes.len()
+    {
+        let size = long(position + 4) as usize;
+        let body = position + 8;
+        let body_end = (body + size).min(bytes.len());
+
+        match &bytes[position..position + 4]
+        {
+            b"fmt " if size >= 16 && body + 16 <= bytes.len() =>
+            {
+                format = Some((word(body), word(body + 2), long(body + 4), word(body + 14)));
+            },
+            b"data" =>
+            {
+                let (encoding, channels, sample_rate, bits) = format.ok_or("WAV data comes before its format")?;
+                if encoding != 1 || channels == 0 || (bits != 8 && bits != 16)
+                {
+                    return Err(format!("Only 8 and 16 bit PCM WAV files are supported, not format {} with {} bits", encoding, bits));
+                }
+                if sample_rate == 0
+                {
+                    return Err("WAV file has a sample rate of 0".to_string());
+                }
+
+                let frame_size = channels as usize * bits as usize / 8;
+                let samples = bytes[body..body_end].chunks_exact(frame_size)
+                    .map(|frame|
+                    {
+                        let sum: i32 = frame.chunks_exact(bits as usize / 8)
+                            .map(|sample| match bits
+                            {
+                                8 => (sample[0] as i32 - 128) << 8,
+                                _ => i16::from_le_bytes([sample[0], sample[1]]) as i32,
+                            })
+                            .sum();
+                        (sum / channels as i32) as i16
+                    })
+                    .collect();
+                return Ok(Wav { sample_rate, samples });
+            },
+            _ => (),
+        }
+        // Chunks are padded to an even size
+        position = body + size + (size & 1);
+    }
+    Err("WAV file has no data".to_string())
+}
@@ -0,0 +1,332 @@
+/// A value in a TOML document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value
+{
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+    TableArray(Vec<Table>),
+}
+
+impl Value
+{
+    fn type_name(&self) -> &'static str
+    {
+        match self
+        {
+            Value::String(_)     => "a string",
+            Value::Integer(_)    => "an integer",
+            Value::Boolean(_)    => "a boolean",
+            Value::Array(_)      => "an array",
+            Value::Table(_)      => "a table",
+            Value::TableArray(_) => "an array of tables",
+        }
+    }
+}
+
+/// Keys and values in the order they were written.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table
+{
+    pub entries: Vec<(String, Value)>,
+}
+
+impl Table
+{
+    pub fn get(&self, key: &str) -> Option<&Value>
+    {
+        self.entries.iter().find(|(name, _)| name == key).map(|(_, value)| value)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Value>
+    {
+        self.entries.iter_mut().find(|(name, _)| name == key).map(|(_, value)| value)
+    }
+
+    pub fn get_string(&self, key: &str) -> Result<Option<&str>, String>
+    {
+        match self.get(key)
+        {
+            Some(Value::String(text)) => Ok(Some(text)),
+            Some(other) => Err(format!("{} should be a string, not {}", key, other.type_name())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_integer(&self, key: &str) -> Result<Option<i64>, String>
+    {
+        match self.get(key)
+        {
+            Some(Value::Integer(number)) => Ok(Some(*number)),
+            Some(other) => Err(format!("{} should be an integer, not {}", key, other.type_name())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_boolean(&self, key: &str) -> Result<Option<bool>, String>
+    {
+        match self.get(key)
+        {
+            Some(Value::Boolean(flag)) => Ok(Some(*flag)),
+            Some(other) => Err(format!("{} should be true or false, not {}", key, other.type_name())),
+            None => Ok(None),
+        }
+    }
+
+    /// An integer that must fit in `0..=max`.
+    pub fn get_ranged(&self, key: &str, max: i64) -> Result<Option<u32>, String>
+    {
+        match self.get_integer(key)?
+        {
+            Some(number) if (0..=max).contains(&number) => Ok(Some(number as u32)),
+            Some(number) => Err(format!("{} = {} is out of range, 0 to {:#X}", key, number, max)),
+            None => Ok(None),
+        }
+    }
+
+    /// The tables of a `[[key]]` array, or none if there is no such array.
+    pub fn get_tables(&self, key: &str) -> Result<&[Table], String>
+    {
+        match self.get(key)
+        {
+            Some(Value::TableArray(tables)) => Ok(tables),
+            Some(other) => Err(format!("{} should be written as [[{}]] tables, not {}", key, key, other.type_name())),
+            None => Ok(&[]),
+        }
+    }
+
+    pub fn get_table(&self, key: &str) -> Result<Option<&Table>, String>
+    {
+        match self.get(key)
+        {
+            Some(Value::Table(table)) => Ok(Some(table)),
+            Some(other) => Err(format!("{} should be a [{}] table, not {}", key, key, other.type_name())),
+            None => Ok(None),
+        }
+    }
+
+    fn insert(&mut self, key: String, value: Value) -> Result<(), String>
+    {
+        if self.get(&key).is_some()
+        {
+            return Err(format!("{} is defined twice", key));
+        }
+        self.entries.push((key, value));
+        Ok(())
+    }
+}
+
+/// Where the `key = value` lines go: the top level, a table or the last table of an array.
+enum Section
+{
+    Root,
+    Table(String),
+    TableArray(String),
+}
+
+/// Parses the part of TOML that machine definitions use into the top level table:
+/// `key = value` pairs, `[table]` and `[[array of tables]]` headers, strings, integers in
+/// decimal, hex, octal or binary, booleans and one line arrays. Dotted keys, inline tables
+/// and dates are not supported.
+pub fn parse(text: &str) -> Result<Table, String>
+{
+    let mut root = Table::default();
+    let mut section = Section::Root;
+
+    for (number, line) in text.lines().enumerate()
+    {
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let line = strip_comment(line).trim();
+        if line.is_empty()
+        {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("[[").and_then(|rest| rest.strip_suffix("]]"))
+        {
+            let name = parse_key(name.trim()).map_err(error)?;
+            match root.get_mut(&name)
+            {
+                Some(Value::TableArray(tables)) => tables.push(Table::default()),
+                Some(_) => return Err(error(format!("{} is already defined as something else", name))),
+                None => root.entries.push((name.clone(), Value::TableArray(vec![Table::default()]))),
+            }
+            section = Section::TableArray(name);
+        }
+        else if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']'))
+        {
+            let name = parse_key(name.trim()).map_err(error)?;
+            root.insert(name.clone(), Value::Table(Table::default())).map_err(error)?;
+            section = Section::Table(name);
+        }
+        else
+        {
+            let (key, value) = match line.split_once('=')
+            {
+                Some((key, value)) => (parse_key(key.trim()).map_err(error)?, value.trim()),
+                None => return Err(error(format!("expected key = value, got {}", line))),
+            };
+            let value = parse_value(value).map_err(error)?;
+
+            let table = match &section
+            {
+                Section::Root => &mut root,
+                Section::Table(name) => match root.get_mut(name)
+                {
+                    Some(Value::Table(table)) => table,
+                    _ => unreachable!("The section header added the table"),
+                },
+                Section::TableArray(name) => match root.get_mut(name)
+                {
+                    Some(Value::TableArray(tables)) => tables.last_mut().expect("The section header added a table"),
+                    _ => unreachable!("The section header added the array"),
+                },
+            };
+            table.insert(key, value).map_err(error)?;
+        }
+    }
+    Ok(root)
+}
+
+/// The line up to a `#` that is not inside a string.
+fn strip_comment(line: &str) -> &str
+{
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices()
+    {
+        match (quote, c)
+        {
+            (Some('"'), '\\') if !escaped => { escaped = true; continue; },
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => (),
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_key(key: &str) -> Result<String, String>
+{
+    if key.starts_with('"') || key.starts_with('\'')
+    {
+        return match parse_value(key)?
+        {
+            Value::String(text) => Ok(text),
+            _ => Err(format!("invalid key {}", key)),
+        };
+    }
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !bare
+    {
+        return Err(format!("invalid key {}, quote it or use letters, digits, - and _", key));
+    }
+    Ok(key.to_string())
+}
+
+fn parse_value(text: &str) -> Result<Value, String>
+{
+    let (value, rest) = parse_prefix(text)?;
+    if !rest.trim().is_empty()
+    {
+        return Err(format!("unexpected {} after the value", rest.trim()));
+    }
+    Ok(value)
+}
+
+/// Parses the value at the start of `text`, returning it and the text after it.
+fn parse_prefix(text: &str) -> Result<(Value, &str), String>
+{
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix('"')
+    {
+        let mut string = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next()
+        {
+            match c
+            {
+                '"' => return Ok((Value::String(string), &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c)
+                {
+                    Some('n')  => string.push('\n'),
+                    Some('t')  => string.push('\t'),
+                    Some('r')  => string.push('\r'),
+                    Some('"')  => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    other => return Err(format!("unknown escape \\{}", other.map_or(String::new(), |c| c.to_string()))),
+                },
+                c => string.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('\'')
+    {
+        return match rest.split_once('\'')
+        {
+            Some((string, rest)) => Ok((Value::String(string.to_string()), rest)),
+            None => Err("unterminated string".to_string()),
+        };
+    }
+    if let Some(mut rest) = text.strip_prefix('[')
+    {
+        let mut items = Vec::new();
+        loop
+        {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']')
+            {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_prefix(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',')
+            {
+                rest = after;
+            }
+            else if !rest.starts_with(']')
+            {
+                return Err("expected , or ] in array".to_string());
+            }
+        }
+    }
+
+    let end = text.find(|c: char| c == ',' || c == ']' || c.is_whitespace()).unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    let value = match word
+    {
+        "true"  => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => Value::Integer(parse_integer(word)?),
+    };
+    Ok((value, rest))
+}
+
+fn parse_integer(word: &str) -> Result<i64, String>
+{
+    let digits = word.replace('_', "");
+    let (negative, digits) = match digits.strip_prefix('-')
+    {
+        Some(rest) => (true, rest.to_string()),
+        None => (false, digits.strip_prefix('+').unwrap_or(&digits).to_string()),
+    };
+    let parsed = match digits.get(..2)
+    {
+        Some("0x") | Some("0X") => i64::from_str_radix(&digits[2..], 16),
+        Some("0o") => i64::from_str_radix(&digits[2..], 8),
+        Some("0b") => i64::from_str_radix(&digits[2..], 2),
+        _ => digits.parse::<i64>(),
+    };
+    match parsed
+    {
+        Ok(number) => Ok(if negative { -number } else { number }),
+        Err(_) if word.is_empty() => Err("missing value".to_string()),
+        Err(_) => Err(format!("invalid value {}", word)),
+    }
+}
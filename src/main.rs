@@ -1,21 +1,450 @@
 use remus8080::*;
 use structopt::StructOpt;
+use std::fs::File;
+use std::io::{stdin, stdout, Read};
+use std::process::exit;
+use std::time::{Duration, Instant};
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use debugger::screen::{RenderMode, ScreenWindow};
+use machines::keymap::KeyMap;
+use machines::screenshot::save;
 
 #[derive(StructOpt)]
 struct Options
 {
     #[structopt(short = "r", long = "rom", help = "Path to ROM file")]
-    rom: String,
+    rom: Option<String>,
 
     #[structopt( short = "f", long = "freq", help = "CPU frequency in Hz", default_value = "2000000")]
     cpu_freq: u32,
+
+    #[structopt(long = "format", parse(try_from_str = image::Format::parse),
+                help = "Format of the ROM, bin or hex, by default picked by its extension")]
+    format: Option<image::Format>,
+
+    #[structopt(short = "m", long = "machine", help = "Run the machine described in a definition file")]
+    machine: Option<std::path::PathBuf>,
+
+    #[structopt(short = "y", long = "symbols", number_of_values = 1,
+                help = "Symbol file for the debugger, may be repeated")]
+    symbols: Vec<std::path::PathBuf>,
+
+    #[structopt(long = "profile",
+                help = "Profile the ROM in the debugger from the start and write folded stacks to this file on quitting")]
+    profile: Option<std::path::PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command
+{
+    #[structopt(about = "Run a CP/M .COM program with its console on this terminal")]
+    Cpm
+    {
+        #[structopt(short = "d", long = "drive", number_of_values = 1, parse(try_from_str = cpm::parse_drive_mapping),
+                    help = "Map a drive to a host directory, e.g. A=./dir")]
+        drives: Vec<(u8, std::path::PathBuf)>,
+
+        #[structopt(help = "Path to the .COM program")]
+        program: String,
+
+        #[structopt(help = "Command tail passed to the program")]
+        arguments: Vec<String>,
+    },
+
+    #[structopt(about = "Boot CP/M 2.2 from the disk image in drive A: on an emulated BIOS")]
+    Boot
+    {
+        #[structopt(short = "d", long = "drive", number_of_values = 1, required = true,
+                    parse(try_from_str = cpm::parse_drive_mapping),
+                    help = "Put a disk image in a drive, e.g. A=cpm22.dsk")]
+        drives: Vec<(u8, std::path::PathBuf)>,
+
+        #[structopt(long = "format", default_value = "ibm-3740", help = "Disk format of the images")]
+        format: String,
+
+        #[structopt(long = "diskdefs", help = "cpmtools diskdefs file to look the format up in")]
+        diskdefs: Option<std::path::PathBuf>,
+
+        #[structopt(long = "ccp", parse(try_from_str = parse_address),
+                    help = "Address the CCP runs from, detected from the boot tracks by default")]
+        ccp_base: Option<u16>,
+    },
+
+    #[structopt(about = "Play Space Invaders on this terminal")]
+    Invaders(InvadersOptions),
+
+    #[structopt(about = "Work an Altair 8800 from its front panel")]
+    Altair
+    {
+        #[structopt(short = "l", long = "load", number_of_values = 1, parse(try_from_str = machines::altair::parse_load),
+                    help = "Load a binary into memory, e.g. basic.bin@0x0")]
+        loads: Vec<(std::path::PathBuf, u16)>,
+
+        #[structopt(long = "fps", default_value = "30", help = "Most times per second to update the lights")]
+        fps: u32,
+
+        #[structopt(long = "serial", possible_values = &["sio", "2sio"],
+                    help = "Run from --start with this serial board on the terminal instead of the front panel")]
+        serial: Option<String>,
+
+        #[structopt(long = "baud", default_value = "9600", help = "Baud rate of the serial board")]
+        baud: u32,
+
+        #[structopt(long = "start", default_value = "0", parse(try_from_str = parse_address),
+                    help = "Address to run from with --serial")]
+        start: u16,
+    },
+
+    #[structopt(about = "Convert between raw binaries and Intel HEX")]
+    Convert
+    {
+        #[structopt(help = "File to read")]
+        input: std::path::PathBuf,
+
+        #[structopt(help = "File to write")]
+        output: std::path::PathBuf,
+
+        #[structopt(long = "org", default_value = "0", parse(try_from_str = parse_address),
+                    help = "Address a binary input is loaded at")]
+        origin: u16,
+
+        #[structopt(long = "entry", parse(try_from_str = parse_address), help = "Entry point to record in a HEX output")]
+        entry: Option<u16>,
+
+        #[structopt(long = "input-format", parse(try_from_str = image::Format::parse),
+                    help = "Format of the input, bin or hex, by default picked by its extension")]
+        input_format: Option<image::Format>,
+
+        #[structopt(long = "format", parse(try_from_str = image::Format::parse),
+                    help = "Format of the output, bin or hex, by default picked by its extension")]
+        format: Option<image::Format>,
+    },
+
+    #[structopt(about = "Assemble 8080 source into a binary, a listing and a symbol file")]
+    Asm
+    {
+        #[structopt(help = "Source file")]
+        source: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", help = "File to write, by default the source with a .bin or .hex extension")]
+        output: Option<std::path::PathBuf>,
+
+        #[structopt(long = "format", parse(try_from_str = image::Format::parse),
+                    help = "Format of the output, bin or hex, by default picked by its extension")]
+        format: Option<image::Format>,
+
+        #[structopt(long = "listing", help = "Listing to write, by default the output with a .lst extension")]
+        listing: Option<std::path::PathBuf>,
+
+        #[structopt(long = "symbols", help = "Symbol file to write, by default the output with a .sym extension")]
+        symbols: Option<std::path::PathBuf>,
+    },
+
+    #[structopt(about = "Link Microsoft REL modules into a .COM program and a symbol map")]
+    Link
+    {
+        #[structopt(required = true, help = "REL modules to link")]
+        inputs: Vec<std::path::PathBuf>,
+
+        #[structopt(short = "o", long = "output", help = "File to write, by default the first module with a .com extension")]
+        output: Option<std::path::PathBuf>,
+
+        #[structopt(short = "l", long = "library", number_of_values = 1,
+                    help = "Library to take the modules defining undefined symbols from, may be repeated")]
+        libraries: Vec<std::path::PathBuf>,
+
+        #[structopt(long = "org", default_value = "0x100", parse(try_from_str = parse_address),
+                    help = "Address of the program segments")]
+        origin: u16,
+
+        #[structopt(long = "data", parse(try_from_str = parse_address),
+                    help = "Address of the data segments, by default after the program")]
+        data: Option<u16>,
+
+        #[structopt(long = "format", parse(try_from_str = image::Format::parse),
+                    help = "Format of the output, bin or hex, by default picked by its extension")]
+        format: Option<image::Format>,
+
+        #[structopt(long = "map", help = "Symbol map to write, by default the output with a .sym extension")]
+        map: Option<std::path::PathBuf>,
+    },
+
+    #[structopt(about = "Disassemble a program into source that assembles back into it")]
+    Disasm
+    {
+        #[structopt(help = "Program to disassemble")]
+        input: std::path::PathBuf,
+
+        #[structopt(short = "o", long = "output", help = "Source to write, by default the input with a .asm extension")]
+        output: Option<std::path::PathBuf>,
+
+        #[structopt(long = "org", default_value = "0", parse(try_from_str = parse_address),
+                    help = "Address a raw binary is loaded at")]
+        origin: u16,
+
+        #[structopt(short = "e", long = "entry", number_of_values = 1, parse(try_from_str = parse_address),
+                    help = "Address execution can start at, may be repeated. By default the program's start")]
+        entries: Vec<u16>,
+
+        #[structopt(long = "format", parse(try_from_str = image::Format::parse),
+                    help = "Format of the input, bin or hex, by default picked by its extension")]
+        format: Option<image::Format>,
+
+        #[structopt(short = "y", long = "symbols", number_of_values = 1, help = "Symbol file naming addresses, may be repeated")]
+        symbols: Vec<std::path::PathBuf>,
+
+        #[structopt(long = "dot", help = "Graphviz file to write the control flow graph of each subroutine to")]
+        dot: Option<std::path::PathBuf>,
+
+        #[structopt(long = "call-graph", help = "Write the graph of which subroutine calls which to --dot instead")]
+        call_graph: bool,
+
+        #[structopt(long = "coverage", number_of_values = 1,
+                    help = "Coverage file the debugger saved, telling code from data, may be repeated")]
+        coverage: Vec<std::path::PathBuf>,
+    },
+
+    #[structopt(about = "List, extract, insert and delete files in a CP/M disk image")]
+    Disk
+    {
+        #[structopt(help = "Path to the disk image")]
+        image: std::path::PathBuf,
+
+        #[structopt(long = "format", default_value = "ibm-3740", help = "Disk format of the image")]
+        format: String,
+
+        #[structopt(long = "diskdefs", help = "cpmtools diskdefs file to look the format up in")]
+        diskdefs: Option<std::path::PathBuf>,
+
+        #[structopt(subcommand)]
+        action: DiskAction,
+    },
+}
+
+#[derive(StructOpt)]
+struct InvadersOptions
+{
+    #[structopt(help = "The 8K ROM, or a directory with invaders.h, .g, .f and .e")]
+    rom: std::path::PathBuf,
+
+    #[structopt(long = "debug", help = "Open the debugger with the screen next to the memory pane")]
+    debug: bool,
+
+    #[structopt(long = "fps", default_value = "30", help = "Most frames per second to draw")]
+    fps: u32,
+
+    #[structopt(long = "half-blocks", help = "Draw with half blocks instead of braille, larger but sharper")]
+    half_blocks: bool,
+
+    #[structopt(long = "ships", default_value = "3", help = "Ships per game, 3 to 6")]
+    ships: u8,
+
+    #[structopt(long = "extra-ship-at", default_value = "1500", help = "Score for an extra ship, 1000 or 1500")]
+    extra_ship_at: u16,
+
+    #[structopt(long = "hide-coin-info", help = "Hide the coin information in the attract mode")]
+    hide_coin_info: bool,
+
+    #[structopt(long = "keymap", help = "Key map file, lines of key = input")]
+    keymap: Option<std::path::PathBuf>,
+
+    #[structopt(long = "hold", help = "Milliseconds a key press holds its input down")]
+    hold: Option<u64>,
+
+    #[structopt(long = "headless", requires = "frames",
+                help = "Run without a screen or keyboard, as fast as possible, for --frames frames")]
+    headless: bool,
+
+    #[structopt(long = "frames", help = "Stop after this many frames")]
+    frames: Option<u64>,
+
+    #[structopt(long = "sound", help = "Record the sound to this WAV file")]
+    sound: Option<std::path::PathBuf>,
+
+    #[structopt(long = "samples", help = "Directory with the sample set 0.wav to 9.wav, tones are used otherwise")]
+    samples: Option<std::path::PathBuf>,
+
+    #[structopt(long = "screenshot", help = "Save the screen to this .png or .ppm file on exit")]
+    screenshot: Option<std::path::PathBuf>,
+
+    #[structopt(long = "dump-every", help = "Save every Nth frame")]
+    dump_every: Option<u64>,
+
+    #[structopt(long = "dump-dir", default_value = ".", help = "Directory to save frames to")]
+    dump_dir: std::path::PathBuf,
+
+    #[structopt(long = "dump-format", default_value = "png", possible_values = &["png", "ppm"],
+                help = "Image format of saved frames")]
+    dump_format: String,
+
+    #[structopt(long = "color", help = "Colour screenshots with the cabinet's overlay")]
+    color: bool,
+}
+
+#[derive(StructOpt)]
+enum DiskAction
+{
+    #[structopt(about = "List the files on the disk")]
+    Ls,
+
+    #[structopt(about = "Copy files from the disk, names may have wildcards and a user number, e.g. 3:*.asm")]
+    Get
+    {
+        files: Vec<String>,
+
+        #[structopt(short = "o", long = "output", default_value = ".", help = "Directory to copy the files to")]
+        output: std::path::PathBuf,
+    },
+
+    #[structopt(about = "Copy host files onto the disk")]
+    Put
+    {
+        files: Vec<std::path::PathBuf>,
+
+        #[structopt(short = "u", long = "user", default_value = "0", help = "User number to store the files under, 0 to 15")]
+        user: u8,
+    },
+
+    #[structopt(about = "Delete files from the disk, names may have wildcards and a user number")]
+    Rm
+    {
+        files: Vec<String>,
+    },
 }
 
 fn main() 
 {
     let args = Options::from_args();
-    let mut p = i8080::Processor::from_file(args.rom, args.cpu_freq);
+
+    match args.command
+    {
+        Some(Command::Cpm { drives, program, arguments }) => run_cpm(program, arguments, drives, args.cpu_freq),
+        Some(Command::Boot { drives, format, diskdefs, ccp_base }) => boot_cpm(drives, format, diskdefs, ccp_base, args.cpu_freq),
+        Some(Command::Disk { image, format, diskdefs, action }) => disk_tool(image, format, diskdefs, action),
+        Some(Command::Invaders(options)) => run_invaders(options, args.cpu_freq),
+        Some(Command::Convert { input, output, origin, entry, input_format, format }) =>
+        {
+            let mut image = or_exit(image::Image::load(&input, input_format, origin));
+            image.entry = entry.or(image.entry);
+            or_exit(image.save(&output, format));
+        },
+        Some(Command::Asm { source, output, format, listing, symbols }) => assemble(source, output, format, listing, symbols),
+        Some(Command::Disasm { input, output, origin, entries, format, symbols, dot, call_graph, coverage }) =>
+        {
+            let program = disassemble(&input, origin, entries, format, symbols, coverage);
+            let output = output.unwrap_or_else(|| input.with_extension("asm"));
+            write_file(&output, program.to_source());
+            if let Some(dot) = dot
+            {
+                let graph = disassembler::graph::Graph::new(&program);
+                write_file(&dot, if call_graph { graph.to_call_graph() } else { graph.to_dot() });
+            }
+        },
+        Some(Command::Link { inputs, output, libraries, origin, data, format, map }) =>
+        {
+            let layout = linker::Layout { program: origin, data };
+            link(inputs, output, libraries, layout, format, map);
+        },
+        Some(Command::Altair { loads, fps, serial, baud, start }) =>
+        {
+            match serial
+            {
+                Some(board) => run_altair_serial(loads, &board, baud, start, args.cpu_freq),
+                None        => run_altair(loads, fps, args.cpu_freq),
+            }
+        },
+        None =>
+        {
+            match (args.machine, args.rom)
+            {
+                (Some(machine), _) => run_machine(&machine),
+                (None, Some(rom))  => run_debugger(rom, args.format, args.symbols, args.profile, args.cpu_freq),
+                (None, None)       => Options::clap().print_help().expect("Failed to print help"),
+            }
+        },
+    }
+}
+
+fn assemble(source: std::path::PathBuf, output: Option<std::path::PathBuf>, format: Option<image::Format>,
+            listing: Option<std::path::PathBuf>, symbols: Option<std::path::PathBuf>)
+{
+    let assembly = or_exit(assembler::assemble_file(&source));
+    let output = output.unwrap_or_else(|| match format
+    {
+        Some(image::Format::IntelHex) => source.with_extension("hex"),
+        _ => source.with_extension("bin"),
+    });
+    match format.unwrap_or_else(|| image::Format::from_path(&output))
+    {
+        image::Format::Binary => or_exit(std::fs::write(&output, assembly.to_binary()).map_err(|e| format!("{}: {}", output.display(), e))),
+        image::Format::IntelHex => or_exit(assembly.image.save(&output, format)),
+    }
+
+    let write = |path: std::path::PathBuf, text: String| std::fs::write(&path, text).map_err(|e| format!("{}: {}", path.display(), e));
+    or_exit(write(listing.unwrap_or_else(|| output.with_extension("lst")), assembly.write_listing()));
+    or_exit(write(symbols.unwrap_or_else(|| output.with_extension("sym")), assembly.write_symbols()));
+}
+
+fn link(inputs: Vec<std::path::PathBuf>, output: Option<std::path::PathBuf>, libraries: Vec<std::path::PathBuf>,
+        layout: linker::Layout, format: Option<image::Format>, map: Option<std::path::PathBuf>)
+{
+    let load = |paths: &[std::path::PathBuf]| -> Vec<linker::rel::Module>
+    {
+        paths.iter().flat_map(|path| or_exit(linker::load_modules(path))).collect()
+    };
+    let linked = or_exit(linker::link(&load(&inputs), &load(&libraries), layout));
+    let output = output.unwrap_or_else(|| inputs[0].with_extension("com"));
+    or_exit(linked.image.save(&output, format));
+
+    let map = map.unwrap_or_else(|| output.with_extension("sym"));
+    or_exit(std::fs::write(&map, linked.write_symbols()).map_err(|e| format!("{}: {}", map.display(), e)));
+}
+
+fn disassemble(input: &std::path::Path, origin: u16, entries: Vec<u16>, format: Option<image::Format>,
+               symbol_files: Vec<std::path::PathBuf>, coverage_files: Vec<std::path::PathBuf>) -> disassembler::traversal::Program
+{
+    let image = or_exit(image::Image::load(input, format, origin));
+    let start = image.start().unwrap_or(origin);
+    let entries = if entries.is_empty() { vec![image.entry.unwrap_or(start)] } else { entries };
+    let mut symbols = symbols::SymbolTable::default();
+    for path in symbol_files
+    {
+        symbols.extend(&or_exit(symbols::SymbolTable::load(&path)));
+    }
+
+    let mut hints = coverage::Coverage::default();
+    for path in coverage_files
+    {
+        hints.merge(&or_exit(coverage::Coverage::load(&path)));
+    }
+
+    disassembler::traversal::disassemble_with_hints(&image.to_binary(), start, &entries, &symbols, &hints)
+}
+
+fn write_file(path: &std::path::Path, text: String)
+{
+    or_exit(std::fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e)));
+}
+
+fn run_debugger(rom: String, format: Option<image::Format>, symbols: Vec<std::path::PathBuf>,
+                profile: Option<std::path::PathBuf>, cpu_freq: u32)
+{
+    let mut p = i8080::Processor::from_bytes(Vec::new(), cpu_freq);
+    or_exit(image::Image::load(std::path::Path::new(&rom), format, 0)).load_into(&mut p);
     let mut dgb = debugger::Debugger::default();
+    for path in symbols
+    {
+        dgb.add_symbols(&or_exit(symbols::SymbolTable::load(&path)));
+    }
+    if let Some(profile) = profile
+    {
+        dgb.profile_to(profile);
+    }
 
     dgb.execute(&mut p, true);
     loop
@@ -28,3 +457,454 @@ fn main()
     }
 }
 
+fn run_cpm(program: String, arguments: Vec<String>, drives: Vec<(u8, std::path::PathBuf)>, cpu_freq: u32)
+{
+    let mut bytes = Vec::new();
+    File::open(&program).expect("No such file").read_to_end(&mut bytes).expect("Failed to read program");
+    if bytes.len() > cpm::TPA_SIZE
+    {
+        eprintln!("{} does not fit in the TPA, which holds {:#X} bytes", program, cpm::TPA_SIZE);
+        exit(1);
+    }
+
+    let mut p = i8080::Processor::from_bytes(Vec::new(), cpu_freq);
+    let mut cpm = cpm::Cpm::new(stdin(), stdout());
+    for (drive, directory) in drives
+    {
+        cpm.mount(drive, directory);
+    }
+    cpm.load(&mut p, &bytes);
+    cpm.set_arguments(&mut p, &arguments);
+    while cpm.clock(&mut p) {}
+}
+
+fn boot_cpm(drives: Vec<(u8, std::path::PathBuf)>, format: String, diskdefs: Option<std::path::PathBuf>,
+            ccp_base: Option<u16>, cpu_freq: u32)
+{
+    let definition = or_exit(cpm::disk::DiskDefinition::find(&format, diskdefs.as_deref()));
+
+    let mut bios = cpm::bios::Bios::new(console::HostConsole::new());
+    for (drive, path) in drives
+    {
+        bios.insert(drive, or_exit(cpm::disk::DiskImage::open(&path, definition.clone())));
+    }
+
+    let mut p = i8080::Processor::from_bytes(Vec::new(), cpu_freq);
+    or_exit(bios.boot(&mut p, ccp_base));
+
+    let _raw = stdout().into_raw_mode().expect("Failed to put the terminal in raw mode");
+    while bios.clock(&mut p) {}
+}
+
+/// Reads the key map file if one is given, else the machine's default key map.
+fn load_keymap(machine: &dyn machines::Machine, path: Option<std::path::PathBuf>, default: &str,
+               hold: Option<u64>) -> KeyMap
+{
+    let text = match path
+    {
+        Some(path) => or_exit(std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))),
+        None       => default.to_string(),
+    };
+    let mut keymap = or_exit(KeyMap::parse(&text, &machine.named_inputs()));
+    if let Some(hold) = hold
+    {
+        keymap.set_hold(Duration::from_millis(hold));
+    }
+    keymap
+}
+
+fn run_invaders(options: InvadersOptions, cpu_freq: u32)
+{
+    use machines::Machine;
+    use machines::invaders::*;
+
+    let dip_switches = DipSwitches
+    {
+        ships:          options.ships,
+        extra_ship_at:  options.extra_ship_at,
+        show_coin_info: !options.hide_coin_info,
+    };
+    let mut invaders = Invaders::new(dip_switches);
+    let mut keymap = load_keymap(&invaders, options.keymap, DEFAULT_KEYMAP, options.hold);
+    let mode = if options.half_blocks { RenderMode::HalfBlock } else { RenderMode::Braille };
+    let overlay = if options.color { invaders.color_overlay() } else { None };
+
+    let rom = or_exit(load_rom(&options.rom));
+    let mut p = i8080::Processor::from_bytes(rom, 0);
+
+    if options.debug
+    {
+        let mut dgb = debugger::Debugger::default();
+        dgb.attach_machine(Box::new(&mut invaders), mode, options.fps);
+        dgb.execute(&mut p, true);
+        while dgb.execute(&mut p, false).is_some() {}
+        drop(dgb);
+        finish_invaders(&mut invaders, &p, &options.sound, &options.samples);
+        return;
+    }
+
+    // Frames are paced so the emulated CPU runs at the requested frequency, 0 runs flat out
+    let frame_time = match cpu_freq
+    {
+        0 => Duration::from_secs(0),
+        _ if options.headless => Duration::from_secs(0),
+        _ => Duration::from_secs(1) * CYCLES_PER_FRAME as u32 / cpu_freq,
+    };
+    let mut window = match options.headless
+    {
+        true  => None,
+        false => Some((ScreenWindow::new("Space Invaders - q to quit", mode, options.fps), termion::async_stdin().keys())),
+    };
+
+    'running: while options.frames.map_or(true, |frames| invaders.get_frames() < frames)
+    {
+        let start = Instant::now();
+        if let Some((_, keys)) = window.as_mut()
+        {
+            for key in keys.by_ref()
+            {
+                match key
+                {
+                    Ok(Key::Char('q')) | Ok(Key::Ctrl('c')) => break 'running,
+                    Ok(key) => { keymap.press(key, &mut invaders, start); },
+                    Err(_) => (),
+                }
+            }
+            keymap.update(&mut invaders, start);
+        }
+
+        invaders.run_frame(&mut p);
+        let framebuffer = invaders.framebuffer(&p).expect("Space Invaders has a screen");
+        if let Some((window, _)) = window.as_mut()
+        {
+            window.draw(&framebuffer);
+        }
+        if options.dump_every.is_some_and(|every| every > 0 && invaders.get_frames().is_multiple_of(every))
+        {
+            let path = options.dump_dir.join(format!("frame-{:06}.{}", invaders.get_frames(), options.dump_format));
+            or_exit(save(&path, &framebuffer, overlay.as_ref()));
+        }
+
+        if let Some(remaining) = frame_time.checked_sub(start.elapsed())
+        {
+            std::thread::sleep(remaining);
+        }
+    }
+    drop(window);
+
+    if let Some(path) = &options.screenshot
+    {
+        let framebuffer = invaders.framebuffer(&p).expect("Space Invaders has a screen");
+        or_exit(save(path, &framebuffer, overlay.as_ref()));
+    }
+    finish_invaders(&mut invaders, &p, &options.sound, &options.samples);
+}
+
+/// Writes the sound recorded during the run, if it was asked for.
+fn finish_invaders(invaders: &mut machines::invaders::Invaders, p: &i8080::Processor,
+                   sound: &Option<std::path::PathBuf>, samples: &Option<std::path::PathBuf>)
+{
+    use machines::sound::*;
+
+    let path = match sound
+    {
+        Some(path) => path,
+        None       => return,
+    };
+    let bank = or_exit(machines::invaders::sound_bank(samples.as_deref(), DEFAULT_SAMPLE_RATE));
+    let events = invaders.take_sound_events();
+    let samples = render(&events, &bank, machines::invaders::CLOCK_FREQUENCY, p.get_cycles(), DEFAULT_SAMPLE_RATE);
+
+    let mut file = or_exit(File::create(path).map_err(|e| format!("{}: {}", path.display(), e)));
+    or_exit(write_wav(&mut file, &samples, DEFAULT_SAMPLE_RATE).map_err(|e| format!("{}: {}", path.display(), e)));
+}
+
+/// An empty Altair with the files loaded into memory. Intel HEX files carry their own
+/// addresses, binaries go where the load says.
+fn load_altair(loads: Vec<(std::path::PathBuf, u16)>) -> i8080::Processor
+{
+    let mut p = i8080::Processor::from_bytes(Vec::new(), 0);
+    for (path, address) in loads
+    {
+        or_exit(image::Image::load(&path, None, address)).load_into(&mut p);
+    }
+    p
+}
+
+fn run_altair(loads: Vec<(std::path::PathBuf, u16)>, fps: u32, cpu_freq: u32)
+{
+    use machines::altair::Altair;
+
+    let mut p = load_altair(loads);
+
+    let mut altair = Altair::new();
+    altair.reset(&mut p);
+
+    // Every tick runs a tick's worth of cycles, 0 runs as many as fit in the tick
+    let tick = Duration::from_secs(1) / fps.max(1);
+    let cycles_per_tick = (cpu_freq / fps.max(1)) as u64;
+    let mut window = debugger::front_panel::PanelWindow::new("Altair 8800", fps);
+    let mut keys = termion::async_stdin().keys();
+    let mut cursor = 0u8;
+
+    'running: loop
+    {
+        let start = Instant::now();
+        for key in keys.by_ref()
+        {
+            let switches = altair.get_switches();
+            match key
+            {
+                Ok(Key::Char('q')) | Ok(Key::Ctrl('c')) => break 'running,
+                Ok(Key::Char('x')) => altair.examine(&mut p),
+                Ok(Key::Char('X')) => altair.examine_next(&mut p),
+                Ok(Key::Char('p')) => altair.deposit(&mut p),
+                Ok(Key::Char('P')) => altair.deposit_next(&mut p),
+                Ok(Key::Char('r')) => altair.run(),
+                Ok(Key::Char('s')) => altair.stop(&p),
+                Ok(Key::Char('n')) => altair.single_step(&mut p),
+                Ok(Key::Char('R')) => altair.reset(&mut p),
+                Ok(Key::Char(' ')) => altair.toggle_switch(cursor),
+                Ok(Key::Left)      => cursor = (cursor + 1).min(15),
+                Ok(Key::Right)     => cursor = cursor.saturating_sub(1),
+                Ok(Key::Backspace) => altair.set_switches(0),
+                Ok(Key::Char(c)) if c.is_ascii_hexdigit() =>
+                {
+                    let digit = c.to_digit(16).expect("Checked above") as u16;
+                    altair.set_switches(switches << 4 | digit);
+                },
+                _ => (),
+            }
+            window.draw(&altair.get_lights(), altair.get_switches(), cursor, true);
+        }
+
+        if altair.is_running()
+        {
+            match cycles_per_tick
+            {
+                0 => while altair.is_running() && start.elapsed() < tick
+                {
+                    altair.run_for(&mut p, 10_000);
+                },
+                cycles => altair.run_for(&mut p, cycles),
+            }
+        }
+        window.draw(&altair.get_lights(), altair.get_switches(), cursor, false);
+
+        if let Some(remaining) = tick.checked_sub(start.elapsed())
+        {
+            std::thread::sleep(remaining);
+        }
+    }
+}
+
+/// Runs an Altair with its serial console on this terminal until Ctrl-] is typed.
+fn run_altair_serial(loads: Vec<(std::path::PathBuf, u16)>, board: &str, baud: u32, start: u16, cpu_freq: u32)
+{
+    use machines::altair::{Altair, CLOCK_FREQUENCY};
+    use machines::serial::*;
+
+    let mut p = load_altair(loads);
+    let mut altair = Altair::new();
+    altair.reset(&mut p);
+    p.set_pc(start);
+
+    let clock_frequency = if cpu_freq > 0 { cpu_freq } else { CLOCK_FREQUENCY };
+    match board
+    {
+        "sio" =>
+        {
+            let mut sio = Sio::new(console::HostConsole::new(), SIO_PORT);
+            sio.set_baud_rate(baud, clock_frequency);
+            altair.attach(Box::new(sio));
+        },
+        _ =>
+        {
+            let mut two_sio = TwoSio::new(console::HostConsole::new(), console::NullConsole, TWO_SIO_PORT);
+            two_sio.set_baud_rate(baud, clock_frequency);
+            altair.attach(Box::new(two_sio));
+        },
+    }
+
+    // Runs in slices of 10 ms, paced to the CPU frequency unless it is 0
+    let slice = Duration::from_millis(10);
+    let cycles_per_slice = clock_frequency as u64 / 100;
+    let _raw = stdout().into_raw_mode().expect("Failed to put the terminal in raw mode");
+    altair.run();
+    while !altair.is_closed()
+    {
+        let start = Instant::now();
+        altair.run_for(&mut p, cycles_per_slice);
+        if cpu_freq > 0
+        {
+            if let Some(remaining) = slice.checked_sub(start.elapsed())
+            {
+                std::thread::sleep(remaining);
+            }
+        }
+    }
+}
+
+/// Builds the machine in a definition file and runs it: on the screen if it has a display,
+/// else with its serial board on the terminal.
+fn run_machine(path: &std::path::Path)
+{
+    use machines::Machine;
+
+    let definition = or_exit(machines::definition::MachineDefinition::load(path));
+    let mut p = i8080::Processor::from_bytes(Vec::new(), 0);
+    let mut machine = or_exit(definition.build(&mut p));
+
+    if definition.display.is_none()
+    {
+        if !definition.has_terminal()
+        {
+            eprintln!("{}: the machine has neither a display nor a serial terminal", path.display());
+            exit(1);
+        }
+        let slice = Duration::from_millis(10);
+        let cycles_per_slice = (definition.clock as u64 / 100).max(1);
+        let _raw = stdout().into_raw_mode().expect("Failed to put the terminal in raw mode");
+        while !machine.is_closed()
+        {
+            let start = Instant::now();
+            let end = p.get_cycles() + cycles_per_slice;
+            while p.get_cycles() < end
+            {
+                machine.clock(&mut p);
+            }
+            if let Some(remaining) = slice.checked_sub(start.elapsed())
+            {
+                std::thread::sleep(remaining);
+            }
+        }
+        return;
+    }
+
+    let mut keymap = or_exit(KeyMap::parse(&definition.keymap, &machine.named_inputs()));
+    let frame_time = Duration::from_secs(1) * definition.frame_cycles as u32 / definition.clock.max(1);
+    let title = format!("{} - q to quit", machine.get_name());
+    let mut window = ScreenWindow::new(&title, RenderMode::Braille, 30);
+    let mut keys = termion::async_stdin().keys();
+
+    'running: loop
+    {
+        let start = Instant::now();
+        for key in keys.by_ref()
+        {
+            match key
+            {
+                Ok(Key::Char('q')) | Ok(Key::Ctrl('c')) => break 'running,
+                Ok(key) => { keymap.press(key, &mut machine, start); },
+                Err(_) => (),
+            }
+        }
+        keymap.update(&mut machine, start);
+
+        machine.run_frame(&mut p);
+        if let Some(framebuffer) = machine.framebuffer(&p)
+        {
+            window.draw(&framebuffer);
+        }
+        if let Some(remaining) = frame_time.checked_sub(start.elapsed())
+        {
+            std::thread::sleep(remaining);
+        }
+    }
+}
+
+/// Parses an address given in decimal or as 0x prefixed hex.
+fn parse_address(value: &str) -> Result<u16, String>
+{
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None      => value.parse::<u16>(),
+    };
+    parsed.map_err(|e| format!("Invalid address {}: {}", value, e))
+}
+
+fn disk_tool(image: std::path::PathBuf, format: String, diskdefs: Option<std::path::PathBuf>, action: DiskAction)
+{
+    use cpm::filesystem::*;
+
+    let definition = or_exit(cpm::disk::DiskDefinition::find(&format, diskdefs.as_deref()));
+    let mut image = or_exit(cpm::disk::DiskImage::open(&image, definition));
+    let mut filesystem = FileSystem::new(&mut image);
+
+    match action
+    {
+        DiskAction::Ls =>
+        {
+            for file in filesystem.files()
+            {
+                println!("{:>2}:{:<12} {:>8}", file.user, cpm::fcb::display_name(&file.name), file.size());
+            }
+            println!("{} blocks free", filesystem.free_blocks());
+        },
+        DiskAction::Get { files, output } =>
+        {
+            for pattern in files
+            {
+                let (user, name) = or_exit(parse_user_file_name(&pattern));
+                let found = filesystem.find(user, &name);
+                if found.is_empty()
+                {
+                    eprintln!("No file matches {}", pattern);
+                }
+                for file in found
+                {
+                    if !cpm::fcb::has_host_name(&file.name)
+                    {
+                        eprintln!("Skipping {:?}, it has no safe host file name", String::from_utf8_lossy(&file.name));
+                        continue;
+                    }
+                    let path = output.join(cpm::fcb::cpm_to_host_name(&file.name));
+                    or_exit(std::fs::write(&path, filesystem.read_file(&file)).map_err(|e| e.to_string()));
+                }
+            }
+        },
+        DiskAction::Put { files, user } =>
+        {
+            for path in files
+            {
+                let host_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+                let name = match cpm::fcb::host_to_cpm_name(host_name)
+                {
+                    Some(name) => name,
+                    None =>
+                    {
+                        eprintln!("{} has no 8.3 name", path.display());
+                        exit(1);
+                    },
+                };
+                let data = or_exit(std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e)));
+                or_exit(filesystem.write_file(user, &name, &data));
+            }
+        },
+        DiskAction::Rm { files } =>
+        {
+            for pattern in files
+            {
+                let (user, name) = or_exit(parse_user_file_name(&pattern));
+                if filesystem.delete(user, &name) == 0
+                {
+                    eprintln!("No file matches {}", pattern);
+                }
+            }
+        },
+    }
+}
+
+fn or_exit<T>(result: Result<T, String>) -> T
+{
+    match result
+    {
+        Ok(value) => value,
+        Err(e) =>
+        {
+            eprintln!("{}", e);
+            exit(1);
+        },
+    }
+}
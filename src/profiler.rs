@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::call_stack::CallStack;
+use crate::coverage::Step;
+use crate::i8080::Processor;
+use crate::symbols::SymbolTable;
+
+/// The name folded stacks give code run outside any call.
+const TOP: &str = "top";
+
+/// Instructions and cycles counted per address and per chain of calls.
+#[derive(Clone, Debug)]
+pub struct Profiler
+{
+    instructions: Vec<u64>,
+    cycles:       Vec<u64>,
+    /// Cycles by the entries of the subroutines being run, outermost first.
+    stacks:       HashMap<Vec<u16>, u64>,
+    /// Calls by subroutine entry.
+    calls:        HashMap<u16, u64>,
+    call_stack:   CallStack,
+    path:         Vec<u16>,
+}
+
+impl Default for Profiler
+{
+    fn default() -> Profiler
+    {
+        Profiler
+        {
+            instructions: vec![0; 0x10000],
+            cycles:       vec![0; 0x10000],
+            stacks:       HashMap::new(),
+            calls:        HashMap::new(),
+            call_stack:   CallStack::default(),
+            path:         Vec::new(),
+        }
+    }
+}
+
+impl Profiler
+{
+    /// Counts the instruction of `step`, `processor` being the state after it ran, to its
+    /// address and to the subroutines it ran in. Time halted is not counted.
+    pub fn record(&mut self, step: &Step, processor: &Processor)
+    {
+        if step.get_halted()
+        {
+            return;
+        }
+        let address = step.get_line().address as usize;
+        let cycles = processor.get_cycles() - step.get_cycles();
+        self.instructions[address] += 1;
+        self.cycles[address] += cycles;
+
+        self.path.clear();
+        self.path.extend(self.call_stack.get_frames().iter().map(|frame| frame.entry));
+        match self.stacks.get_mut(&self.path[..])
+        {
+            Some(total) => *total += cycles,
+            None => { self.stacks.insert(self.path.clone(), cycles); },
+        }
+
+        let depth = self.call_stack.get_frames().len();
+        self.call_stack.update(step, processor);
+        if self.call_stack.get_frames().len() > depth
+        {
+            *self.calls.entry(self.call_stack.get_frames()[depth].entry).or_default() += 1;
+        }
+    }
+
+    /// The instructions run and cycles taken at `address`.
+    pub fn get(&self, address: u16) -> (u64, u64)
+    {
+        (self.instructions[address as usize], self.cycles[address as usize])
+    }
+
+    pub fn get_total_cycles(&self) -> u64
+    {
+        self.stacks.values().sum()
+    }
+
+    /// The `count` addresses that took the most cycles, with their instructions and cycles.
+    pub fn hotspots(&self, count: usize) -> Vec<(u16, u64, u64)>
+    {
+        let mut hotspots: Vec<(u16, u64, u64)> = (0..=0xFFFF).filter(|address| self.instructions[*address as usize] > 0)
+            .map(|address: u16| { let (instructions, cycles) = self.get(address); (address, instructions, cycles) }).collect();
+        hotspots.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        hotspots.truncate(count);
+        hotspots
+    }
+
+    /// The `count` subroutines that took the most cycles with the ones they call, as their
+    /// entry, calls, cycles in themselves and cycles with their callees.
+    pub fn subroutines(&self, count: usize) -> Vec<(u16, u64, u64, u64)>
+    {
+        let mut totals: HashMap<u16, (u64, u64)> = HashMap::new();
+        for (path, cycles) in self.stacks.iter()
+        {
+            for (depth, entry) in path.iter().enumerate()
+            {
+                // Recursion counts once towards the total
+                if path[..depth].contains(entry)
+                {
+                    continue;
+                }
+                let (own, total) = totals.entry(*entry).or_default();
+                *total += cycles;
+                if depth + 1 == path.len()
+                {
+                    *own += cycles;
+                }
+            }
+        }
+        let mut subroutines: Vec<(u16, u64, u64, u64)> = totals.into_iter()
+            .map(|(entry, (own, total))| (entry, self.calls.get(&entry).copied().unwrap_or(0), own, total)).collect();
+        subroutines.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        subroutines.truncate(count);
+        subroutines
+    }
+
+    /// The top `count` hotspots and subroutines as lines of text.
+    pub fn report(&self, symbols: &SymbolTable, count: usize) -> Vec<String>
+    {
+        let total = self.get_total_cycles().max(1) as f64;
+        let name = |address: u16| symbols.describe(address).unwrap_or_default();
+        let mut lines = vec![format!("{:<24} {:>12} {:>12} {:>6}", "Address", "Instructions", "Cycles", "%")];
+        for (address, instructions, cycles) in self.hotspots(count)
+        {
+            lines.push(format!("{:04X} {:<19} {:>12} {:>12} {:>5.1}%", address, name(address), instructions, cycles, 100.0 * cycles as f64 / total));
+        }
+        lines.push(format!("{:<24} {:>12} {:>12} {:>12} {:>6}", "Subroutine", "Calls", "Self", "Total", "%"));
+        for (entry, calls, own, all) in self.subroutines(count)
+        {
+            lines.push(format!("{:04X} {:<19} {:>12} {:>12} {:>12} {:>5.1}%", entry, name(entry), calls, own, all, 100.0 * all as f64 / total));
+        }
+        lines
+    }
+
+    /// The cycles of each chain of calls as folded stacks, `top;OUTER;INNER cycles` lines
+    /// that flame graph tools read, subroutines named by `symbols` or their address.
+    pub fn to_folded(&self, symbols: &SymbolTable) -> String
+    {
+        let mut lines: Vec<String> = self.stacks.iter().map(|(path, cycles)|
+        {
+            let names: Vec<String> = path.iter().map(|entry| symbols.get_name(*entry).map_or_else(|| format!("{:04X}", entry), str::to_string)).collect();
+            let stack = std::iter::once(TOP.to_string()).chain(names).collect::<Vec<String>>().join(";");
+            format!("{} {}\n", stack, cycles)
+        }).collect();
+        lines.sort();
+        lines.concat()
+    }
+
+    pub fn save_folded(&self, path: &Path, symbols: &SymbolTable) -> Result<(), String>
+    {
+        std::fs::write(path, self.to_folded(symbols)).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
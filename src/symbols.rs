@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::assembler::expression::{is_name, parse_number};
+
+/// How far past a symbol an address is still shown as `symbol+offset`.
+const MAX_OFFSET: u16 = 0x100;
+
+/// Names for addresses, as read from symbol files.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable
+{
+    /// By address, then name.
+    symbols: Vec<(u16, String)>,
+    by_name: HashMap<String, u16>,
+}
+
+impl SymbolTable
+{
+    /// Reads a symbol file. Each line holds `ADDR NAME` pairs in hex, as the assembler, the
+    /// linker and L80 write them, or a `NAME = value` or `NAME EQU value` definition. Blank
+    /// lines and lines starting with `;` or `#` are skipped.
+    pub fn parse(text: &str) -> Result<SymbolTable, String>
+    {
+        let mut table = SymbolTable::default();
+        for (number, line) in text.lines().enumerate()
+        {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#')
+            {
+                continue;
+            }
+            let symbols = parse_line(line).ok_or_else(|| format!("line {}: {} is not a symbol definition", number + 1, line))?;
+            for (name, value) in symbols
+            {
+                table.add(&name, value);
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn load(path: &Path) -> Result<SymbolTable, String>
+    {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        SymbolTable::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Adds a symbol, replacing one with the same name.
+    pub fn add(&mut self, name: &str, value: u16)
+    {
+        let name = name.to_ascii_uppercase();
+        if let Some(old) = self.by_name.insert(name.clone(), value)
+        {
+            self.symbols.retain(|(address, symbol)| !(*address == old && *symbol == name));
+        }
+        let index = self.symbols.partition_point(|(address, symbol)| (*address, symbol) < (value, &name));
+        self.symbols.insert(index, (value, name));
+    }
+
+    /// Adds the symbols of another table.
+    pub fn extend(&mut self, other: &SymbolTable)
+    {
+        for (value, name) in other.symbols.iter()
+        {
+            self.add(name, *value);
+        }
+    }
+
+    /// The symbols and their values, by value.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)>
+    {
+        self.symbols.iter().map(|(value, name)| (name.as_str(), *value))
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.symbols.is_empty()
+    }
+
+    /// The address of a symbol, in any case.
+    pub fn get_address(&self, name: &str) -> Option<u16>
+    {
+        self.by_name.get(&name.to_ascii_uppercase()).copied()
+    }
+
+    /// The first symbol at exactly `address`.
+    pub fn get_name(&self, address: u16) -> Option<&str>
+    {
+        let index = self.symbols.partition_point(|(value, _)| *value < address);
+        match self.symbols.get(index)
+        {
+            Some((value, name)) if *value == address => Some(name),
+            _ => None,
+        }
+    }
+
+    /// `NAME` or `NAME+3` for the closest symbol at or below `address`, if there is one near.
+    pub fn describe(&self, address: u16) -> Option<String>
+    {
+        let index = self.symbols.partition_point(|(value, _)| *value <= address);
+        let value = self.symbols[..index].last()?.0;
+        let name = self.get_name(value)?;
+        match address - value
+        {
+            0 => Some(name.to_string()),
+            offset if offset < MAX_OFFSET => Some(format!("{}+{}", name, offset)),
+            _ => None,
+        }
+    }
+}
+
+/// The symbols a line of a symbol file defines.
+fn parse_line(line: &str) -> Option<Vec<(String, u16)>>
+{
+    let words: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == '=' || c == ':').filter(|word| !word.is_empty()).collect();
+    let value = |word: &str| -> Option<u16>
+    {
+        let number = match word.strip_prefix('$').or_else(|| word.strip_prefix('#'))
+        {
+            Some(hex) => i64::from_str_radix(hex, 16).ok(),
+            None => parse_number(word).ok(),
+        };
+        number.filter(|number| (0..0x10000).contains(number)).map(|number| number as u16)
+    };
+
+    // NAME = value, NAME: EQU value and the like, with anything after the value ignored
+    let definition = line.contains('=') || words.get(1).is_some_and(|word| word.eq_ignore_ascii_case("EQU"));
+    if definition
+    {
+        let name = words.first().filter(|name| is_name(name))?;
+        let operand = if words.get(1)?.eq_ignore_ascii_case("EQU") { words.get(2)? } else { words.get(1)? };
+        return Some(vec![(name.to_string(), value(operand)?)]);
+    }
+
+    // ADDR NAME pairs
+    if !words.len().is_multiple_of(2)
+    {
+        return None;
+    }
+    words.chunks(2).map(|pair|
+    {
+        let address = u16::from_str_radix(pair[0], 16).ok()?;
+        Some((pair[1].to_string(), address)).filter(|(name, _)| is_name(name))
+    }).collect()
+}
@@ -0,0 +1,43 @@
+use remus8080::assembler::assemble;
+use remus8080::coverage::Step;
+use remus8080::i8080::Processor;
+
+/// Two calls deep with SP starting at 0100H: OUTER at 0007H calls INNER at 000BH.
+#[allow(dead_code)]
+pub const NESTED: &str = "
+        LXI  SP,0100H
+        CALL OUTER
+        HLT
+OUTER:  CALL INNER
+        RET
+INNER:  NOP
+        RET";
+
+/// A processor about to run `source`, assembled at 0000H.
+pub fn processor(source: &str) -> Processor
+{
+    Processor::from_bytes(assemble(source).unwrap().to_binary(), 0)
+}
+
+/// Runs one instruction, taking the interrupt `rst` in the same clock if given, and hands
+/// `record` the step before it and the processor after.
+pub fn step<F: FnMut(&Step, &Processor)>(processor: &mut Processor, rst: Option<u8>, mut record: F)
+{
+    let step = Step::new(processor);
+    processor.clock();
+    if let Some(rst) = rst
+    {
+        processor.interrupt(rst);
+    }
+    record(&step, processor);
+}
+
+/// Runs `steps` instructions of `source`, each handed to `record`.
+pub fn run<F: FnMut(&Step, &Processor)>(source: &str, steps: usize, mut record: F)
+{
+    let mut processor = processor(source);
+    for _ in 0..steps
+    {
+        step(&mut processor, None, &mut record);
+    }
+}
@@ -0,0 +1,100 @@
+mod tests
+{
+    use remus8080::i8080::Processor;
+    use remus8080::machines::altair::*;
+    use remus8080::debugger::front_panel::panel_lines;
+
+    #[test]
+    fn examine_and_deposit()
+    {
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut altair = Altair::new();
+
+        altair.set_switches(0x0100);
+        altair.examine(&mut cpu);
+        assert_eq!(cpu.get_pc(), 0x0100);
+        assert_eq!(altair.get_lights().address, 0x0100);
+
+        altair.set_switches(0x3E);
+        altair.deposit(&mut cpu);
+        altair.set_switches(0x42);
+        altair.deposit_next(&mut cpu);
+        assert_eq!(cpu.get_memory_at(0x0100), 0x3E);
+        assert_eq!(cpu.get_memory_at(0x0101), 0x42);
+        assert_eq!(altair.get_lights().address, 0x0101);
+        assert_eq!(altair.get_lights().data, 0x42);
+
+        altair.examine_next(&mut cpu);
+        assert_eq!(altair.get_lights().address, 0x0102);
+        assert_eq!(altair.get_lights().data, 0x00);
+    }
+
+    #[test]
+    fn sense_switches()
+    {
+        // IN 0FFH; OUT 10H
+        let mut cpu = Processor::from_bytes(vec![0xDB, 0xFF, 0xD3, 0x10], 0);
+        let mut altair = Altair::new();
+        altair.set_switches(0xA500);
+
+        altair.single_step(&mut cpu);
+        assert_eq!(cpu.get_registers().accumulator, 0xA5);
+        let lights = altair.get_lights();
+        assert!(lights.status[3]);
+        assert_eq!(lights.address, 0xFFFF);
+        assert_eq!(lights.data, 0xA5);
+
+        altair.single_step(&mut cpu);
+        let lights = altair.get_lights();
+        assert!(lights.status[5]);
+        assert_eq!(lights.address, 0x1010);
+        assert_eq!(cpu.get_pc(), 4);
+    }
+
+    #[test]
+    fn run_stop_and_reset()
+    {
+        // EI; HLT
+        let mut cpu = Processor::from_bytes(vec![0xFB, 0x76], 0);
+        let mut altair = Altair::new();
+
+        altair.run();
+        altair.run_for(&mut cpu, 100);
+        assert!(cpu.get_halted());
+        assert!(!altair.get_lights().wait);
+        assert!(altair.get_lights().status[0]);
+        assert!(altair.get_lights().status[6]);
+
+        // Panel switches do nothing while running
+        altair.set_switches(0x1234);
+        altair.examine(&mut cpu);
+        assert_ne!(cpu.get_pc(), 0x1234);
+
+        altair.stop(&cpu);
+        assert!(altair.get_lights().wait);
+        altair.reset(&mut cpu);
+        assert_eq!(cpu.get_pc(), 0);
+        assert!(!cpu.get_halted());
+        assert!(!altair.get_lights().status[6]);
+        assert_eq!(cpu.get_memory_at(1), 0x76);
+    }
+
+    #[test]
+    fn load_argument()
+    {
+        assert_eq!(parse_load("basic.bin").unwrap(), ("basic.bin".into(), 0));
+        assert_eq!(parse_load("basic.bin@0x1000").unwrap(), ("basic.bin".into(), 0x1000));
+        assert!(parse_load("basic.bin@zz").is_err());
+    }
+
+    #[test]
+    fn panel_shows_switches_and_lights()
+    {
+        let mut altair = Altair::new();
+        altair.set_switches(0x8001);
+        let lines = panel_lines(&altair.get_lights(), altair.get_switches(), 0);
+        assert!(lines[0].contains("INTE"));
+        assert!(lines.iter().any(|line| line.starts_with("SWITCH   ▲  ▼ ▼")));
+        assert!(lines.last().unwrap().ends_with("^ 8001"));
+    }
+}
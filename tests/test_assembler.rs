@@ -0,0 +1,229 @@
+mod tests
+{
+    use std::path::Path;
+    use remus8080::assembler::*;
+    use remus8080::assembler::expression::{evaluate, evaluate_defined};
+    use remus8080::symbols::SymbolTable;
+
+    fn bytes(source: &str) -> Vec<u8>
+    {
+        assemble(source).unwrap().to_binary()
+    }
+
+    #[test]
+    fn cpudiag_matches_shipped_binary()
+    {
+        let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+        let shipped = std::fs::read(roms.join("cpudiag.bin")).unwrap();
+        let assembly = assemble_file(&roms.join("cpudiag_shipped.asm")).unwrap();
+        assert_eq!(assembly.to_binary(), shipped);
+        assert!(assembly.symbols.contains(&("CPU".to_string(), 0x01AB)));
+
+        // The upstream source differs from the binary in two messages and where the stack is
+        let upstream = assemble_file(&roms.join("cpudiag.asm")).unwrap();
+        assert_eq!(upstream.to_binary().len(), 1457);
+        assert!(upstream.to_binary().windows(16).any(|text| text == b"VERSION 1.0  (C)"));
+        assert!(upstream.symbols.contains(&("STACK".to_string(), 0x07A8)));
+        assert!(assembly.symbols.contains(&("STACK".to_string(), 0x07AD)));
+    }
+
+    #[test]
+    fn instructions()
+    {
+        assert_eq!(bytes("\tMOV A,M\n\tMOV M,B\n\tMVI L,0FFH\n\tADD C\n\tCMP M\n\tINR A\n\tDCR M"),
+                   vec![0x7E, 0x70, 0x2E, 0xFF, 0x81, 0xBE, 0x3C, 0x35]);
+        assert_eq!(bytes("\tLXI SP,1234H\n\tPUSH PSW\n\tPOP H\n\tDAD D\n\tINX B\n\tLDAX D\n\tSTAX B"),
+                   vec![0x31, 0x34, 0x12, 0xF5, 0xE1, 0x19, 0x03, 0x1A, 0x02]);
+        assert_eq!(bytes("\tRST 7\n\tIN 10H\n\tOUT 1\n\tCPI 'A'\n\tJNZ 0\n\tCALL 100H\n\tLHLD 5\n\tXTHL\n\tHLT\n\tRPE"),
+                   vec![0xFF, 0xDB, 0x10, 0xD3, 0x01, 0xFE, 0x41, 0xC2, 0x00, 0x00, 0xCD, 0x00, 0x01, 0x2A, 0x05, 0x00,
+                        0xE3, 0x76, 0xE8]);
+    }
+
+    #[test]
+    fn lowercase_labels_and_directives()
+    {
+        let source = "\
+        org 100h
+start:  lxi h,message
+count   equ 3
+        mvi b,count
+loop:   dcr b
+        jnz loop
+        jmp $
+message db 'It''s', 0dh, \"ok\", 0
+table:  dw start, loop+1
+        ds 2
+value   set 1
+value   set value+1
+        db value, high table, low table
+        end start
+        db 99";
+        let assembly = assemble(source).unwrap();
+        assert_eq!(assembly.image.start(), Some(0x100));
+        assert_eq!(assembly.image.entry, Some(0x100));
+        assert_eq!(assembly.to_binary(), vec![
+            0x21, 0x0C, 0x01, 0x06, 0x03, 0x05, 0xC2, 0x05, 0x01, 0xC3, 0x09, 0x01,
+            b'I', b't', b'\'', b's', 0x0D, b'o', b'k', 0x00, 0x00, 0x01, 0x06, 0x01, 0x00, 0x00,
+            0x02, 0x01, 0x14,
+        ]);
+        assert!(assembly.symbols.contains(&("MESSAGE".to_string(), 0x10C)));
+        assert!(assembly.symbols.contains(&("VALUE".to_string(), 2)));
+        assert_eq!(assembly.end, 0x11D);
+    }
+
+    #[test]
+    fn expressions()
+    {
+        let lookup = |name: &str| if name == "TEN" { Some(10) } else { None };
+        let value = |text: &str| evaluate(text, 0x100, &lookup).unwrap().unwrap();
+        assert_eq!(value("1 + 2 * 3"), 7);
+        assert_eq!(value("(1 + 2) * 3"), 9);
+        assert_eq!(value("$ + ten"), 0x10A);
+        assert_eq!(value("0FFH AND 0FH SHL 2"), 0x3C);
+        assert_eq!(value("1 OR 2 AND 3"), 3);
+        assert_eq!(value("NOT 0"), 0xFFFF);
+        assert_eq!(value("HIGH 1234H + LOW 1234H"), 0x46);
+        assert_eq!(value("17 MOD 5 + 1010B + 17Q"), 2 + 10 + 15);
+        assert_eq!(value("TEN EQ 10"), 0xFFFF);
+        assert_eq!(value("'AB' - 'A'"), 0x4142 - 0x41);
+        assert_eq!(value("-1"), -1);
+        assert_eq!(value("0x10 << 1 | 1"), 0x21);
+        assert_eq!(evaluate("LATER + 1", 0, &lookup).unwrap(), None);
+        assert_eq!(evaluate_defined("LATER + 1", 0, &lookup).unwrap_err(), "undefined symbol LATER");
+        assert!(evaluate("(1 + 2", 0, &lookup).is_err());
+        assert!(evaluate("12G", 0, &lookup).is_err());
+        assert_eq!(evaluate("1 SHL -1", 0, &lookup).unwrap_err(), "negative shift count -1");
+        assert_eq!(evaluate("1 SHR -1", 0, &lookup).unwrap_err(), "negative shift count -1");
+        assert_eq!(value("7FFFFFFFFFFFFFFFH + 1"), i64::MIN);
+        assert_eq!(value("-(7FFFFFFFFFFFFFFFH + 1) / -1 * 2"), 0);
+    }
+
+    #[test]
+    fn errors_name_the_line()
+    {
+        assert_eq!(assemble("\tNOP\n\tFOO A").unwrap_err(), "line 2: unknown instruction FOO");
+        assert_eq!(assemble("\tJMP NOWHERE").unwrap_err(), "line 1: undefined symbol NOWHERE");
+        assert_eq!(assemble("X:\tNOP\nX:\tNOP").unwrap_err(), "line 2: X is defined twice");
+        assert_eq!(assemble("\tMOV M,M").unwrap_err(), "line 1: MOV M,M is not an instruction");
+        assert!(assemble("\tMVI A,100H").unwrap_err().contains("does not fit in a byte"));
+        assert!(assemble("\tPUSH SP").is_err());
+        assert!(assemble("\tMOV A").is_err());
+        assert!(assemble("\tDS LATER\nLATER\tEQU 1").is_err());
+        assert!(assemble("\tORG 0FFFFH\n\tDW 0").is_err());
+    }
+
+    #[test]
+    fn listing_and_symbols()
+    {
+        let assembly = assemble("\tORG 100H\nFIVE\tEQU 5\nGO:\tJMP GO ; loop\n\tDB 1,2,3,4,5\n").unwrap();
+        assert_eq!(assembly.write_listing(), "\
+0100                 1  \tORG 100H
+     = 0005          2  FIVE\tEQU 5
+0100 C3 00 01        3  GO:\tJMP GO ; loop
+0103 01 02 03 04     4  \tDB 1,2,3,4,5
+0107 05
+");
+        assert_eq!(assembly.write_symbols(), "0005 FIVE\n0100 GO\n");
+    }
+
+    #[test]
+    fn macros_with_parameters_and_locals()
+    {
+        let source = "\
+print   macro   text, port
+        local   loop, done
+        lxi     h,text
+loop:   mov     a,m
+        ora     a
+        jz      done
+        out     port
+        inx     h
+        jmp     loop
+done:
+        endm
+        org     100h
+        print   hello, 1
+        print   <hello+1>, 0
+hello:  db      'Hi', 0
+        end";
+        let assembly = assemble(source).unwrap();
+        assert_eq!(assembly.to_binary(), vec![
+            0x21, 0x1C, 0x01, 0x7E, 0xB7, 0xCA, 0x0E, 0x01, 0xD3, 0x01, 0x23, 0xC3, 0x03, 0x01,
+            0x21, 0x1D, 0x01, 0x7E, 0xB7, 0xCA, 0x1C, 0x01, 0xD3, 0x00, 0x23, 0xC3, 0x11, 0x01,
+            b'H', b'i', 0x00,
+        ]);
+        assert!(!assembly.symbols.iter().any(|(name, _)| name.starts_with("??")));
+        assert!(assembly.write_listing().contains("0100 21 1C 01        3+         lxi     h,hello"));
+    }
+
+    #[test]
+    fn repeats_and_conditionals()
+    {
+        let source = "\
+DEBUG   EQU     1
+COUNT   SET     0
+        REPT    3
+COUNT   SET     COUNT+1
+        DB      COUNT
+        ENDM
+        IRP     REG,<B,C,D>
+        INR     REG
+        ENDM
+        IF      DEBUG
+        IF      COUNT EQ 3
+        DB      0AAH
+        ELSE
+        DB      0BBH
+        ENDIF
+        ELSE
+        DB      0CCH
+        ENDIF
+TWICE   MACRO   X
+        IF      X GT 9
+        EXITM
+        ENDIF
+        DB      X, X
+        ENDM
+        TWICE   5
+        TWICE   10
+        DB      '&'";
+        assert_eq!(bytes(source), vec![1, 2, 3, 0x04, 0x0C, 0x14, 0xAA, 5, 5, b'&']);
+    }
+
+    #[test]
+    fn includes_and_error_locations()
+    {
+        let dir = std::env::temp_dir().join(format!("remus8080_asm_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("lib/defs.asm"), "BDOS\tEQU\t5\nSAY\tMACRO\tC\n\tMVI\tE,C\n\tBOGUS\nENDM\n").unwrap();
+        std::fs::write(dir.join("main.asm"), "\tINCLUDE\tlib/defs.asm\n\tCALL\tBDOS\n\tSAY\t'x'\n").unwrap();
+
+        let error = assemble_file(&dir.join("main.asm")).unwrap_err();
+        let defs = dir.join("lib").join("defs.asm");
+        let main = dir.join("main.asm");
+        assert_eq!(error, format!("{}: line 4: unknown instruction BOGUS, in SAY at {}: line 3", defs.display(), main.display()));
+
+        std::fs::write(dir.join("lib/defs.asm"), "BDOS\tEQU\t5\nSAY\tMACRO\tC\n\tMVI\tE,C\n\tENDM\n").unwrap();
+        assert_eq!(assemble_file(&main).unwrap().to_binary(), vec![0xCD, 0x05, 0x00, 0x1E, b'x']);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(assemble("\tIF 1\n\tNOP").unwrap_err(), "line 1: no ENDIF for this IF");
+        assert_eq!(assemble("M\tMACRO\n\tNOP").unwrap_err(), "line 1: no ENDM for this block");
+        assert_eq!(assemble("\tENDIF").unwrap_err(), "line 1: ENDIF without IF");
+        assert_eq!(assemble("\tIF LATER\n\tENDIF\nLATER\tEQU 1").unwrap_err(), "line 1: undefined symbol LATER");
+        assert!(assemble("R\tMACRO\n\tR\n\tENDM\n\tR").unwrap_err().contains("nest more than"));
+    }
+
+    #[test]
+    fn single_instructions()
+    {
+        let none = SymbolTable::default();
+        assert_eq!(assemble_instruction("mvi a,'x'", 0x100, &none).unwrap(), vec![0x3E, b'x']);
+        assert_eq!(assemble_instruction("  JMP $+3 ; skip", 0x200, &none).unwrap(), vec![0xC3, 0x03, 0x02]);
+        assert_eq!(assemble_instruction("DW 1234H", 0, &none).unwrap(), vec![0x34, 0x12]);
+        assert_eq!(assemble_instruction("ORG 100H", 0, &none).unwrap_err(), "unknown instruction ORG");
+        assert_eq!(assemble_instruction("CALL PRINT", 0, &none).unwrap_err(), "undefined symbol PRINT");
+        let symbols = SymbolTable::parse("0109 print").unwrap();
+        assert_eq!(assemble_instruction("CALL PRINT+1", 0, &symbols).unwrap(), vec![0xCD, 0x0A, 0x01]);
+    }
+}
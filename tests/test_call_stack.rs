@@ -0,0 +1,136 @@
+mod common;
+
+mod tests
+{
+    use super::common::{self, processor, NESTED};
+    use remus8080::call_stack::*;
+    use remus8080::i8080::Processor;
+
+    fn step(processor: &mut Processor, call_stack: &mut CallStack, interrupt: Option<u8>)
+    {
+        common::step(processor, interrupt, |step, processor| call_stack.update(step, processor));
+    }
+
+    fn run(source: &str, steps: usize) -> CallStack
+    {
+        let mut call_stack = CallStack::default();
+        common::run(source, steps, |step, processor| call_stack.update(step, processor));
+        call_stack
+    }
+
+    #[test]
+    fn follows_calls_and_returns()
+    {
+        let call_stack = run(NESTED, 4);
+        assert_eq!(call_stack.get_frames(), &[
+            Frame { entry: 0x0007, return_address: 0x0006, stack_pointer: 0x00FE, interrupt: false },
+            Frame { entry: 0x000B, return_address: 0x000A, stack_pointer: 0x00FC, interrupt: false },
+        ]);
+        let call_stack = run(NESTED, 6);
+        assert_eq!(call_stack.get_frames().len(), 0);
+        assert!(call_stack.get_mismatches().is_empty());
+    }
+
+    #[test]
+    fn follows_interrupts()
+    {
+        let mut processor = processor("
+        JMP  START
+        ORG  8
+        RET
+        ORG  10H
+START:  LXI  SP,0100H
+        EI
+        NOP");
+        let mut call_stack = CallStack::default();
+        for interrupt in [None, None, None, Some(1)]
+        {
+            step(&mut processor, &mut call_stack, interrupt);
+        }
+        assert_eq!(call_stack.get_frames(), &[Frame { entry: 0x0008, return_address: 0x0015, stack_pointer: 0x00FE, interrupt: true }]);
+        step(&mut processor, &mut call_stack, None);
+        assert!(call_stack.get_frames().is_empty());
+        assert!(call_stack.get_mismatches().is_empty());
+    }
+
+    #[test]
+    fn follows_interrupts_taken_with_a_push()
+    {
+        let source = "
+        JMP  START
+        ORG  8
+        RET
+        ORG  10H
+START:  LXI  SP,0100H
+        EI
+        {}
+        HLT
+SUB:    RET";
+        // Stepped with the interrupt taken in the same clock as the instruction
+        let start = |instruction: &str|
+        {
+            let mut processor = processor(&source.replace("{}", instruction));
+            let mut call_stack = CallStack::default();
+            for interrupt in [None, None, None, Some(1)]
+            {
+                step(&mut processor, &mut call_stack, interrupt);
+            }
+            (processor, call_stack)
+        };
+        let (mut processor, mut call_stack) = start("CALL SUB");
+        assert_eq!(call_stack.get_frames(), &[
+            Frame { entry: 0x0018, return_address: 0x0017, stack_pointer: 0x00FE, interrupt: false },
+            Frame { entry: 0x0008, return_address: 0x0018, stack_pointer: 0x00FC, interrupt: true },
+        ]);
+        step(&mut processor, &mut call_stack, None);
+        step(&mut processor, &mut call_stack, None);
+        assert!(call_stack.get_frames().is_empty());
+        assert!(call_stack.get_mismatches().is_empty());
+
+        let (_, call_stack) = start("PUSH B");
+        assert_eq!(call_stack.get_frames(), &[Frame { entry: 0x0008, return_address: 0x0015, stack_pointer: 0x00FC, interrupt: true }]);
+    }
+
+    #[test]
+    fn flags_stack_tricks()
+    {
+        let call_stack = run("
+        LXI  SP,0100H
+        LXI  H,0
+        CALL SKIP
+        DB   0
+        HLT
+SKIP:   XTHL
+        INX  H
+        XTHL
+        RET", 7);
+        assert!(call_stack.get_frames().is_empty());
+        let messages: Vec<&str> = call_stack.get_mismatches().iter().map(|mismatch| mismatch.message.as_str()).collect();
+        assert_eq!(messages, vec!["XTHL swapped return address 0009 for 0000", "XTHL swapped return address 0000 for 000A"]);
+
+        let call_stack = run("
+        LXI  SP,0100H
+        CALL SUB
+        HLT
+SUB:    LXI  H,0100H
+        SPHL", 4);
+        assert!(call_stack.get_frames().is_empty());
+        assert_eq!(call_stack.get_mismatches(), &[Mismatch { address: 0x000A, message: "SPHL dropped return address 0006".to_string() }]);
+
+        let call_stack = run("
+        LXI  SP,0100H
+        CALL SUB
+        HLT
+SUB:    LXI  H,0
+        PUSH H
+        RET", 5);
+        assert_eq!(call_stack.get_frames().len(), 1);
+        let call_stack = run("
+        LXI  SP,0100H
+        CALL SUB
+        HLT
+SUB:    POP  H
+        PCHL", 3);
+        assert_eq!(call_stack.get_mismatches()[0].message, "POP dropped return address 0006");
+    }
+}
@@ -0,0 +1,82 @@
+mod common;
+
+mod tests
+{
+    use super::common;
+    use remus8080::assembler::assemble;
+    use remus8080::coverage::*;
+    use remus8080::disassembler::traversal::disassemble_with_hints;
+    use remus8080::symbols::SymbolTable;
+
+    fn run(source: &str, steps: usize) -> Coverage
+    {
+        let mut coverage = Coverage::default();
+        common::run(source, steps, |step, processor| coverage.record(step, processor));
+        coverage
+    }
+
+    const PROGRAM: &str = "
+        LXI  SP,0100H
+        LXI  H,VALUE
+        MOV  A,M
+        STA  COPY
+        CALL DONE
+        HLT
+DONE:   RET
+VALUE:  DB   5
+COPY:   DB   0
+        DB   0";
+
+    #[test]
+    fn records_how_bytes_are_used()
+    {
+        let coverage = run(PROGRAM, 7);
+        assert_eq!(coverage.get(0x0000), OPCODE);
+        assert_eq!(coverage.get(0x0001), OPERAND);
+        assert_eq!(coverage.get(0x0006), OPCODE);
+        assert_eq!(coverage.get(0x000E), OPCODE);
+        assert_eq!(coverage.get(0x000F), READ);
+        assert_eq!(coverage.get(0x0010), WRITTEN);
+        assert_eq!(coverage.get(0x0011), 0);
+        assert_eq!(coverage.get(0x00FE), WRITTEN | READ);
+        assert_eq!(coverage.get(0x00FF), WRITTEN | READ);
+
+        let [executed, read, written, unused] = coverage.percentages(0x0D, 0x10);
+        assert_eq!((executed, read, written, unused), (50.0, 25.0, 25.0, 0.0));
+    }
+
+    #[test]
+    fn saves_and_merges_runs()
+    {
+        let coverage = run(PROGRAM, 3);
+        let text = coverage.to_text();
+        assert!(text.starts_with("0000-0000 X\n0001-0002 O\n0003-0003 X\n"));
+        assert!(text.ends_with("0006-0006 X\n000F-000F R\n"));
+        assert_eq!(Coverage::parse(&text).unwrap(), coverage);
+        assert!(Coverage::parse("0100-0102 Q").is_err());
+
+        let mut merged = Coverage::parse("000F W\n; more\n0020-0021 RW").unwrap();
+        merged.merge(&coverage);
+        assert_eq!(merged.get(0x000F), READ | WRITTEN);
+        assert_eq!(merged.get(0x0021), READ | WRITTEN);
+        assert_eq!(merged.get(0x0006), OPCODE);
+    }
+
+    #[test]
+    fn hints_tell_code_from_data()
+    {
+        // 0000 LXI H,0006H; PCHL; DB 3AH,3EH; 0006 MVI A,1; RET, with 0004 taken for an entry
+        let binary = [0x21, 0x06, 0x00, 0xE9, 0x3A, 0x3E, 0x3E, 0x01, 0xC9];
+        let instructions = |hints: &Coverage|
+        {
+            let program = disassemble_with_hints(&binary, 0, &[0, 4], &SymbolTable::default(), hints);
+            program.instructions.keys().copied().collect::<Vec<u16>>()
+        };
+        assert_eq!(instructions(&Coverage::default()), vec![0x0000, 0x0003, 0x0004]);
+
+        let hints = Coverage::parse("0004-0005 R\n0006 X\n0007 O\n0008 X").unwrap();
+        assert_eq!(instructions(&hints), vec![0x0000, 0x0003, 0x0006, 0x0008]);
+        let with = disassemble_with_hints(&binary, 0, &[0, 4], &SymbolTable::default(), &hints);
+        assert_eq!(assemble(&with.to_source()).unwrap().to_binary(), binary.to_vec());
+    }
+}
@@ -0,0 +1,463 @@
+mod tests
+{
+    use remus8080::cpm::Cpm;
+    use remus8080::i8080::Processor;
+
+    fn run(program: &[u8], input: &[u8]) -> String
+    {
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut cpm = Cpm::new(input, Vec::new());
+        cpm.load(&mut cpu, program);
+        while cpm.clock(&mut cpu) {}
+        String::from_utf8_lossy(cpm.output()).to_string()
+    }
+
+    #[test]
+    fn cpudiag()
+    {
+        let program = std::fs::read("roms/cpudiag.bin").unwrap();
+        let output = run(&program, b"");
+        assert!(output.contains("CPU IS OPERATIONAL"), "{}", output);
+    }
+
+    #[test]
+    fn print_string_and_warm_boot()
+    {
+        // LXI D,0x010C; MVI C,9; CALL 5; JMP 0; "hi$"
+        let program = [0x11, 0x0C, 0x01, 0x0E, 0x09, 0xCD, 0x05, 0x00, 0xC3, 0x00, 0x00, 0x00,
+                       b'h', b'i', b'$'];
+        assert_eq!(run(&program, b""), "hi");
+    }
+
+    #[test]
+    fn console_input_echoes()
+    {
+        // MVI C,1; CALL 5; MOV E,A; MVI C,2; CALL 5; RET
+        let program = [0x0E, 0x01, 0xCD, 0x05, 0x00, 0x5F, 0x0E, 0x02, 0xCD, 0x05, 0x00, 0xC9];
+        assert_eq!(run(&program, b"x"), "xx");
+    }
+}
+
+mod files
+{
+    use remus8080::cpm::Cpm;
+    use remus8080::i8080::Processor;
+    use std::path::PathBuf;
+
+    const FCB: u16 = 0x5C;
+    const DMA: u16 = 0x80;
+
+    fn temp_dir(name: &str) -> PathBuf
+    {
+        let dir = std::env::temp_dir().join(format!("remus8080_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn setup(dir: &PathBuf) -> (Processor, Cpm<&'static [u8], Vec<u8>>)
+    {
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut cpm = Cpm::new(&b""[..], Vec::new());
+        cpm.mount(0, dir.clone());
+        // Loop forever at 0x100 between BDOS calls
+        cpm.load(&mut cpu, &[0xC3, 0x00, 0x01]);
+        (cpu, cpm)
+    }
+
+    /// Calls BDOS function `c` with `de` and returns A.
+    fn bdos(cpu: &mut Processor, cpm: &mut Cpm<&'static [u8], Vec<u8>>, c: u8, de: u16) -> u8
+    {
+        let mut regs = cpu.get_registers();
+        regs.c = c;
+        regs.d = (de >> 8) as u8;
+        regs.e = de as u8;
+        cpu.set_all_registers(regs);
+
+        let sp = cpu.get_stack_pointer() - 2;
+        cpu.set_memory_at(sp, 0x00);
+        cpu.set_memory_at(sp + 1, 0x01);
+        cpu.set_stack_pointer(sp);
+        cpu.set_pc(0x0005);
+        while cpu.get_pc() != 0x0100
+        {
+            assert!(cpm.clock(cpu));
+        }
+        cpu.get_registers().accumulator
+    }
+
+    fn set_fcb(cpu: &mut Processor, name: &[u8; 11])
+    {
+        cpu.load_bytes_at(FCB, &[0; 36]);
+        cpu.load_bytes_at(FCB + 1, name);
+    }
+
+    #[test]
+    fn make_write_read()
+    {
+        let dir = temp_dir("make_write_read");
+        let (mut cpu, mut cpm) = setup(&dir);
+
+        set_fcb(&mut cpu, b"HELLO   TXT");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 22, FCB), 0);
+        for record in 0..3u8
+        {
+            cpu.load_bytes_at(DMA, &[b'a' + record; 128]);
+            assert_eq!(bdos(&mut cpu, &mut cpm, 21, FCB), 0);
+        }
+        assert_eq!(bdos(&mut cpu, &mut cpm, 16, FCB), 0);
+
+        let contents = std::fs::read(dir.join("hello.txt")).unwrap();
+        assert_eq!(contents.len(), 384);
+        assert_eq!(contents[200], b'b');
+
+        set_fcb(&mut cpu, b"HELLO   TXT");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 15, FCB), 0);
+        assert_eq!(cpu.get_memory_at(FCB + 15), 3);
+        assert_eq!(bdos(&mut cpu, &mut cpm, 20, FCB), 0);
+        assert_eq!(cpu.get_memory_at(DMA), b'a');
+
+        // Random read of record 2, then sequential continues from there
+        cpu.set_memory_at(FCB + 33, 2);
+        assert_eq!(bdos(&mut cpu, &mut cpm, 33, FCB), 0);
+        assert_eq!(cpu.get_memory_at(DMA), b'c');
+        assert_eq!(bdos(&mut cpu, &mut cpm, 20, FCB), 0);
+        assert_eq!(bdos(&mut cpu, &mut cpm, 20, FCB), 1);
+
+        assert_eq!(bdos(&mut cpu, &mut cpm, 35, FCB), 0);
+        assert_eq!(cpu.get_memory_at(FCB + 33), 3);
+    }
+
+    #[test]
+    fn partial_record_is_padded()
+    {
+        let dir = temp_dir("partial_record");
+        std::fs::write(dir.join("Short.Txt"), b"abc").unwrap();
+        let (mut cpu, mut cpm) = setup(&dir);
+
+        set_fcb(&mut cpu, b"SHORT   TXT");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 15, FCB), 0);
+        assert_eq!(bdos(&mut cpu, &mut cpm, 20, FCB), 0);
+        assert_eq!(cpu.get_memory_at(DMA + 2), b'c');
+        assert_eq!(cpu.get_memory_at(DMA + 3), 0x1A);
+    }
+
+    #[test]
+    fn search_rename_delete()
+    {
+        let dir = temp_dir("search_rename_delete");
+        std::fs::write(dir.join("one.asm"), b"1").unwrap();
+        std::fs::write(dir.join("two.asm"), b"2").unwrap();
+        std::fs::write(dir.join("three.com"), b"3").unwrap();
+        std::fs::write(dir.join("not_eight_three.asm"), b"4").unwrap();
+        let (mut cpu, mut cpm) = setup(&dir);
+
+        set_fcb(&mut cpu, b"????????ASM");
+        let mut found = Vec::new();
+        let mut result = bdos(&mut cpu, &mut cpm, 17, FCB);
+        while result != 0xFF
+        {
+            let entry: Vec<u8> = (0..11).map(|i| cpu.get_memory_at(DMA + 1 + i)).collect();
+            found.push(String::from_utf8(entry).unwrap());
+            result = bdos(&mut cpu, &mut cpm, 18, FCB);
+        }
+        assert_eq!(found, vec!["ONE     ASM", "TWO     ASM"]);
+
+        set_fcb(&mut cpu, b"ONE     ASM");
+        cpu.load_bytes_at(FCB + 17, b"UNO     ASM");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 23, FCB), 0);
+        assert!(dir.join("uno.asm").exists());
+
+        set_fcb(&mut cpu, b"????????ASM");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 19, FCB), 0);
+        assert!(!dir.join("uno.asm").exists());
+        assert!(!dir.join("two.asm").exists());
+        assert!(dir.join("three.com").exists());
+
+        set_fcb(&mut cpu, b"MISSING TXT");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 15, FCB), 0xFF);
+    }
+
+    #[test]
+    fn names_stay_in_the_drive()
+    {
+        let dir = temp_dir("names_stay_in_the_drive");
+        std::fs::write(dir.join("one.asm"), b"1").unwrap();
+        let (mut cpu, mut cpm) = setup(&dir);
+
+        for name in [b"../../AB   ", b"AB/CD   TXT", b"AB\\CD   TXT", b"AB\x01     TXT"]
+        {
+            set_fcb(&mut cpu, name);
+            assert_eq!(bdos(&mut cpu, &mut cpm, 22, FCB), 0xFF);
+        }
+        set_fcb(&mut cpu, b"ONE     ASM");
+        cpu.load_bytes_at(FCB + 17, b"../ONE  ASM");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 23, FCB), 0xFF);
+        let names: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, vec!["one.asm"]);
+    }
+
+    #[test]
+    fn buffers_wrap_around_the_top_of_memory()
+    {
+        let dir = temp_dir("buffers_wrap");
+        std::fs::write(dir.join("short.txt"), b"abc").unwrap();
+        let (mut cpu, mut cpm) = setup(&dir);
+
+        // Wrapping only into 0000H to 0003H, clear of the BDOS entry the calls return through
+        bdos(&mut cpu, &mut cpm, 26, 0xFF84);
+        set_fcb(&mut cpu, b"SHORT   TXT");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 15, FCB), 0);
+        assert_eq!(bdos(&mut cpu, &mut cpm, 20, FCB), 0);
+        assert_eq!(cpu.get_memory_at(0xFF86), b'c');
+        assert_eq!(cpu.get_memory_at(0x0003), 0x1A);
+
+        bdos(&mut cpu, &mut cpm, 26, 0xFFE4);
+        set_fcb(&mut cpu, b"SHORT   TXT");
+        assert_eq!(bdos(&mut cpu, &mut cpm, 17, FCB), 0);
+        assert_eq!(cpu.get_memory_at(0xFFE5), b'S');
+        assert_eq!(cpu.get_memory_at(0x0000), 0);
+
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut cpm = Cpm::new(&b"abc\r"[..], Vec::new());
+        cpm.load(&mut cpu, &[0xC3, 0x00, 0x01]);
+        cpu.set_memory_at(0xFFFE, 8);
+        assert_eq!(bdos(&mut cpu, &mut cpm, 10, 0xFFFE), 0);
+        assert_eq!(cpu.get_memory_at(0xFFFF), 3);
+        assert_eq!(cpu.get_memory_at(0x0002), b'c');
+    }
+}
+
+mod fcb
+{
+    use remus8080::cpm::fcb::Fcb;
+    use remus8080::i8080::Processor;
+
+    #[test]
+    fn wraps_around_the_top_of_memory()
+    {
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let fcb = Fcb::at(0xFFF0);
+        fcb.set_random_record(&mut cpu, 0x1234);
+        assert_eq!(fcb.random_record(&cpu), 0x1234);
+        assert_eq!(cpu.get_memory_at(0x0011), 0x34);
+
+        fcb.set_sequential_record(&mut cpu, 300);
+        assert_eq!(fcb.sequential_record(&cpu), 300);
+        assert_eq!(cpu.get_memory_at(0x0010), (300 % 128) as u8);
+    }
+}
+
+mod bios
+{
+    use remus8080::console::BufferConsole;
+    use remus8080::cpm::bios::{detect_ccp_base, Bios};
+    use remus8080::cpm::disk::{DiskDefinition, DiskImage};
+    use remus8080::i8080::Processor;
+
+    const CCP_BASE: u16 = 0xE400;
+
+    /// Builds an IBM 3740 image whose boot tracks hold a "CCP" at 0xE400 running `program`.
+    fn system_disk(program: &[u8]) -> Vec<u8>
+    {
+        let definition = DiskDefinition::ibm_3740();
+        let mut data = vec![0xE5; definition.image_size()];
+        let ccp = 128;
+        data[ccp..ccp + 3].copy_from_slice(&[0xC3, 0x5C, 0xE7]);
+        data[ccp + 0x35C..ccp + 0x35C + program.len()].copy_from_slice(program);
+        data
+    }
+
+    #[test]
+    fn skew_and_parameter_block()
+    {
+        let definition = DiskDefinition::ibm_3740();
+        let physical: Vec<u16> = definition.skew_table().iter().map(|s| s + 1).collect();
+        assert_eq!(physical, vec![1, 7, 13, 19, 25, 5, 11, 17, 23, 3, 9, 15, 21,
+                                  2, 8, 14, 20, 26, 6, 12, 18, 24, 4, 10, 16, 22]);
+        assert_eq!(definition.parameter_block(),
+                   [26, 0, 3, 7, 0, 242, 0, 63, 0, 0xC0, 0, 16, 0, 2, 0]);
+        assert_eq!(definition.record_offset(2, 1), (2 * 26 + 6) * 128);
+    }
+
+    #[test]
+    fn boot_and_read_sector()
+    {
+        let bios_base: u16 = CCP_BASE + 0x1600;
+        let call = |entry: u16| [0xCD, (bios_base + entry * 3) as u8, ((bios_base + entry * 3) >> 8) as u8];
+        let mut program = Vec::new();
+        program.extend_from_slice(&[0x0E, b'A']);                      // MVI C,'A'
+        program.extend_from_slice(&call(4));                           // CONOUT
+        program.extend_from_slice(&[0x0E, 0x00]);                      // MVI C,0
+        program.extend_from_slice(&call(9));                           // SELDSK
+        program.extend_from_slice(&[0x01, 0x02, 0x00]);                // LXI B,2
+        program.extend_from_slice(&call(10));                          // SETTRK
+        program.extend_from_slice(&[0x01, 0x01, 0x00]);                // LXI B,1
+        program.extend_from_slice(&call(11));                          // SETSEC
+        program.extend_from_slice(&[0x01, 0x00, 0x20]);                // LXI B,0x2000
+        program.extend_from_slice(&call(12));                          // SETDMA
+        program.extend_from_slice(&call(13));                          // READ
+        program.extend_from_slice(&[0x3A, 0x00, 0x20, 0x4F]);          // LDA 0x2000, MOV C,A
+        program.extend_from_slice(&call(4));                           // CONOUT
+        program.extend_from_slice(&call(3));                           // CONIN
+        program.extend_from_slice(&[0x4F]);                            // MOV C,A
+        program.extend_from_slice(&call(4));                           // CONOUT
+        program.extend_from_slice(&call(3));                           // CONIN, closes the console
+
+        let mut data = system_disk(&program);
+        let definition = DiskDefinition::ibm_3740();
+        let offset = definition.record_offset(2, 1);
+        data[offset] = b'Z';
+
+        assert_eq!(detect_ccp_base(&data[128..]), Some(CCP_BASE));
+
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut bios = Bios::new(BufferConsole::new(b"q"));
+        bios.insert(0, DiskImage::from_bytes(data, definition));
+        bios.boot(&mut cpu, None).unwrap();
+
+        assert_eq!(cpu.get_pc(), CCP_BASE);
+        assert_eq!(cpu.get_memory_at(0x0005), 0xC3);
+        assert_eq!(cpu.get_memory_at(0x0006), 0x06);
+        assert_eq!(cpu.get_memory_at(0x0007), 0xEC);
+
+        let mut steps = 0;
+        while bios.clock(&mut cpu)
+        {
+            steps += 1;
+            assert!(steps < 10000);
+        }
+        assert_eq!(bios.console().output, b"AZq");
+    }
+
+    #[test]
+    fn read_wraps_around_the_top_of_memory()
+    {
+        let bios_base: u16 = CCP_BASE + 0x1600;
+        let call = |entry: u16| [0xCD, (bios_base + entry * 3) as u8, ((bios_base + entry * 3) >> 8) as u8];
+        let mut program = Vec::new();
+        program.extend_from_slice(&[0x31, 0x00, 0x20]);                // LXI SP,0x2000
+        program.extend_from_slice(&[0x01, 0x02, 0x00]);                // LXI B,2
+        program.extend_from_slice(&call(10));                          // SETTRK
+        program.extend_from_slice(&[0x01, 0x01, 0x00]);                // LXI B,1
+        program.extend_from_slice(&call(11));                          // SETSEC
+        program.extend_from_slice(&[0x01, 0xC0, 0xFF]);                // LXI B,0xFFC0
+        program.extend_from_slice(&call(12));                          // SETDMA
+        program.extend_from_slice(&call(13));                          // READ
+        program.extend_from_slice(&call(3));                           // CONIN, closes the console
+
+        let mut data = system_disk(&program);
+        let definition = DiskDefinition::ibm_3740();
+        let offset = definition.record_offset(2, 1);
+        data[offset] = b'A';
+        data[offset + 127] = b'Z';
+
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut bios = Bios::new(BufferConsole::new(b""));
+        bios.insert(0, DiskImage::from_bytes(data, definition));
+        bios.boot(&mut cpu, None).unwrap();
+        while bios.clock(&mut cpu) {}
+        assert_eq!(cpu.get_memory_at(0xFFC0), b'A');
+        assert_eq!(cpu.get_memory_at(0x003F), b'Z');
+    }
+
+    #[test]
+    fn boot_without_system_fails()
+    {
+        let definition = DiskDefinition::ibm_3740();
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut bios = Bios::new(BufferConsole::new(b""));
+        bios.insert(0, DiskImage::formatted(definition));
+        assert!(bios.boot(&mut cpu, None).is_err());
+    }
+
+    #[test]
+    fn boot_above_the_top_of_memory_fails()
+    {
+        let definition = DiskDefinition::ibm_3740();
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut bios = Bios::new(BufferConsole::new(b""));
+        bios.insert(0, DiskImage::from_bytes(system_disk(&[]), definition));
+        assert_eq!(bios.boot(&mut cpu, Some(0xF000)).unwrap_err(), "The CCP at F000H leaves no room for the BIOS, it must be at or below E94DH");
+    }
+}
+
+mod disk_image
+{
+    use remus8080::cpm::disk::{DiskDefinition, DiskImage};
+    use remus8080::cpm::filesystem::{parse_user_file_name, FileSystem};
+
+    #[test]
+    fn write_read_delete()
+    {
+        let mut image = DiskImage::formatted(DiskDefinition::ibm_3740());
+        let mut filesystem = FileSystem::new(&mut image);
+        let free = filesystem.free_blocks();
+        assert_eq!(free, 243 - 2);
+
+        let data: Vec<u8> = (0..40000).map(|i| (i % 251) as u8).collect();
+        let (user, name) = parse_user_file_name("big.bin").unwrap();
+        filesystem.write_file(user, &name, &data).unwrap();
+        let (user, name) = parse_user_file_name("5:small.txt").unwrap();
+        filesystem.write_file(user, &name, b"hello").unwrap();
+        assert_eq!(filesystem.write_file(16, &name, b"hello"), Err("User number 16 is out of range".to_string()));
+
+        let files = filesystem.files();
+        assert_eq!(files.len(), 2);
+        assert_eq!(&files[0].name, b"BIG     BIN");
+        assert_eq!(files[0].records, 313);
+        assert_eq!(files[0].blocks.len(), 40);
+        assert_eq!(files[1].user, 5);
+        assert_eq!(filesystem.free_blocks(), free - 41);
+
+        let read = filesystem.read_file(&files[0]);
+        assert_eq!(read.len(), 313 * 128);
+        assert_eq!(&read[..40000], &data[..]);
+        assert_eq!(read[40000], 0x1A);
+
+        let (_, pattern) = parse_user_file_name("*.*").unwrap();
+        assert_eq!(filesystem.find(0, &pattern).len(), 1);
+        assert_eq!(filesystem.delete(0, &pattern), 1);
+        assert_eq!(filesystem.files().len(), 1);
+        assert_eq!(filesystem.free_blocks(), free - 1);
+    }
+
+    #[test]
+    fn directory_entries_match_cpm()
+    {
+        let mut image = DiskImage::formatted(DiskDefinition::ibm_3740());
+        let data = vec![0u8; 40000];
+        FileSystem::new(&mut image).write_file(0, b"BIG     BIN", &data).unwrap();
+
+        // The directory starts on track 2, entries 0-2 describe the three extents
+        let directory = &image.bytes()[2 * 26 * 128..];
+        assert_eq!(&directory[1..12], b"BIG     BIN");
+        assert_eq!((directory[12], directory[15], directory[16]), (0, 128, 2));
+        assert_eq!((directory[64 + 12], directory[64 + 15], directory[64 + 16]), (2, 57, 34));
+    }
+
+    #[test]
+    fn parse_diskdefs()
+    {
+        let text = "# comment\n\
+                    diskdef test-disk\n  seclen 512\n  tracks 40\n  sectrk 9\n  blocksize 2048\n\
+                    \n  maxdir 128\n  skew 1\n  boottrk 1\n  os 2.2\nend\n";
+        let definitions = DiskDefinition::parse_diskdefs(text).unwrap();
+        assert_eq!(definitions.len(), 1);
+        let definition = &definitions[0];
+        assert_eq!(definition.name, "test-disk");
+        assert_eq!(definition.records_per_track(), 36);
+        assert_eq!(definition.blocks(), 87);
+        assert_eq!(definition.directory_blocks(), 2);
+
+        assert!(DiskDefinition::parse_diskdefs("seclen 128\n").is_err());
+        let invalid = |replace: &str, with: &str| DiskDefinition::parse_diskdefs(&text.replace(replace, with)).unwrap_err();
+        assert!(invalid("boottrk 1", "boottrk 40").contains("no tracks left"));
+        assert!(invalid("blocksize 2048", "blocksize 0").contains("block size of 0"));
+        assert!(invalid("blocksize 2048", "blocksize 1536").contains("block size of 1536"));
+        assert!(invalid("seclen 512", "seclen 100").contains("sector size of 100"));
+        assert!(invalid("tracks 40", "tracks 65577").contains("invalid tracks 65577"));
+        assert!(DiskDefinition::find("ibm-3740", None).is_ok());
+        assert!(DiskDefinition::find("no-such-disk", None).is_err());
+    }
+}
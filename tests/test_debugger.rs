@@ -0,0 +1,82 @@
+mod tests
+{
+    use remus8080::assembler::assemble;
+    use remus8080::debugger::Target;
+    use remus8080::i8080::Processor;
+
+    /// Clocks `processor` until it reaches `target`, returning the instructions run.
+    fn run(processor: &mut Processor, target: Target) -> usize
+    {
+        for count in 1..100
+        {
+            processor.clock();
+            if target.reached(processor)
+            {
+                return count;
+            }
+        }
+        panic!("{:?} not reached", target);
+    }
+
+    fn processor(source: &str, steps: usize) -> Processor
+    {
+        let mut processor = Processor::from_bytes(assemble(source).unwrap().to_binary(), 0);
+        for _ in 0..steps
+        {
+            processor.clock();
+        }
+        processor
+    }
+
+    #[test]
+    fn steps_over_a_call()
+    {
+        let mut processor = processor("
+        LXI  SP,0100H
+        CALL SUB
+        HLT
+SUB:    NOP
+        RET", 1);
+        let target = Target::Return { address: 0x0006, stack_pointer: processor.get_stack_pointer() };
+        assert_eq!(run(&mut processor, target), 3);
+        assert_eq!(processor.get_pc(), 0x0006);
+
+        // A call made with SP at 0000H runs with SP wrapped around to FFFEH
+        let mut wrapped = Processor::from_bytes(assemble("LXI SP,0FFFEH").unwrap().to_binary(), 0);
+        wrapped.clock();
+        assert!(!Target::Return { address: 0x0003, stack_pointer: 0x0000 }.reached(&wrapped));
+        assert!(Target::Return { address: 0x0003, stack_pointer: 0xFFFC }.reached(&wrapped));
+    }
+
+    #[test]
+    fn does_not_stop_in_a_deeper_recursion()
+    {
+        let mut processor = processor("
+        LXI  SP,0100H
+        MVI  A,2
+        CALL SUB
+        HLT
+SUB:    DCR  A
+        JZ   DONE
+        CALL SUB
+DONE:   RET", 5);
+        // At the CALL inside the first SUB, whose return address DONE the second passes by
+        assert_eq!(processor.get_pc(), 0x000D);
+        let target = Target::Return { address: 0x0010, stack_pointer: processor.get_stack_pointer() };
+        assert_eq!(run(&mut processor, target), 4);
+        assert_eq!(processor.get_stack_pointer(), 0x00FE);
+    }
+
+    #[test]
+    fn runs_until_an_address()
+    {
+        let mut processor = processor("
+        MVI  B,3
+LOOP:   DCR  B
+        JNZ  LOOP
+        HLT", 0);
+        assert_eq!(run(&mut processor, Target::Address(0x0006)), 7);
+        assert_eq!(processor.get_registers().b, 0);
+        assert!(!Target::None.reached(&processor));
+    }
+}
@@ -0,0 +1,149 @@
+mod tests
+{
+    use std::path::{Path, PathBuf};
+    use remus8080::i8080::Processor;
+    use remus8080::machines::{Machine, VideoLayout};
+    use remus8080::machines::definition::*;
+    use remus8080::machines::invaders::{DipSwitches, Invaders};
+    use remus8080::machines::keymap::{InputBinding, KeyMap};
+    use remus8080::machines::toml::{self, Value};
+
+    fn temp_dir(name: &str) -> PathBuf
+    {
+        let dir = std::env::temp_dir().join(format!("remus8080_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn toml_subset()
+    {
+        let text = "name = \"a # b\" # comment\nclock = 2_000_000\nmask = 0xFF\nflags = [true, false]\n\
+                    \n[display]\n\"odd key\" = 'C:\\path'\n\n[[device]]\nport = -1\n[[device]]\nport = 0b101\n";
+        let root = toml::parse(text).unwrap();
+        assert_eq!(root.get_string("name").unwrap(), Some("a # b"));
+        assert_eq!(root.get_integer("clock").unwrap(), Some(2_000_000));
+        assert_eq!(root.get_integer("mask").unwrap(), Some(255));
+        assert_eq!(root.get("flags"), Some(&Value::Array(vec![Value::Boolean(true), Value::Boolean(false)])));
+
+        let display = root.get_table("display").unwrap().unwrap();
+        assert_eq!(display.get_string("odd key").unwrap(), Some("C:\\path"));
+        let devices = root.get_tables("device").unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].get_integer("port").unwrap(), Some(-1));
+        assert_eq!(devices[1].get_integer("port").unwrap(), Some(5));
+
+        assert_eq!(toml::parse("a = 1\na = 2").unwrap_err(), "line 2: a is defined twice");
+        assert!(toml::parse("a = \"open").unwrap_err().starts_with("line 1"));
+        assert!(toml::parse("[t]\n[t]").is_err());
+        assert!(root.get_string("clock").is_err());
+    }
+
+    #[test]
+    fn invaders_definition()
+    {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("machines/invaders.toml");
+        let definition = MachineDefinition::load(&path).unwrap();
+        assert_eq!(definition.name, "Space Invaders");
+        assert_eq!(definition.clock, 1_996_800);
+        assert_eq!(definition.frame_cycles, 33536);
+        assert_eq!(definition.memory.len(), 2);
+        assert_eq!(definition.memory[0].rom, Some(path.parent().unwrap().join("invaders.rom")));
+        assert_eq!(definition.devices[3], DeviceDefinition::ShiftRegister { amount_port: 2, data_port: 4, result_port: 3 });
+        assert_eq!(definition.interrupts[1], TimedInterrupt { rst: 2, at: 28672, period: 33536 });
+        assert_eq!(definition.display.unwrap().layout, VideoLayout::Columns);
+        assert_eq!(definition.overlay.as_ref().unwrap().bands.len(), 3);
+        assert!(definition.inputs.contains(&("coin".to_string(), InputBinding { port: 1, mask: 0x01 })));
+        assert!(KeyMap::parse(&definition.keymap, &definition.inputs).is_ok());
+        assert!(!definition.has_terminal());
+    }
+
+    #[test]
+    fn definition_errors()
+    {
+        let dir = Path::new(".");
+        assert!(MachineDefinition::parse("cpu = \"z80\"", dir).unwrap_err().contains("z80"));
+        assert!(MachineDefinition::parse("clock = 0", dir).unwrap_err().starts_with("clock = 0"));
+        assert!(MachineDefinition::parse("[[memory]]\nstart = 0xF000\nsize = 0x2000", dir).unwrap_err().starts_with("memory 1"));
+        assert!(MachineDefinition::parse("[[device]]\ntype = \"floppy\"", dir).unwrap_err().contains("floppy"));
+        assert!(MachineDefinition::parse("[[interrupt]]\nrst = 8", dir).is_err());
+        assert!(MachineDefinition::parse("[[overlay]]\ncolor = [1, 2, 3]", dir).is_err());
+
+        let definition = MachineDefinition::parse("[[memory]]\nstart = 0\nsize = 0x100\nrom = \"missing.bin\"", dir).unwrap();
+        assert!(definition.build(&mut Processor::from_bytes(Vec::new(), 0)).is_err());
+    }
+
+    #[test]
+    fn builds_and_runs()
+    {
+        let dir = temp_dir("definition");
+        // JMP 20H, an RST 1 handler that halts and at 20H:
+        // IN 1; STA 100H; MVI A,8; OUT 4; MVI A,4; OUT 4; MVI A,1; OUT 2; IN 3; STA 101H; EI; JMP $
+        let mut program = vec![0xC3, 0x20, 0x00];
+        program.resize(0x20, 0);
+        program[0x08] = 0x76;
+        program.extend_from_slice(&[0xDB, 0x01, 0x32, 0x00, 0x01, 0x3E, 0x08, 0xD3, 0x04, 0x3E, 0x04, 0xD3, 0x04,
+                                    0x3E, 0x01, 0xD3, 0x02, 0xDB, 0x03, 0x32, 0x01, 0x01, 0xFB, 0xC3, 0x37, 0x00]);
+        std::fs::write(dir.join("program.bin"), program).unwrap();
+
+        let text = "frame-cycles = 1000\n\
+                    [[memory]]\nstart = 0\nsize = 0x100\nrom = \"program.bin\"\n\
+                    [[device]]\ntype = \"input\"\nport = 1\nvalue = 0xF0\nactive-low = true\n\
+                    [[device]]\ntype = \"shift-register\"\namount-port = 2\ndata-port = 4\nresult-port = 3\n\
+                    [[device]]\ntype = \"sio\"\nport = 0x10\nconsole = \"none\"\n\
+                    [[interrupt]]\nrst = 1\nat = 500\nperiod = 1000\n\
+                    [inputs]\nfire = \"1:0x10\"\n";
+        let definition = MachineDefinition::parse(text, &dir).unwrap();
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut machine = definition.build(&mut cpu).unwrap();
+
+        machine.set_input(1, 0x10, true);
+        assert_eq!(machine.read_port(1), 0xE0);
+        assert_eq!(machine.read_port(0x10), 0x01);
+        assert_eq!(machine.read_port(0x42), 0xFF);
+
+        machine.run_frame(&mut cpu);
+        assert_eq!(machine.get_frames(), 1);
+        assert_eq!(cpu.get_memory_at(0x100), 0xE0);
+        assert_eq!(cpu.get_memory_at(0x101), 0x08);
+        // RST 1 was taken from the JMP $ loop
+        assert!(cpu.get_halted());
+        assert_eq!(cpu.get_stack_pointer(), 0x1E);
+        assert_eq!(cpu.get_memory_at(0x1E), 0x37);
+        assert_eq!(machine.named_inputs(), vec![("fire".to_string(), InputBinding { port: 1, mask: 0x10 })]);
+    }
+
+    #[test]
+    fn configured_invaders_matches_built_in()
+    {
+        let dir = temp_dir("definition_invaders");
+        // JMP 18H; RST 1 and RST 2 handlers that just return, then at 18H count up in video
+        // RAM through the shifter forever:
+        // LXI SP,2400H; LXI H,2400H; EI; loop: INR M; INX H; MVI A,0AAH; OUT 4; IN 3; MOV M,A; JMP loop
+        let mut program = vec![0xC3, 0x18, 0x00];
+        program.resize(0x18, 0);
+        program[0x08..0x0A].copy_from_slice(&[0xFB, 0xC9]);
+        program[0x10..0x12].copy_from_slice(&[0xFB, 0xC9]);
+        program.extend_from_slice(&[0x31, 0x00, 0x24, 0x21, 0x00, 0x24, 0xFB, 0x34, 0x23, 0x3E, 0xAA,
+                                    0xD3, 0x04, 0xDB, 0x03, 0x77, 0xC3, 0x1F, 0x00]);
+        std::fs::write(dir.join("invaders.rom"), &program).unwrap();
+
+        let source = std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("machines/invaders.toml")).unwrap();
+        let mut configured_cpu = Processor::from_bytes(Vec::new(), 0);
+        let mut configured = MachineDefinition::parse(&source, &dir).unwrap().build(&mut configured_cpu).unwrap();
+
+        let mut built_in_cpu = Processor::from_bytes(program, 0);
+        let mut built_in = Invaders::new(DipSwitches::default());
+
+        for _ in 0..3
+        {
+            configured.run_frame(&mut configured_cpu);
+            built_in.run_frame(&mut built_in_cpu);
+        }
+        assert_eq!(configured_cpu.get_cycles(), built_in_cpu.get_cycles());
+        assert_eq!(configured.framebuffer(&configured_cpu), built_in.framebuffer(&built_in_cpu));
+        assert_eq!(configured.color_overlay(), built_in.color_overlay());
+        assert_eq!(configured.read_port(1), built_in.read_port(1));
+    }
+}
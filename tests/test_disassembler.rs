@@ -0,0 +1,120 @@
+mod tests
+{
+    use std::path::Path;
+    use remus8080::assembler::assemble;
+    use remus8080::disassembler::*;
+    use remus8080::disassembler::graph::{EdgeKind, Graph};
+    use remus8080::disassembler::traversal::disassemble;
+    use remus8080::i8080::instructions::InstructionTypes;
+    use remus8080::symbols::SymbolTable;
+
+    /// LXI H,0109H; CALL 0109H; JNZ 0100H; JMP 010AH; RST 1; HLT; ...
+    const PROGRAM: [u8; 16] = [
+        0x21, 0x09, 0x01, 0xCD, 0x09, 0x01, 0xC2, 0x00, 0x01, 0xC3, 0x0E, 0x01, 0xCF, 0x76, 0xCB, 0xE9,
+    ];
+
+    fn read(address: u16) -> u8
+    {
+        address.checked_sub(0x100).and_then(|offset| PROGRAM.get(offset as usize)).copied().unwrap_or(0)
+    }
+
+    #[test]
+    fn decodes_without_running()
+    {
+        let lines = decode_from(&read, 0x100, 8, false);
+        let addresses: Vec<u16> = lines.iter().map(|line| line.address).collect();
+        assert_eq!(addresses, vec![0x100, 0x103, 0x106, 0x109, 0x10C, 0x10D, 0x10E, 0x10F]);
+        assert_eq!(lines[0].bytes, vec![0x21, 0x09, 0x01]);
+        assert_eq!(lines[0].instruction, Some(InstructionTypes::LXI));
+        assert_eq!(lines[6].instruction, None);
+
+        let flows: Vec<Flow> = lines.iter().map(Line::get_flow).collect();
+        assert_eq!(flows, vec![Flow::Next, Flow::Call(0x109), Flow::ConditionalJump(0x100), Flow::Jump(0x10E),
+                               Flow::Call(0x08), Flow::Next, Flow::Next, Flow::Indirect]);
+    }
+
+    #[test]
+    fn formats_for_the_assembler()
+    {
+        let none = SymbolTable::default();
+        let text = |address| decode(&read, address).format(&none);
+        assert_eq!(text(0x100), "LXI  H,0109H");
+        assert_eq!(text(0x103), "CALL 0109H");
+        assert_eq!(text(0x10C), "RST  1");
+        assert_eq!(text(0x10D), "HLT");
+        assert_eq!(text(0x10E), "DB   0CBH");
+        assert_eq!(decode(&|_| 0xFE, 0).format(&none), "CPI  0FEH");
+        assert_eq!(decode(&|address| [0x36, 0x0A][address as usize % 2], 0).format(&none), "MVI  M,0AH");
+        assert_eq!(decode(&|_| 0xF5, 0).format(&none), "PUSH PSW");
+
+        let symbols = SymbolTable::parse("0100 START\n0109 PRINT").unwrap();
+        let text = |address| decode(&read, address).format(&symbols);
+        assert_eq!(text(0x100), "LXI  H,PRINT");
+        assert_eq!(text(0x103), "CALL PRINT");
+        assert_eq!(text(0x109), "JMP  PRINT+5");
+    }
+
+    #[test]
+    fn follows_jumps_and_finds_earlier_instructions()
+    {
+        let followed: Vec<u16> = decode_from(&read, 0x100, 5, true).iter().map(|line| line.address).collect();
+        assert_eq!(followed, vec![0x100, 0x103, 0x106, 0x109, 0x10E]);
+
+        let before: Vec<u16> = decode_before(&read, 0x109, 2).iter().map(|line| line.address).collect();
+        assert_eq!(before, vec![0x103, 0x106]);
+        assert_eq!(decode_before(&read, 0x109, 10).last().unwrap().address, 0x106);
+        assert!(decode_before(&read, 0, 4).is_empty());
+    }
+
+    #[test]
+    fn traverses_from_entries()
+    {
+        let program = disassemble(&PROGRAM, 0x100, &[0x100], &SymbolTable::default());
+        let addresses: Vec<u16> = program.instructions.keys().copied().collect();
+        assert_eq!(addresses, vec![0x100, 0x103, 0x106, 0x109]);
+        assert_eq!(program.labels.get_name(0x109), Some("L0109"));
+        assert_eq!(program.labels.get_name(0x10E), Some("L010E"));
+        assert_eq!(program.labels.get_name(0x10C), None);
+
+        let source = program.to_source();
+        assert!(source.contains("L0109:\n\tJMP\tL010E\n\tDB\t0CFH,76H\nL010E:\n\tDB\t0CBH,0E9H\n"));
+        assert_eq!(assemble(&source).unwrap().to_binary(), PROGRAM.to_vec());
+    }
+
+    #[test]
+    fn cpudiag_reassembles()
+    {
+        let binary = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/cpudiag.bin")).unwrap();
+        let program = disassemble(&binary, 0x100, &[0x100], &SymbolTable::default());
+        let source = program.to_source();
+        assert!(source.contains("\tDB\t'MICROCOSM ASSOCIATES"));
+        assert_eq!(assemble(&source).unwrap().to_binary(), binary);
+    }
+
+    #[test]
+    fn splits_blocks_for_graphs()
+    {
+        // 0100 CALL 0108H; JZ 0100H; PCHL; 0108 MVI A,1; RET
+        let binary = [0xCD, 0x08, 0x01, 0xCA, 0x00, 0x01, 0xE9, 0x00, 0x3E, 0x01, 0xC9];
+        let program = disassemble(&binary, 0x100, &[0x100], &SymbolTable::default());
+        let graph = Graph::new(&program);
+        let starts: Vec<u16> = graph.blocks.keys().copied().collect();
+        assert_eq!(starts, vec![0x100, 0x103, 0x106, 0x108]);
+        assert_eq!(graph.subroutines.iter().copied().collect::<Vec<u16>>(), vec![0x100, 0x108]);
+        assert_eq!(graph.blocks[&0x108].instructions, vec![0x108, 0x10A]);
+        let kinds = |start| graph.blocks[&start].edges.iter().map(|edge| (edge.kind, edge.to)).collect::<Vec<_>>();
+        assert_eq!(kinds(0x100), vec![(EdgeKind::Call, Some(0x108)), (EdgeKind::FallThrough, Some(0x103))]);
+        assert_eq!(kinds(0x103), vec![(EdgeKind::Branch, Some(0x100)), (EdgeKind::FallThrough, Some(0x106))]);
+        assert_eq!(kinds(0x106), vec![(EdgeKind::Indirect, None)]);
+        assert!(kinds(0x108).is_empty());
+        assert_eq!(graph.subroutine(0x100).len(), 3);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("n0100 -> x0108 [label=\"CALL\", style=bold];"));
+        assert!(dot.contains("n0106 -> indirect [label=\"PCHL\", style=dashed];"));
+        assert!(dot.contains("n0108 [label=\"L0108:\\l0108  MVI  A,01H\\l010A  RET\\l\"];"));
+        let calls = graph.to_call_graph();
+        assert!(calls.contains("n0100 -> n0108;"));
+        assert!(calls.contains("n0100 -> indirect [style=dashed];"));
+    }
+}
@@ -0,0 +1,94 @@
+mod tests
+{
+    use std::path::Path;
+    use remus8080::i8080::Processor;
+    use remus8080::image::*;
+
+    #[test]
+    fn parse_records()
+    {
+        let text = ":0300300002337A1E\n\
+                    :020000040000FA\n\
+                    :020000020010EC\n\
+                    :02000000C3003B\n\
+                    :0400000300000100F8\n\
+                    :00000001FF\n";
+        let image = ihex::parse(text).unwrap();
+        assert_eq!(image.segments, vec![
+            Segment { address: 0x0030, bytes: vec![0x02, 0x33, 0x7A] },
+            Segment { address: 0x0100, bytes: vec![0xC3, 0x00] },
+        ]);
+        assert_eq!(image.entry, Some(0x0100));
+
+        let mut cpu = Processor::from_bytes(Vec::new(), 0);
+        image.load_into(&mut cpu);
+        assert_eq!(cpu.get_memory_at(0x0031), 0x33);
+        assert_eq!(cpu.get_memory_at(0x0100), 0xC3);
+        assert_eq!(cpu.get_pc(), 0x0100);
+    }
+
+    #[test]
+    fn entry_from_end_of_file_record()
+    {
+        let image = ihex::parse(":01010000AA54\n:00010001FE\n").unwrap();
+        assert_eq!(image.entry, Some(0x0100));
+        let image = ihex::parse(":0400000500001234B1\n:00000001FF\n").unwrap();
+        assert_eq!(image.entry, Some(0x1234));
+    }
+
+    #[test]
+    fn invalid_records()
+    {
+        assert_eq!(ihex::parse(":0300300002337A1F\n:00000001FF\n").unwrap_err(), "line 1: checksum is 1F, should be 1E");
+        assert!(ihex::parse(":0300300002337A1E\n").unwrap_err().contains("end of file"));
+        assert!(ihex::parse("0300300002337A1E\n").unwrap_err().starts_with("line 1"));
+        assert!(ihex::parse(":0400300002337A1E\n").is_err());
+        assert!(ihex::parse(":020000040001F9\n:01000000AA55\n:00000001FF\n").unwrap_err().contains("64K"));
+        assert!(ihex::parse(":02000004FFFFFC\n:01FFFF000001\n:00000001FF\n").unwrap_err().contains("64K"));
+        assert!(ihex::parse(":00000001FF\n:01000000AA55\n").unwrap_err().starts_with("line 2"));
+        assert!(ihex::parse(":00000006FA\n").unwrap_err().contains("unknown record type 06"));
+        assert_eq!(ihex::parse(":0100000\u{e9}0\n:00000001FF\n").unwrap_err(), "line 1: invalid hex digits in :0100000\u{e9}0");
+    }
+
+    #[test]
+    fn write_and_read_back()
+    {
+        let mut image = Image::default();
+        image.add(0x0100, &(0..40).collect::<Vec<u8>>());
+        image.add(0x2000, &[0xFF]);
+        image.entry = Some(0x0100);
+
+        let text = ihex::write(&image, ihex::DEFAULT_RECORD_SIZE);
+        assert_eq!(text.lines().count(), 5);
+        assert!(text.starts_with(":10010000000102030405060708090A0B0C0D0E0F77\n"));
+        assert!(text.ends_with(":00010001FE\n"));
+        assert_eq!(ihex::parse(&text).unwrap(), image);
+    }
+
+    #[test]
+    fn formats_and_binaries()
+    {
+        assert_eq!(Format::from_path(Path::new("a.HEX")), Format::IntelHex);
+        assert_eq!(Format::from_path(Path::new("a.ihx")), Format::IntelHex);
+        assert_eq!(Format::from_path(Path::new("a.com")), Format::Binary);
+        assert_eq!(Format::parse("hex"), Ok(Format::IntelHex));
+        assert!(Format::parse("srec").is_err());
+
+        let mut cpu = Processor::from_bytes(vec![1, 2, 3, 4, 5, 6], 0);
+        let image = Image::from_memory(&cpu, &[(1, 2), (4, 4)]);
+        assert_eq!(image.len(), 3);
+        assert_eq!(image.to_binary(), vec![2, 3, 0, 5]);
+
+        let path = std::env::temp_dir().join(format!("remus8080_image_{}.hex", std::process::id()));
+        image.save(&path, None).unwrap();
+        let loaded = Image::load(&path, None, 0).unwrap();
+        assert_eq!(loaded.segments, image.segments);
+        let raw = Image::load(&path, Some(Format::Binary), 0x10).unwrap();
+        assert_eq!(raw.start(), Some(0x10));
+        std::fs::remove_file(&path).unwrap();
+
+        Image::from_binary(&[9, 9], 0x8000).load_into(&mut cpu);
+        assert_eq!(cpu.get_memory_at(0x8001), 9);
+        assert_eq!(cpu.get_pc(), 0);
+    }
+}
@@ -0,0 +1,94 @@
+mod tests
+{
+    use remus8080::i8080::Processor;
+    use remus8080::machines::Machine;
+    use remus8080::machines::invaders::*;
+
+    /// A program that enables interrupts and spins, with the RST 1 and RST 2 handlers
+    /// recording that they ran in memory.
+    fn spin_with_handlers() -> Processor
+    {
+        let mut program = vec![0; 0x20];
+        program[0x00..0x07].copy_from_slice(&[0x31, 0x00, 0x24, 0xFB, 0xC3, 0x04, 0x00]); // LXI SP; EI; JMP $
+        program[0x08..0x0E].copy_from_slice(&[0x3E, 0x01, 0x32, 0x00, 0x20, 0xFB]);       // MVI A,1; STA 2000H; EI
+        program[0x0E] = 0xC9;                                                              // RET
+        program[0x10..0x16].copy_from_slice(&[0x3E, 0x02, 0x32, 0x01, 0x20, 0xFB]);       // MVI A,2; STA 2001H; EI
+        program[0x16] = 0xC9;                                                              // RET
+        Processor::from_bytes(program, 0)
+    }
+
+    #[test]
+    fn shift_register()
+    {
+        // MVI A,0AAH; OUT 4; MVI A,0FFH; OUT 4; MVI A,3; OUT 2; IN 3
+        let program = vec![0x3E, 0xAA, 0xD3, 0x04, 0x3E, 0xFF, 0xD3, 0x04, 0x3E, 0x03, 0xD3, 0x02, 0xDB, 0x03];
+        let mut cpu = Processor::from_bytes(program, 0);
+        let mut invaders = Invaders::new(DipSwitches::default());
+
+        for _ in 0..7
+        {
+            invaders.clock(&mut cpu);
+        }
+        assert_eq!(cpu.get_pc(), 14);
+        assert_eq!(cpu.get_registers().accumulator, 0xFD);
+    }
+
+    #[test]
+    fn input_ports_and_dip_switches()
+    {
+        let mut invaders = Invaders::new(DipSwitches { ships: 5, extra_ship_at: 1000, show_coin_info: false });
+        assert_eq!(invaders.read_port(0), 0x0E);
+        assert_eq!(invaders.read_port(1), 0x08);
+        assert_eq!(invaders.read_port(2), 0x8A);
+
+        invaders.set_input(1, COIN | P1_LEFT, true);
+        invaders.set_input(2, P2_FIRE, true);
+        assert_eq!(invaders.read_port(1), 0x29);
+        assert_eq!(invaders.read_port(2), 0x9A);
+
+        invaders.set_input(1, COIN, false);
+        assert_eq!(invaders.read_port(1), 0x28);
+    }
+
+    #[test]
+    fn interrupts_follow_the_beam()
+    {
+        let mut cpu = spin_with_handlers();
+        let mut invaders = Invaders::new(DipSwitches::default());
+
+        while cpu.get_cycles() < MID_SCREEN_CYCLE - 20
+        {
+            invaders.clock(&mut cpu);
+        }
+        assert_eq!(cpu.get_memory_at(0x2000), 0);
+
+        while cpu.get_cycles() < MID_SCREEN_CYCLE + 40
+        {
+            invaders.clock(&mut cpu);
+        }
+        assert_eq!(cpu.get_memory_at(0x2000), 1);
+        assert_eq!(cpu.get_memory_at(0x2001), 0);
+
+        invaders.run_frame(&mut cpu);
+        assert_eq!(cpu.get_memory_at(0x2001), 2);
+        assert_eq!(invaders.get_frames(), 1);
+        assert_eq!(cpu.get_stack_pointer(), 0x2400);
+    }
+
+    #[test]
+    fn framebuffer_is_rotated()
+    {
+        let mut cpu = Processor::from_bytes(vec![], 0);
+        let invaders = Invaders::new(DipSwitches::default());
+        cpu.set_memory_at(VRAM_START, 0x01);
+        cpu.set_memory_at(VRAM_START + 31, 0x80);
+        cpu.set_memory_at(VRAM_END - 1, 0x80);
+
+        let framebuffer = invaders.framebuffer(&cpu).unwrap();
+        assert_eq!((framebuffer.width, framebuffer.height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert!(framebuffer.get(0, 255));
+        assert!(framebuffer.get(0, 0));
+        assert!(framebuffer.get(223, 0));
+        assert_eq!(framebuffer.pixels.iter().filter(|lit| **lit).count(), 3);
+    }
+}
@@ -0,0 +1,46 @@
+mod tests
+{
+    use std::time::{Duration, Instant};
+    use termion::event::Key;
+    use remus8080::machines::Machine;
+    use remus8080::machines::invaders::*;
+    use remus8080::machines::keymap::*;
+
+    #[test]
+    fn parse_keymap()
+    {
+        let invaders = Invaders::new(DipSwitches::default());
+        let text = "# comment\nhold = 50\nx = coin\nf2 = 2:0x10\nctrl-a = P1-FIRE\n";
+        let keymap = KeyMap::parse(text, &invaders.named_inputs()).unwrap();
+        assert_eq!(keymap.get_hold(), Duration::from_millis(50));
+
+        assert!(KeyMap::parse("x = nothing", &invaders.named_inputs()).is_err());
+        assert!(KeyMap::parse("spacebar = coin", &invaders.named_inputs()).is_err());
+        assert_eq!(parse_key("space"), Ok(Key::Char(' ')));
+        assert_eq!(parse_key("F5"), Ok(Key::F(5)));
+        assert_eq!(parse_key("ctrl-a"), Ok(Key::Ctrl('a')));
+        assert_eq!(parse_key("Left"), Ok(Key::Left));
+    }
+
+    #[test]
+    fn press_and_release()
+    {
+        let mut invaders = Invaders::new(DipSwitches::default());
+        let mut keymap = KeyMap::parse(DEFAULT_KEYMAP, &invaders.named_inputs()).unwrap();
+        keymap.set_hold(Duration::from_millis(100));
+        let start = Instant::now();
+
+        assert!(keymap.press(Key::Char('c'), &mut invaders, start));
+        assert!(keymap.press(Key::Left, &mut invaders, start));
+        assert!(!keymap.press(Key::Char('z'), &mut invaders, start));
+        assert_eq!(invaders.read_port(1), 0x08 | COIN | P1_LEFT);
+
+        // A repeat of the left key keeps it down after the coin is released
+        keymap.press(Key::Left, &mut invaders, start + Duration::from_millis(80));
+        keymap.update(&mut invaders, start + Duration::from_millis(120));
+        assert_eq!(invaders.read_port(1), 0x08 | P1_LEFT);
+
+        keymap.update(&mut invaders, start + Duration::from_millis(180));
+        assert_eq!(invaders.read_port(1), 0x08);
+    }
+}
@@ -0,0 +1,210 @@
+mod tests
+{
+    use remus8080::linker::*;
+    use remus8080::linker::rel::*;
+
+    /// Writes REL items the way M80 does.
+    #[derive(Default)]
+    struct Writer
+    {
+        bits: Vec<bool>,
+    }
+
+    impl Writer
+    {
+        fn put(&mut self, value: u16, bits: usize) -> &mut Writer
+        {
+            for i in (0..bits).rev()
+            {
+                self.bits.push((value >> i) & 1 == 1);
+            }
+            self
+        }
+
+        fn byte(&mut self, byte: u8) -> &mut Writer
+        {
+            self.put(0, 1).put(byte as u16, 8)
+        }
+
+        fn bytes(&mut self, bytes: &[u8]) -> &mut Writer
+        {
+            for byte in bytes
+            {
+                self.byte(*byte);
+            }
+            self
+        }
+
+        fn word(&mut self, segment: u16, value: u16) -> &mut Writer
+        {
+            self.put(1, 1).put(segment, 2).put(value & 0xFF, 8).put(value >> 8, 8)
+        }
+
+        fn special(&mut self, item: u16, address: Option<(u16, u16)>, name: Option<&str>) -> &mut Writer
+        {
+            self.put(0b100, 3).put(item, 4);
+            if let Some((segment, value)) = address
+            {
+                self.word_field(segment, value);
+            }
+            if let Some(name) = name
+            {
+                self.put(name.len() as u16 & 7, 3);
+                for c in name.bytes()
+                {
+                    self.put(c as u16, 8);
+                }
+            }
+            if item == 14
+            {
+                while !self.bits.len().is_multiple_of(8)
+                {
+                    self.bits.push(false);
+                }
+            }
+            self
+        }
+
+        fn word_field(&mut self, segment: u16, value: u16)
+        {
+            self.put(segment, 2).put(value & 0xFF, 8).put(value >> 8, 8);
+        }
+
+        fn finish(&mut self) -> Vec<u8>
+        {
+            self.special(15, None, None);
+            self.bits.chunks(8).map(|chunk| chunk.iter().enumerate().fold(0, |byte, (i, bit)| byte | ((*bit as u8) << (7 - i)))).collect()
+        }
+    }
+
+    /// Absolute words are written as two bytes, the segment 0 marking a special item.
+    const ABSOLUTE: u16 = 0;
+    const PROGRAM: u16 = 1;
+    const DATA: u16 = 2;
+    const COMMON: u16 = 3;
+
+    fn modules(writer: &mut Writer) -> Vec<Module>
+    {
+        parse(&writer.finish()).unwrap()
+    }
+
+    /// CALL PRINT, LXI H,MESSAGE, JMP $, CALL PRINT, LXI H,PRINT+2 and "Hi" in the data.
+    fn main_module(writer: &mut Writer) -> &mut Writer
+    {
+        writer.special(2, None, Some("MAIN"))
+            .special(7, Some((PROGRAM, 0)), Some("START"))
+            .special(10, Some((ABSOLUTE, 3)), None)
+            .special(13, Some((PROGRAM, 15)), None)
+            .bytes(&[0xCD, 0, 0])
+            .byte(0x21).word(DATA, 0)
+            .byte(0xC3).word(PROGRAM, 6)
+            .byte(0xCD).word(PROGRAM, 1)
+            .byte(0x21).special(9, Some((ABSOLUTE, 2)), None).word(PROGRAM, 10)
+            .special(11, Some((DATA, 0)), None)
+            .bytes(b"Hi\0")
+            .special(6, Some((PROGRAM, 13)), Some("PRINT"))
+            .special(14, Some((PROGRAM, 0)), None)
+    }
+
+    fn library_module<'a>(writer: &'a mut Writer, name: &str, symbol: &str) -> &'a mut Writer
+    {
+        writer.special(2, None, Some(name))
+            .special(7, Some((PROGRAM, 0)), Some(symbol))
+            .special(13, Some((PROGRAM, 1)), None)
+            .byte(0xC9)
+            .special(14, Some((ABSOLUTE, 0)), None)
+    }
+
+    #[test]
+    fn parses_items()
+    {
+        let mut writer = Writer::default();
+        writer.special(2, None, Some("LONGNAME"))
+            .byte(0x3E)
+            .word(DATA, 0x1234)
+            .special(5, Some((ABSOLUTE, 16)), Some("BUF"))
+            .special(14, Some((PROGRAM, 2)), None);
+        library_module(&mut writer, "LIB", "X");
+        let modules = modules(&mut writer);
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0].items, vec![
+            Item::ProgramName("LONGNAME".to_string()),
+            Item::Byte(0x3E),
+            Item::Word(Address { segment: SegmentType::Data, offset: 0x1234 }),
+            Item::CommonSize(Address { segment: SegmentType::Absolute, offset: 16 }, "BUF".to_string()),
+            Item::EndModule(Address { segment: SegmentType::Program, offset: 2 }),
+        ]);
+        assert_eq!(modules[0].get_start(), Some(Address { segment: SegmentType::Program, offset: 2 }));
+        assert_eq!(modules[1].get_name(), Some("LIB"));
+        assert_eq!(modules[1].get_start(), None);
+
+        let mut padded = Writer::default();
+        library_module(&mut padded, "LIB", "X");
+        let mut bytes = padded.finish();
+        bytes.truncate(bytes.len() - 1);
+        bytes.extend_from_slice(&[0x1A; 5]);
+        assert_eq!(parse(&bytes).unwrap().len(), 1);
+        assert!(parse(&bytes[..4]).is_err());
+    }
+
+    #[test]
+    fn links_externals_from_libraries()
+    {
+        let main = modules(main_module(&mut Writer::default()));
+        let mut writer = Writer::default();
+        library_module(&mut writer, "UNUSED", "OTHER");
+        library_module(&mut writer, "PRINT", "PRINT");
+        let libraries = modules(&mut writer);
+
+        let linked = link(&main, &libraries, Layout::default()).unwrap();
+        assert_eq!(linked.image.start(), Some(0x100));
+        assert_eq!(linked.image.entry, Some(0x100));
+        assert_eq!(linked.image.to_binary(), vec![
+            0xCD, 0x0F, 0x01, 0x21, 0x10, 0x01, 0xC3, 0x06, 0x01, 0xCD, 0x0F, 0x01, 0x21, 0x11, 0x01,
+            0xC9, b'H', b'i', 0x00,
+        ]);
+        assert_eq!(linked.write_symbols(), "0100 START\n010F PRINT\n");
+
+        let linked = link(&main, &libraries, Layout { program: 0x4000, data: Some(0x8000) }).unwrap();
+        assert_eq!(&linked.image.to_binary()[..6], &[0xCD, 0x0F, 0x40, 0x21, 0x00, 0x80]);
+        assert_eq!(linked.image.segments.last().unwrap().address, 0x8000);
+    }
+
+    #[test]
+    fn places_common_blocks_after_the_data()
+    {
+        let mut writer = Writer::default();
+        writer.special(2, None, Some("A"))
+            .special(5, Some((ABSOLUTE, 2)), Some("BUF"))
+            .special(13, Some((PROGRAM, 3)), None)
+            .special(1, None, Some("BUF"))
+            .byte(0x21).word(COMMON, 1)
+            .special(11, Some((COMMON, 0)), None)
+            .bytes(&[0xAA, 0xBB])
+            .special(14, Some((PROGRAM, 0)), None);
+        writer.special(2, None, Some("B"))
+            .special(5, Some((ABSOLUTE, 4)), Some("BUF"))
+            .special(10, Some((ABSOLUTE, 1)), None)
+            .special(13, Some((PROGRAM, 1)), None)
+            .byte(0xC9)
+            .special(11, Some((DATA, 0)), None)
+            .byte(0x55)
+            .special(14, Some((ABSOLUTE, 0)), None);
+        let linked = link(&modules(&mut writer), &[], Layout::default()).unwrap();
+        assert_eq!(linked.image.to_binary(), vec![0x21, 0x06, 0x01, 0xC9, 0x55, 0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn reports_undefined_and_duplicate_symbols()
+    {
+        let main = modules(main_module(&mut Writer::default()));
+        assert_eq!(link(&main, &[], Layout::default()).unwrap_err(), "undefined symbols: PRINT");
+
+        let mut writer = Writer::default();
+        library_module(&mut writer, "ONE", "PRINT");
+        library_module(&mut writer, "TWO", "PRINT");
+        let mut twice = main.clone();
+        twice.extend(modules(&mut writer));
+        assert_eq!(link(&twice, &[], Layout::default()).unwrap_err(), "PRINT is defined in both ONE and TWO");
+    }
+}
@@ -28,6 +28,95 @@ mod tests
         assert_eq!(reg.h, r.d);
         assert_eq!(reg.d, r.h);
     }
-        
 
+    #[test]
+    fn in_out()
+    {
+        // IN 7; OUT 9
+        let mem = vec![0xDB, 0x07, 0xD3, 0x09];
+        let mut cpu = Processor::from_bytes(mem, 0);
+        cpu.set_input_port(7, 0x5A);
+
+        cpu.clock();
+        assert_eq!(cpu.get_registers().accumulator, 0x5A);
+        assert_eq!(cpu.take_input(), Some(7));
+        cpu.clock();
+        assert_eq!(cpu.get_pc(), 4);
+        assert_eq!(cpu.take_output(), Some((9, 0x5A)));
+        assert_eq!(cpu.take_output(), None);
+    }
+
+    #[test]
+    fn rst()
+    {
+        let mut mem = vec![0; 0x1234];
+        mem[0x1233] = 0xDF;
+        let mut cpu = Processor::from_bytes(mem, 0);
+        cpu.set_pc(0x1233);
+        cpu.set_stack_pointer(0x100);
+
+        cpu.clock();
+        assert_eq!(cpu.get_pc(), 0x18);
+        assert_eq!(cpu.get_stack_pointer(), 0xFE);
+        assert_eq!(cpu.get_memory_at(0xFE), 0x34);
+        assert_eq!(cpu.get_memory_at(0xFF), 0x12);
+    }
+
+    #[test]
+    fn interrupt()
+    {
+        // EI; NOP
+        let mem = vec![0xFB, 0x00, 0x00];
+        let mut cpu = Processor::from_bytes(mem, 0);
+        cpu.set_stack_pointer(0x100);
+
+        assert_eq!(cpu.interrupt(1), false);
+        cpu.clock();
+        cpu.clock();
+        assert_eq!(cpu.interrupt(1), true);
+        assert_eq!(cpu.get_pc(), 0x08);
+        assert_eq!(cpu.get_memory_at(0xFE), 0x02);
+        assert_eq!(cpu.get_interrupts_enabled(), false);
+    }
+
+    #[test]
+    fn cycles()
+    {
+        // XRA A; CZ 5; NOP; RNZ; RZ
+        let mem = vec![0xAF, 0xCC, 0x05, 0x00, 0x00, 0xC0, 0xC8];
+        let mut cpu = Processor::from_bytes(mem, 0);
+        cpu.set_stack_pointer(0x100);
+
+        cpu.clock();
+        assert_eq!(cpu.get_cycles(), 4);
+        cpu.clock();
+        assert_eq!(cpu.get_cycles(), 21);
+        cpu.clock();
+        assert_eq!(cpu.get_cycles(), 26);
+        cpu.clock();
+        assert_eq!(cpu.get_cycles(), 37);
+        assert_eq!(cpu.get_pc(), 4);
+    }
+
+    #[test]
+    fn hlt()
+    {
+        // EI; HLT; NOP
+        let mem = vec![0xFB, 0x76, 0x00];
+        let mut cpu = Processor::from_bytes(mem, 0);
+        cpu.set_stack_pointer(0x100);
+
+        cpu.clock();
+        cpu.clock();
+        assert_eq!(cpu.get_halted(), true);
+        assert_eq!(cpu.get_pc(), 2);
+        cpu.clock();
+        assert_eq!(cpu.get_pc(), 2);
+        assert_eq!(cpu.get_cycles(), 4 + 7 + 4);
+
+        assert_eq!(cpu.interrupt(7), true);
+        assert_eq!(cpu.get_halted(), false);
+        assert_eq!(cpu.get_pc(), 0x38);
+        assert_eq!(cpu.get_memory_at(0xFE), 0x02);
+    }
 }
@@ -0,0 +1,46 @@
+mod common;
+
+mod tests
+{
+    use super::common::{self, NESTED};
+    use remus8080::assembler::assemble;
+    use remus8080::profiler::Profiler;
+    use remus8080::symbols::SymbolTable;
+
+    fn profile() -> (Profiler, SymbolTable)
+    {
+        let mut symbols = SymbolTable::default();
+        for (name, value) in assemble(NESTED).unwrap().symbols.iter()
+        {
+            symbols.add(name, *value);
+        }
+        let mut profiler = Profiler::default();
+        common::run(NESTED, 10, |step, processor| profiler.record(step, processor));
+        (profiler, symbols)
+    }
+
+    #[test]
+    fn counts_addresses_and_subroutines()
+    {
+        let (profiler, _) = profile();
+        assert_eq!(profiler.get(0x0003), (1, 17));
+        assert_eq!(profiler.get(0x000B), (1, 4));
+        assert_eq!(profiler.get(0x000E), (0, 0));
+        assert_eq!(profiler.get_total_cycles(), 75);
+        assert_eq!(profiler.hotspots(2), vec![(0x0003, 1, 17), (0x0007, 1, 17)]);
+        assert_eq!(profiler.subroutines(5), vec![(0x0007, 1, 27, 41), (0x000B, 1, 14, 14)]);
+    }
+
+    #[test]
+    fn reports_and_folds_stacks()
+    {
+        let (profiler, symbols) = profile();
+        assert_eq!(profiler.to_folded(&symbols), "top 34\ntop;OUTER 27\ntop;OUTER;INNER 14\n");
+
+        let report = profiler.report(&symbols, 1);
+        assert_eq!(report.len(), 4);
+        assert!(report[1].starts_with("0003"));
+        assert!(report[3].starts_with("0007 OUTER"));
+        assert!(report[3].ends_with("54.7%"));
+    }
+}
@@ -0,0 +1,53 @@
+mod tests
+{
+    use remus8080::debugger::screen::*;
+    use remus8080::machines::Framebuffer;
+
+    fn diagonal(size: usize) -> Framebuffer
+    {
+        let mut framebuffer = Framebuffer::new(size, size);
+        for i in 0..size
+        {
+            framebuffer.set(i, i, true);
+        }
+        framebuffer
+    }
+
+    #[test]
+    fn braille()
+    {
+        let lines = render_lines(&diagonal(4), RenderMode::Braille, 1);
+        // Dots 1 and 5 in the left cell, dots 3 and 8 in the right one
+        assert_eq!(lines, vec!["\u{2811}\u{2884}".to_string()]);
+    }
+
+    #[test]
+    fn half_blocks()
+    {
+        let lines = render_lines(&diagonal(2), RenderMode::HalfBlock, 1);
+        assert_eq!(lines, vec!["▀▄".to_string()]);
+    }
+
+    #[test]
+    fn scaling_keeps_single_pixels()
+    {
+        let mut framebuffer = Framebuffer::new(224, 256);
+        framebuffer.set(223, 255, true);
+
+        let scale = fit_scale(&framebuffer, RenderMode::Braille, 40, 30);
+        assert_eq!(scale, 3);
+        let lines = render_lines(&framebuffer, RenderMode::Braille, scale);
+        assert_eq!(lines.len(), 22);
+        assert!(lines.iter().all(|line| line.chars().count() == 38));
+        assert_ne!(lines[21].chars().last(), Some('\u{2800}'));
+        assert_eq!(lines.concat().chars().filter(|c| *c != '\u{2800}').count(), 1);
+    }
+
+    #[test]
+    fn frame_limiter()
+    {
+        let mut limiter = FrameLimiter::new(1);
+        assert!(limiter.ready());
+        assert!(!limiter.ready());
+    }
+}
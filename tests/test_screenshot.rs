@@ -0,0 +1,106 @@
+mod tests
+{
+    use remus8080::machines::Framebuffer;
+    use remus8080::machines::Machine;
+    use remus8080::machines::invaders::*;
+    use remus8080::machines::screenshot::*;
+
+    fn checkerboard() -> Framebuffer
+    {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        framebuffer.set(0, 0, true);
+        framebuffer.set(2, 0, true);
+        framebuffer.set(1, 1, true);
+        framebuffer
+    }
+
+    /// Reads the chunks of a PNG file, checking their CRCs.
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)>
+    {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1A\n");
+        let mut chunks = Vec::new();
+        let mut position = 8;
+        while position < png.len()
+        {
+            let length = u32::from_be_bytes([png[position], png[position + 1], png[position + 2], png[position + 3]]) as usize;
+            let body = &png[position + 4..position + 8 + length];
+            let crc = &png[position + 8 + length..position + 12 + length];
+            assert_eq!(crc32(body).to_be_bytes(), crc);
+            chunks.push((String::from_utf8_lossy(&body[..4]).to_string(), body[4..].to_vec()));
+            position += 12 + length;
+        }
+        chunks
+    }
+
+    #[test]
+    fn ppm()
+    {
+        let mut ppm = Vec::new();
+        write_ppm(&mut ppm, &checkerboard(), None).unwrap();
+        let mut expected = b"P6\n3 2\n255\n".to_vec();
+        for lit in [true, false, true, false, true, false].iter()
+        {
+            expected.extend_from_slice(if *lit { &WHITE } else { &BLACK });
+        }
+        assert_eq!(ppm, expected);
+    }
+
+    #[test]
+    fn png()
+    {
+        let mut png = Vec::new();
+        write_png(&mut png, &checkerboard(), None).unwrap();
+        let chunks = chunks(&png);
+        let names: Vec<&str> = chunks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[0].1, vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+
+        // A single stored deflate block holding both filtered rows
+        let idat = &chunks[1].1;
+        let rows = &idat[7..idat.len() - 4];
+        assert_eq!(&idat[..7], &[0x78, 0x01, 0x01, 20, 0, !20u8, 0xFF]);
+        assert_eq!(rows[0], 0);
+        assert_eq!(&rows[1..4], &WHITE);
+        assert_eq!(rows[10], 0);
+        assert_eq!(&idat[idat.len() - 4..], &adler32(rows).to_be_bytes());
+    }
+
+    #[test]
+    fn save_by_extension()
+    {
+        let dir = std::env::temp_dir().join(format!("remus8080_screenshot_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ppm = dir.join("shot.PPM");
+        save(&ppm, &checkerboard(), None).unwrap();
+        assert!(std::fs::read(&ppm).unwrap().starts_with(b"P6"));
+
+        let text = dir.join("shot.txt");
+        assert!(save(&text, &checkerboard(), None).unwrap_err().ends_with("screenshots are .png or .ppm files"));
+        assert!(!text.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checksums()
+    {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn invaders_overlay()
+    {
+        let invaders = Invaders::new(DipSwitches::default());
+        let overlay = invaders.color_overlay().unwrap();
+        assert_eq!(overlay.color_at(100, 10), WHITE);
+        assert_eq!(overlay.color_at(100, 40), [0xFF, 0x20, 0x20]);
+        assert_eq!(overlay.color_at(100, 200), [0x20, 0xFF, 0x20]);
+        assert_eq!(overlay.color_at(20, 250), [0x20, 0xFF, 0x20]);
+        assert_eq!(overlay.color_at(200, 250), WHITE);
+
+        let mut framebuffer = Framebuffer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+        framebuffer.set(0, 40, true);
+        let rgb = to_rgb(&framebuffer, Some(&overlay));
+        assert_eq!(&rgb[(40 * SCREEN_WIDTH) * 3..(40 * SCREEN_WIDTH) * 3 + 3], &[0xFF, 0x20, 0x20]);
+    }
+}
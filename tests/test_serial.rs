@@ -0,0 +1,133 @@
+mod tests
+{
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use remus8080::console::{BufferConsole, Console, NullConsole};
+    use remus8080::i8080::Processor;
+    use remus8080::machines::PortDevice;
+    use remus8080::machines::altair::Altair;
+    use remus8080::machines::serial::*;
+
+    /// A scripted console whose output can be looked at after the device took it over.
+    struct SharedConsole
+    {
+        input:  Vec<u8>,
+        output: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Console for SharedConsole
+    {
+        fn status(&mut self) -> bool
+        {
+            !self.input.is_empty()
+        }
+
+        fn read(&mut self) -> Option<u8>
+        {
+            if self.input.is_empty() { None } else { Some(self.input.remove(0)) }
+        }
+
+        fn write(&mut self, c: u8)
+        {
+            self.output.borrow_mut().push(c);
+        }
+    }
+
+    #[test]
+    fn sio_status_and_data()
+    {
+        let mut sio = Sio::new(BufferConsole::new(b"A"), SIO_PORT);
+        assert_eq!(sio.peek(0x00), Some(0x01));
+
+        sio.update(0);
+        assert_eq!(sio.peek(0x00), Some(0x00));
+        assert_eq!(sio.peek(0x01), Some(b'A'));
+        sio.read(0x01);
+        assert_eq!(sio.peek(0x00), Some(0x01));
+
+        // The transmitter is busy for a character time after every character
+        assert!(sio.write(0x01, b'x'));
+        assert_eq!(sio.peek(0x00), Some(0x81));
+        sio.update(character_cycles(DEFAULT_BAUD_RATE, 2_000_000));
+        assert_eq!(sio.peek(0x00).map(|status| status & 0x80), Some(0x00));
+        assert_eq!(sio.console().output, b"x");
+
+        assert_eq!(sio.peek(0x10), None);
+        assert!(!sio.write(0x10, 0));
+    }
+
+    #[test]
+    fn sio_interrupts()
+    {
+        let mut sio = Sio::new(BufferConsole::new(b"A"), SIO_PORT);
+        sio.write(0x00, 0x01);
+        assert_eq!(sio.interrupt(), None);
+        sio.update(0);
+        assert_eq!(sio.interrupt(), Some(DEFAULT_INTERRUPT));
+        sio.read(0x01);
+        assert_eq!(sio.interrupt(), None);
+
+        sio.write(0x00, 0x02);
+        assert_eq!(sio.interrupt(), Some(DEFAULT_INTERRUPT));
+    }
+
+    #[test]
+    fn acia_status_and_interrupts()
+    {
+        let mut acia = Acia::new(BufferConsole::new(b"B"), TWO_SIO_PORT);
+        assert_eq!(acia.peek(0x10), Some(TRANSMIT_EMPTY));
+
+        acia.update(0);
+        assert_eq!(acia.peek(0x10), Some(TRANSMIT_EMPTY | RECEIVE_FULL));
+        assert_eq!(acia.interrupt(), None);
+
+        // 8N1, divide by 16, receive interrupt
+        acia.write(0x10, 0x95);
+        assert_eq!(acia.peek(0x10), Some(INTERRUPT_REQUEST | TRANSMIT_EMPTY | RECEIVE_FULL));
+        assert_eq!(acia.interrupt(), Some(DEFAULT_INTERRUPT));
+        assert_eq!(acia.peek(0x11), Some(b'B'));
+        acia.read(0x11);
+        assert_eq!(acia.interrupt(), None);
+
+        // Transmit interrupt while the transmitter is empty
+        acia.write(0x10, 0x35);
+        assert_eq!(acia.interrupt(), Some(DEFAULT_INTERRUPT));
+        acia.write(0x11, b'y');
+        assert_eq!(acia.interrupt(), None);
+    }
+
+    #[test]
+    fn two_sio_channels()
+    {
+        let mut board = TwoSio::new(NullConsole, BufferConsole::new(b"C"), TWO_SIO_PORT);
+        board.set_interrupt_vector(5);
+        board.update(0);
+        assert_eq!(board.peek(0x10), Some(TRANSMIT_EMPTY));
+        assert_eq!(board.peek(0x12), Some(TRANSMIT_EMPTY | RECEIVE_FULL));
+        assert_eq!(board.peek(0x13), Some(b'C'));
+        assert_eq!(board.peek(0x14), None);
+
+        board.write(0x12, 0x95);
+        assert_eq!(board.interrupt(), Some(5));
+        board.write(0x13, b'z');
+        assert_eq!(board.channel_b().console().output, b"z");
+    }
+
+    #[test]
+    fn altair_echoes_through_sio()
+    {
+        // Wait for a character, echo it and loop:
+        // IN 0; ANI 1; JNZ 0; IN 1; OUT 1; JMP 0
+        let program = vec![0xDB, 0x00, 0xE6, 0x01, 0xC2, 0x00, 0x00, 0xDB, 0x01, 0xD3, 0x01, 0xC3, 0x00, 0x00];
+        let mut cpu = Processor::from_bytes(program, 0);
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let console = SharedConsole { input: b"hello".to_vec(), output: output.clone() };
+
+        let mut altair = Altair::new();
+        altair.attach(Box::new(Sio::new(console, SIO_PORT)));
+        altair.run();
+        altair.run_for(&mut cpu, 5000);
+        assert_eq!(*output.borrow(), b"hello");
+        assert!(!altair.is_closed());
+    }
+}
@@ -0,0 +1,87 @@
+mod tests
+{
+    use remus8080::i8080::Processor;
+    use remus8080::machines::Machine;
+    use remus8080::machines::invaders::*;
+    use remus8080::machines::sound::*;
+
+    #[test]
+    fn records_bit_edges()
+    {
+        let mut recorder = SoundRecorder::new();
+        recorder.record(10, 3, 0x02);
+        recorder.record(20, 3, 0x03);
+        recorder.record(30, 3, 0x01);
+        recorder.record(40, 3, 0x01);
+
+        assert_eq!(recorder.events(), &[
+            SoundEvent { cycle: 10, port: 3, bit: 1, on: true },
+            SoundEvent { cycle: 20, port: 3, bit: 0, on: true },
+            SoundEvent { cycle: 30, port: 3, bit: 1, on: false },
+        ]);
+        assert_eq!(recorder.take_events().len(), 3);
+        assert!(recorder.events().is_empty());
+    }
+
+    #[test]
+    fn invaders_shot_to_wav()
+    {
+        // MVI A,2; OUT 3; XRA A; OUT 3
+        let program = vec![0x3E, 0x02, 0xD3, 0x03, 0xAF, 0xD3, 0x03];
+        let mut cpu = Processor::from_bytes(program, 0);
+        let mut invaders = Invaders::new(DipSwitches::default());
+        for _ in 0..4
+        {
+            invaders.clock(&mut cpu);
+        }
+
+        let events = invaders.take_sound_events();
+        assert_eq!(events, vec![
+            SoundEvent { cycle: 17, port: 3, bit: 1, on: true },
+            SoundEvent { cycle: 31, port: 3, bit: 1, on: false },
+        ]);
+
+        // The shot plays to the end even though its bit went off straight away
+        let sample_rate = 8000;
+        let bank = sound_bank(None, sample_rate).unwrap();
+        let samples = render(&events, &bank, CLOCK_FREQUENCY, cpu.get_cycles(), sample_rate);
+        assert_eq!(samples.len(), 1600);
+        assert!(samples.iter().all(|sample| *sample != 0));
+
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &samples, sample_rate).unwrap();
+        assert_eq!(wav.len(), 44 + 3200);
+        let parsed = parse_wav(&wav).unwrap();
+        assert_eq!(parsed.sample_rate, sample_rate);
+        assert_eq!(parsed.samples, samples);
+
+        // Bytes 24 to 27 hold the sample rate
+        wav[24..28].copy_from_slice(&[0; 4]);
+        assert_eq!(parse_wav(&wav).err(), Some("WAV file has a sample rate of 0".to_string()));
+    }
+
+    #[test]
+    fn looped_sound_stops_with_its_bit()
+    {
+        let mut bank = SoundBank::new();
+        bank.insert(3, 0, Sound { samples: vec![100, 200], looped: true });
+        let events = [
+            SoundEvent { cycle: 100, port: 3, bit: 0, on: true },
+            SoundEvent { cycle: 500, port: 3, bit: 0, on: false },
+        ];
+
+        // One sample every 100 cycles
+        let samples = render(&events, &bank, 1000, 800, 10);
+        assert_eq!(samples, vec![0, 100, 200, 100, 200, 0, 0, 0]);
+    }
+
+    #[test]
+    fn samples_are_resampled()
+    {
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &[1, 2, 3, 4], 8000).unwrap();
+        let sound = Sound::from_wav(&wav, 4000, false).unwrap();
+        assert_eq!(sound.samples, vec![1, 3]);
+        assert!(Sound::from_wav(b"nonsense", 4000, false).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+mod tests
+{
+    use remus8080::i8080::instructions::Instruction;
+    use remus8080::symbols::*;
+
+    #[test]
+    fn reads_symbol_file_formats()
+    {
+        let text = "\
+; from the assembler and L80
+0100 START
+0109 PRINT\t0120 Loop
+
+# NAME = value lists
+BDOS = 0005H
+buffer EQU 80H
+WBOOT: equ $0000 ; warm boot
+";
+        let symbols = SymbolTable::parse(text).unwrap();
+        assert_eq!(symbols.len(), 6);
+        assert_eq!(symbols.get_address("loop"), Some(0x120));
+        assert_eq!(symbols.get_address("BDOS"), Some(5));
+        assert_eq!(symbols.get_address("BUFFER"), Some(0x80));
+        assert_eq!(symbols.get_name(0), Some("WBOOT"));
+        assert_eq!(symbols.get_name(0x101), None);
+        assert_eq!(SymbolTable::parse("0100").unwrap_err(), "line 1: 0100 is not a symbol definition");
+        assert!(SymbolTable::parse("X = 10000H").is_err());
+    }
+
+    #[test]
+    fn describes_addresses()
+    {
+        let mut symbols = SymbolTable::parse("0100 START\n0109 PRINT\n0109 ALIAS").unwrap();
+        assert_eq!(symbols.describe(0x0100).as_deref(), Some("START"));
+        assert_eq!(symbols.describe(0x010C).as_deref(), Some("ALIAS+3"));
+        assert_eq!(symbols.describe(0x00FF), None);
+        assert_eq!(symbols.describe(0x0300), None);
+        symbols.add("print", 0x200);
+        assert_eq!(symbols.get_address("PRINT"), Some(0x200));
+        assert_eq!(symbols.iter().count(), 3);
+    }
+
+    #[test]
+    fn names_branch_targets()
+    {
+        let symbols = SymbolTable::parse("0109 PRINT").unwrap();
+        let mut call = Instruction::new();
+        call.byte_to_op(0xCD, 0x09, 0x01);
+        assert_eq!(call.get_name_with_symbols(&symbols), "CALL PRINT");
+        let mut jump = Instruction::new();
+        jump.byte_to_op(0xC2, 0x0C, 0x01);
+        assert_eq!(jump.get_name_with_symbols(&symbols), "JNZ  PRINT+3");
+        jump.byte_to_op(0xC3, 0x00, 0x00);
+        assert_eq!(jump.get_name_with_symbols(&symbols), "JMP  0");
+        let mut load = Instruction::new();
+        load.byte_to_op(0x3A, 0x09, 0x01);
+        assert_eq!(load.get_name_with_symbols(&symbols), load.clone().get_name_byte().1);
+    }
+}